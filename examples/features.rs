@@ -1,8 +1,7 @@
-extern crate rustc_serialize;
+extern crate serde_json;
 extern crate telegram_bot;
 
 use telegram_bot::*;
-use rustc_serialize::json;
 
 fn main() {
     // Create bot, test simple API call and print bot information
@@ -47,7 +46,7 @@ fn main() {
                 MessageType::Location(loc) => {
                     // Print event
                     println!("<{}> is here: {}", name,
-                        json::encode(&loc).unwrap());
+                        serde_json::to_string(&loc).unwrap());
 
                     // Send chat action (this is useless here, it's just for
                     // demonstration purposes)
@@ -67,7 +66,7 @@ fn main() {
                 MessageType::Contact(c) => {
                     // Print event
                     println!("<{}> send a contact: {}", name,
-                        json::encode(&c).unwrap());
+                        serde_json::to_string(&c).unwrap());
 
                     // Just forward the contact back to the sender...
                     try!(api.forward_message(chat_id, chat_id, m.message_id));
@@ -0,0 +1,218 @@
+//! Receiving albums as a whole.
+//!
+//! Telegram sends every photo or video of an album (a media group) as a
+//! separate message, all with the same `media_group_id`.
+//! `Listener::listen_grouped` collects these messages and passes them to the
+//! handler together, once no further message of the album arrived for a
+//! short time:
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use telegram_bot::*;
+//! use telegram_bot::album::Grouped;
+//!
+//! let api = Api::from_env("TELEGRAM_BOT_TOKEN").unwrap();
+//! let mut listener = api.listener(ListeningMethod::LongPoll(None));
+//!
+//! listener.listen_grouped(Duration::from_secs(1), |event| {
+//!     match event {
+//!         Grouped::Album(album) => println!("{} photos", album.messages.len()),
+//!         Grouped::Single(update) => println!("{:?}", update),
+//!     }
+//!     Ok(ListeningAction::Continue)
+//! }).unwrap();
+//! ```
+//!
+//! Updates are still handled in the order they were received: An update
+//! after the first message of an album waits until the album is complete.
+
+use std::cmp;
+use std::collections::VecDeque;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use types::{Integer, Message, Update, UpdateId};
+use {Listener, ListeningAction, ListeningMethod, Result};
+
+/// How long to wait before polling again if no new update arrived while an
+/// album is incomplete.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// An update passed to the handler of `Listener::listen_grouped`.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Grouped {
+    /// An update which isn't part of an album.
+    Single(Update),
+    Album(Album),
+}
+
+/// The messages of an album, in the order they were received.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Album {
+    pub media_group_id: String,
+    pub messages: Vec<Message>,
+}
+
+// An update or album waiting to be handled.
+struct Pending {
+    ids: Vec<UpdateId>,
+    event: Grouped,
+    // When the album is complete, if no other message arrives
+    complete_at: Instant,
+}
+
+impl Listener {
+    /// Receive updates and handle them with the given closure, passing the
+    /// messages of an album together. An album is considered complete when
+    /// no new message of it arrived within `debounce`. See the `album`
+    /// module.
+    ///
+    /// Apart from that, this behaves like `listen`. Updates waiting for an
+    /// album to complete are not confirmed, so they are received again if
+    /// the listener stops. Because Telegram sends at most 100 unconfirmed
+    /// updates at once, messages of an album which arrive more than 99
+    /// updates after its first one are not part of it.
+    pub fn listen_grouped<H>(&mut self, debounce: Duration, mut handler: H) -> Result<()>
+        where H: FnMut(Grouped) -> Result<ListeningAction>
+    {
+        match self.method {
+            ListeningMethod::LongPoll(timeout) => {
+                try!(self.load_offset());
+                let timeout = timeout.or(Some(30));
+
+                let mut pending: VecDeque<Pending> = VecDeque::new();
+                // Updates before this id were received already. Waiting
+                // updates aren't confirmed, so they are received again.
+                let mut received_until = self.confirmed;
+
+                loop {
+                    // Don't wait longer than the first album may take
+                    let timeout = match pending.front() {
+                        Some(p) => Some(seconds_until(p.complete_at)),
+                        None => timeout,
+                    };
+                    let updates = match self.poll(self.confirmed, timeout) {
+                        Ok(Some(val)) => val,
+                        Ok(None) => break,
+                        Err(e) => {
+                            error!("{:?}", e);
+                            continue
+                        }
+                    };
+
+                    let mut received_new = false;
+                    for u in updates {
+                        if u.update_id < received_until {
+                            continue;
+                        }
+                        received_until = u.update_id.next();
+                        received_new = true;
+
+                        if try!(self.is_duplicate(u.update_id)) {
+                            debug!("Skipping already handled update {}", u.update_id);
+                            continue;
+                        }
+                        add(&mut pending, u, debounce);
+                    }
+
+                    // Telegram answers at once while there are unconfirmed
+                    // updates, so wait a bit for the album to complete.
+                    if !received_new {
+                        if let Some(p) = pending.front() {
+                            let now = Instant::now();
+                            if p.complete_at > now {
+                                thread::sleep(cmp::min(POLL_INTERVAL, p.complete_at - now));
+                            }
+                        }
+                    }
+
+                    // Handle everything up to the first incomplete album
+                    let now = Instant::now();
+                    while pending.front().map_or(false, |p| p.complete_at <= now) {
+                        let Pending { ids, event, .. } = pending.pop_front().unwrap();
+
+                        let res = handler(event);
+                        if let Err(e) = res {
+                            // Confirm the updates handled before
+                            let _ = try!(self.send_get_updates(self.confirmed, None, Some(0)));
+                            error!("{:?}", e);
+                            return Err(e);
+                        }
+
+                        if let Some(ref mut store) = self.dedup_store {
+                            for &id in &ids {
+                                try!(store.insert(id));
+                            }
+                        }
+                        // Everything before the next waiting update is handled
+                        let handled_until = pending.front()
+                            .map_or(received_until, |p| p.ids[0]);
+                        if handled_until > self.confirmed {
+                            self.confirmed = handled_until;
+                            try!(self.store_offset(handled_until));
+                        }
+
+                        if let Ok(ListeningAction::Stop) = res {
+                            let _ = try!(self.send_get_updates(self.confirmed, None, Some(0)));
+                            return Ok(());
+                        }
+                    }
+
+                    if self.shutdown.is_shutdown() {
+                        break;
+                    }
+                }
+
+                // Shutdown requested: Confirm the handled updates
+                let _ = try!(self.send_get_updates(self.confirmed, None, Some(0)));
+                Ok(())
+            }
+        }
+    }
+}
+
+// Adds the update to its album or as a new pending update.
+fn add(pending: &mut VecDeque<Pending>, update: Update, debounce: Duration) {
+    let now = Instant::now();
+    let group = update.message.as_ref().and_then(|m| m.media_group_id.clone());
+    let group = match group {
+        Some(group) => group,
+        None => {
+            pending.push_back(Pending {
+                ids: vec![update.update_id],
+                event: Grouped::Single(update),
+                complete_at: now,
+            });
+            return;
+        }
+    };
+
+    for p in pending.iter_mut() {
+        if let Grouped::Album(ref mut album) = p.event {
+            if album.media_group_id == group {
+                p.ids.push(update.update_id);
+                album.messages.push(update.message.unwrap());
+                p.complete_at = now + debounce;
+                return;
+            }
+        }
+    }
+    pending.push_back(Pending {
+        ids: vec![update.update_id],
+        event: Grouped::Album(Album {
+            media_group_id: group,
+            messages: vec![update.message.unwrap()],
+        }),
+        complete_at: now + debounce,
+    });
+}
+
+// Whole seconds (rounded up) until the given time, used as poll timeout.
+fn seconds_until(time: Instant) -> Integer {
+    let now = Instant::now();
+    if time <= now {
+        return 0;
+    }
+    let wait = time - now;
+    wait.as_secs() as Integer + if wait.subsec_nanos() > 0 { 1 } else { 0 }
+}
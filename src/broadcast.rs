@@ -0,0 +1,344 @@
+//! Sending the same message to many chats.
+//!
+//! A `Broadcast` sends a request created by a template closure to every chat
+//! in a list. It respects the rate limits (using the rate limiter of the `Api`
+//! or, if there is none, a default one), retries requests failing because of
+//! transient errors and records chats that can't be reached anymore, e.g.
+//! because the user blocked the bot.
+//!
+//! With a progress file, every handled chat is written to disk immediately. If
+//! the program crashes, running the same broadcast with the same progress
+//! file again will skip all chats which were already handled. Chats which
+//! failed with a transient error are not recorded, so they are tried again.
+//!
+//! ```no_run
+//! use telegram_bot::*;
+//! use telegram_bot::broadcast::Broadcast;
+//!
+//! let api = Api::from_env("TELEGRAM_BOT_TOKEN").unwrap();
+//! let subscribers = vec![ChatId(1234), ChatId(5678)];
+//!
+//! let summary = Broadcast::new(subscribers, |chat| {
+//!         SendMessage::new(chat, "*New release!*").parse_mode(ParseMode::Markdown)
+//!     })
+//!     .progress_file("announcement.progress")
+//!     .run(&api, |p| println!("{}/{} done", p.done, p.total))
+//!     .unwrap();
+//!
+//! for &(chat, ref failure) in &summary.failed {
+//!     println!("Could not reach {}: {}", chat, failure);
+//! }
+//! ```
+
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use ratelimit::{RateLimiter, RateLimits};
+use requests::Request;
+use types::{ChatId, ChatRef};
+use util::open_lines_for_append;
+use {Api, Error, Result};
+
+/// Why a message could not be delivered to a chat.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Failure {
+    /// The bot was blocked by the user, removed from the chat or the user was
+    /// deactivated.
+    Blocked,
+    /// The chat doesn't exist (anymore).
+    ChatNotFound,
+    /// The group was upgraded to a supergroup with the given id.
+    Migrated(ChatId),
+    /// Any other permanent error.
+    Other(String),
+    /// A transient error (e.g. a network problem) that persisted after all
+    /// retries. These chats are not written to the progress file, so a
+    /// resumed broadcast tries them again.
+    Transient(String),
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Failure::Blocked => f.write_str("blocked"),
+            Failure::ChatNotFound => f.write_str("chat not found"),
+            Failure::Migrated(id) => write!(f, "migrated to {}", id),
+            Failure::Other(ref desc) | Failure::Transient(ref desc) => f.write_str(desc),
+        }
+    }
+}
+
+/// Passed to the progress callback after every chat.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Progress {
+    /// Number of chats in this broadcast.
+    pub total: usize,
+    /// Number of chats handled so far (including chats handled before a
+    /// restart).
+    pub done: usize,
+    pub sent: usize,
+    pub failed: usize,
+}
+
+/// The result of a broadcast.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Summary {
+    /// Number of chats the message was delivered to.
+    pub sent: usize,
+    /// All chats the message could not be delivered to.
+    pub failed: Vec<(ChatId, Failure)>,
+    /// Number of chats (included in `sent` and `failed`) which were already
+    /// handled according to the progress file.
+    pub resumed: usize,
+}
+
+impl Summary {
+    fn record(&mut self, chat: ChatId, outcome: ::std::result::Result<(), Failure>) {
+        match outcome {
+            Ok(()) => self.sent += 1,
+            Err(failure) => self.failed.push((chat, failure)),
+        }
+    }
+}
+
+/// Sends one request per chat. See the module documentation for more
+/// information.
+pub struct Broadcast<F> {
+    chats: Vec<ChatId>,
+    template: F,
+    max_retries: u32,
+    retry_delay: Duration,
+    progress_file: Option<PathBuf>,
+}
+
+impl<F, R> Broadcast<F>
+    where F: FnMut(ChatId) -> R, R: Request
+{
+    /// Creates a broadcast to the given chats. `template` is called once for
+    /// every chat and has to return the request to send to it.
+    pub fn new(chats: Vec<ChatId>, template: F) -> Broadcast<F> {
+        Broadcast {
+            chats: chats,
+            template: template,
+            max_retries: 3,
+            retry_delay: Duration::from_secs(1),
+            progress_file: None,
+        }
+    }
+
+    /// How often a request failing with a transient error (e.g. a network
+    /// problem) is repeated. Waiting for the rate limiter of the `Api` (in
+    /// `RateLimitMode::Try`) doesn't count as a retry. Default: 3.
+    pub fn max_retries(mut self, retries: u32) -> Self {
+        self.max_retries = retries;
+        self
+    }
+
+    /// Time to wait before the first retry. It's doubled for every further
+    /// retry. If Telegram says how long to wait, that time is used instead.
+    /// Default: 1 second.
+    pub fn retry_delay(mut self, delay: Duration) -> Self {
+        self.retry_delay = delay;
+        self
+    }
+
+    /// Records the progress in the given file and skips all chats already
+    /// recorded there.
+    pub fn progress_file<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.progress_file = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Sends the requests and calls `on_progress` after every chat.
+    ///
+    /// Failing requests don't stop the broadcast, they are collected in the
+    /// returned `Summary`. An `Err` is only returned if the progress file
+    /// can't be read or written.
+    pub fn run<P>(mut self, api: &Api, mut on_progress: P) -> Result<Summary>
+        where P: FnMut(&Progress)
+    {
+        let mut summary = Summary::default();
+        let mut handled = HashSet::new();
+
+        // Load the results of a previous run
+        let mut file = None;
+        if let Some(ref path) = self.progress_file {
+            if path.exists() {
+                for (chat, outcome) in try!(read_progress(path)) {
+                    if handled.insert(chat) {
+                        summary.record(chat, outcome);
+                        summary.resumed += 1;
+                    }
+                }
+            }
+            // A line written partially before a crash would be merged with
+            // the next one
+            file = Some(try!(open_lines_for_append(path)));
+        }
+
+        // If the Api doesn't limit the requests, we have to do it.
+        let limiter = match api.rate_limiter() {
+            Some(_) => None,
+            None => Some(RateLimiter::new(RateLimits::default())),
+        };
+
+        let mut chats = self.chats.clone();
+        let mut seen = HashSet::new();
+        chats.retain(|chat| seen.insert(*chat));
+        let total = chats.len();
+
+        for chat in chats {
+            if handled.contains(&chat) {
+                continue;
+            }
+
+            let outcome = try!(self.send(api, limiter.as_ref(), chat));
+
+            if let Some(ref mut file) = file {
+                try!(write_progress(file, chat, &outcome));
+            }
+            handled.insert(chat);
+            summary.record(chat, outcome);
+
+            on_progress(&Progress {
+                total: total,
+                done: summary.sent + summary.failed.len(),
+                sent: summary.sent,
+                failed: summary.failed.len(),
+            });
+        }
+
+        Ok(summary)
+    }
+
+    // Sends the request to one chat, retrying on transient errors.
+    fn send(&mut self, api: &Api, limiter: Option<&RateLimiter>, chat: ChatId)
+            -> Result<::std::result::Result<(), Failure>> {
+        let mut delay = self.retry_delay;
+        let mut retries = 0;
+
+        loop {
+            let request = (self.template)(chat);
+            if let (Some(limiter), 0) = (limiter, retries) {
+                try!(limiter.acquire_many(&ChatRef::from(chat), request.message_count()));
+            }
+            let error = match api.send(request) {
+                Ok(_) => return Ok(Ok(())),
+                Err(e) => e,
+            };
+
+            let wait = match classify(error) {
+                Classified::Permanent(failure) => return Ok(Err(failure)),
+                Classified::Limited(wait) => {
+                    thread::sleep(wait);
+                    continue;
+                }
+                Classified::Transient(_, desc) if retries >= self.max_retries => {
+                    return Ok(Err(Failure::Transient(desc)));
+                }
+                Classified::Transient(wait, _) => wait.unwrap_or(delay),
+            };
+
+            debug!("Broadcast to {} failed, retrying in {:?}", chat, wait);
+            thread::sleep(wait);
+            retries += 1;
+            delay = delay.checked_mul(2).unwrap_or(delay);
+        }
+    }
+}
+
+enum Classified {
+    /// The request might succeed if repeated (maybe after the given time).
+    Transient(Option<Duration>, String),
+    /// The rate limiter of the `Api` refused to send it yet.
+    Limited(Duration),
+    Permanent(Failure),
+}
+
+fn classify(error: Error) -> Classified {
+    let desc = error.to_string();
+    match error {
+        Error::Http(_) | Error::Io(_) => Classified::Transient(None, desc),
+        Error::RateLimited(wait) => Classified::Limited(wait),
+        Error::RetryAfter(secs) => {
+            let wait = Duration::from_secs(if secs > 0 { secs as u64 } else { 0 });
+            Classified::Transient(Some(wait), desc)
+        }
+        Error::ChatMigrated(id) => Classified::Permanent(Failure::Migrated(id)),
+        Error::Api(desc) => {
+            if desc.starts_with("Forbidden") {
+                Classified::Permanent(Failure::Blocked)
+            } else if desc.contains("chat not found") {
+                Classified::Permanent(Failure::ChatNotFound)
+            } else if desc.starts_with("Bad Request") || desc.starts_with("Unauthorized") {
+                Classified::Permanent(Failure::Other(desc))
+            } else {
+                // Probably an internal server error
+                Classified::Transient(None, desc)
+            }
+        }
+        _ => Classified::Permanent(Failure::Other(desc)),
+    }
+}
+
+// ===========================================================================
+// Progress file
+// ===========================================================================
+// Every line has the form "<chat id> <outcome>", where outcome is one of
+// "sent", "blocked", "not_found", "migrated <new id>" and "failed <desc>".
+// Transient failures are not written.
+
+fn write_progress(file: &mut File, chat: ChatId,
+                  outcome: &::std::result::Result<(), Failure>) -> Result<()> {
+    let line = match *outcome {
+        Err(Failure::Transient(_)) => return Ok(()),
+        Ok(()) => format!("{} sent", chat),
+        Err(Failure::Blocked) => format!("{} blocked", chat),
+        Err(Failure::ChatNotFound) => format!("{} not_found", chat),
+        Err(Failure::Migrated(to)) => format!("{} migrated {}", chat, to),
+        Err(Failure::Other(ref desc)) => {
+            format!("{} failed {}", chat, desc.replace('\n', " "))
+        }
+    };
+
+    try!(writeln!(file, "{}", line));
+    try!(file.sync_data());
+    Ok(())
+}
+
+fn read_progress(path: &Path)
+                 -> Result<Vec<(ChatId, ::std::result::Result<(), Failure>)>> {
+    let file = BufReader::new(try!(File::open(path)));
+    let mut entries = Vec::new();
+
+    for line in file.lines() {
+        let line = try!(line);
+        let mut parts = line.splitn(3, ' ');
+        let chat = match parts.next().and_then(|id| id.parse().ok()) {
+            Some(id) => ChatId(id),
+            None => continue,
+        };
+
+        let outcome = match (parts.next(), parts.next()) {
+            (Some("sent"), None) => Ok(()),
+            (Some("blocked"), None) => Err(Failure::Blocked),
+            (Some("not_found"), None) => Err(Failure::ChatNotFound),
+            (Some("migrated"), Some(to)) => match to.parse() {
+                Ok(to) => Err(Failure::Migrated(ChatId(to))),
+                Err(_) => continue,
+            },
+            (Some("failed"), desc) => Err(Failure::Other(desc.unwrap_or("").into())),
+            // Probably the last line of a crashed run which was not written
+            // completely.
+            _ => continue,
+        };
+        entries.push((chat, outcome));
+    }
+
+    Ok(entries)
+}
@@ -0,0 +1,49 @@
+// Convenience methods on `Message` and `Chat`. They are just shortcuts for
+// the corresponding requests, so e.g. answering a message doesn't require
+// passing the chat and message id around by hand.
+
+use requests::*;
+use types::*;
+use {Api, Result};
+
+impl Message {
+    /// Answers this message with the given text (as a reply, so the original
+    /// message is quoted).
+    pub fn reply<T: Into<String>>(&self, api: &Api, text: T) -> Result<Message> {
+        api.send(SendMessage::new(self, text).reply_to(self.message_id))
+    }
+
+    /// Forwards this message to another chat.
+    pub fn forward_to<C: Into<ChatRef>>(&self, api: &Api, chat: C) -> Result<Message> {
+        api.send(ForwardMessage::new(chat, self, self.message_id))
+    }
+
+    /// Deletes this message.
+    pub fn delete(&self, api: &Api) -> Result<bool> {
+        api.send(DeleteMessage::new(self, self.message_id))
+    }
+
+    /// Replaces the text of this message. Only works for messages sent by the
+    /// bot itself.
+    pub fn edit_text<T: Into<String>>(&self, api: &Api, text: T) -> Result<Message> {
+        api.send(EditMessageText::new(self, self.message_id, text))
+    }
+}
+
+impl Chat {
+    /// Sends a text message to this chat.
+    pub fn send<T: Into<String>>(&self, api: &Api, text: T) -> Result<Message> {
+        api.send(SendMessage::new(self, text))
+    }
+
+    /// Sends the given chat action to this chat.
+    pub fn action(&self, api: &Api, action: ChatAction) -> Result<bool> {
+        api.send(SendChatAction::new(self, action))
+    }
+
+    /// Tells the users of this chat that the bot is typing. Same as
+    /// `action(api, ChatAction::Typing)`.
+    pub fn typing(&self, api: &Api) -> Result<bool> {
+        self.action(api, ChatAction::Typing)
+    }
+}
@@ -0,0 +1,188 @@
+//! Skipping updates which were handled already.
+//!
+//! Even with an `OffsetStore`, an update is delivered again if the bot crashes
+//! after the handler finished but before the new offset was saved. If a
+//! handler must never run twice for the same update (e.g. because it charges
+//! a user), a `DedupStore` can be set with `Listener::set_dedup_store`. The
+//! listener records the id of every handled update in the store and skips all
+//! updates already recorded there.
+//!
+//! For the guarantee to hold, the store must survive a crash: Use a
+//! `FileDedupStore` or implement `DedupStore` for your own database. Such an
+//! implementation can override `DedupStore::handle` to run the handler and
+//! record the update in a single transaction.
+
+use std::collections::{HashSet, VecDeque};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use types::{Update, UpdateId};
+use {ListeningAction, Result};
+
+/// Records the ids of handled updates.
+pub trait DedupStore: Send {
+    /// Returns whether the update with the given id was handled already.
+    fn contains(&mut self, id: UpdateId) -> Result<bool>;
+
+    /// Records that the update with the given id was handled.
+    fn insert(&mut self, id: UpdateId) -> Result<()>;
+
+    /// Calls the handler with the update and records the update if the
+    /// handler succeeded.
+    ///
+    /// Stores which support transactions can override this method to make
+    /// the side effects of the handler and the record atomic.
+    fn handle(&mut self, update: Update,
+              handler: &mut dyn FnMut(Update) -> Result<ListeningAction>)
+              -> Result<ListeningAction> {
+        let id = update.update_id;
+        let action = try!(handler(update));
+        try!(self.insert(id));
+        Ok(action)
+    }
+}
+
+/// Remembers the ids of the last `capacity` handled updates in memory.
+///
+/// This only prevents duplicates within one process, e.g. when a `Listener`
+/// is restarted after an error. Use `FileDedupStore` to survive crashes.
+#[derive(Debug, Clone)]
+pub struct MemoryDedupStore {
+    capacity: usize,
+    order: VecDeque<UpdateId>,
+    ids: HashSet<UpdateId>,
+}
+
+impl MemoryDedupStore {
+    pub fn new(capacity: usize) -> MemoryDedupStore {
+        MemoryDedupStore {
+            capacity: capacity,
+            order: VecDeque::new(),
+            ids: HashSet::new(),
+        }
+    }
+
+    fn add(&mut self, id: UpdateId) {
+        if !self.ids.insert(id) {
+            return;
+        }
+
+        self.order.push_back(id);
+        while self.order.len() > self.capacity {
+            if let Some(old) = self.order.pop_front() {
+                self.ids.remove(&old);
+            }
+        }
+    }
+}
+
+impl Default for MemoryDedupStore {
+    /// Remembers the last 1000 updates.
+    fn default() -> Self {
+        MemoryDedupStore::new(1000)
+    }
+}
+
+impl DedupStore for MemoryDedupStore {
+    fn contains(&mut self, id: UpdateId) -> Result<bool> {
+        Ok(self.ids.contains(&id))
+    }
+
+    fn insert(&mut self, id: UpdateId) -> Result<()> {
+        self.add(id);
+        Ok(())
+    }
+}
+
+/// Remembers the ids of the last `capacity` handled updates in a file.
+///
+/// Every id is appended (and synced to disk) before the update counts as
+/// handled. The file is compacted from time to time, so it doesn't grow
+/// without bounds.
+#[derive(Debug)]
+pub struct FileDedupStore {
+    path: PathBuf,
+    file: File,
+    lines: usize,
+    memory: MemoryDedupStore,
+}
+
+impl FileDedupStore {
+    /// Opens (or creates) the given file and loads the ids stored in it.
+    pub fn open<P: AsRef<Path>>(path: P, capacity: usize) -> Result<FileDedupStore> {
+        let path = path.as_ref().to_path_buf();
+        let mut memory = MemoryDedupStore::new(capacity);
+        let mut lines = 0;
+
+        if path.exists() {
+            let mut content = String::new();
+            try!(try!(File::open(&path)).read_to_string(&mut content));
+
+            // Only complete lines count, the last one might have been written
+            // partially before a crash.
+            let complete = content.rfind('\n').map_or("", |end| &content[..end]);
+            for line in complete.lines() {
+                if let Ok(id) = line.trim().parse() {
+                    memory.add(UpdateId(id));
+                    lines += 1;
+                }
+            }
+        }
+
+        let file = try!(OpenOptions::new().create(true).append(true).open(&path));
+        let mut store = FileDedupStore {
+            path: path,
+            file: file,
+            lines: lines,
+            memory: memory,
+        };
+
+        // Get rid of a partially written line
+        try!(store.compact());
+        Ok(store)
+    }
+
+    // Rewrites the file with only the ids still in the window.
+    fn compact(&mut self) -> Result<()> {
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+
+        {
+            let mut file = try!(File::create(&tmp));
+            for id in &self.memory.order {
+                try!(writeln!(file, "{}", id));
+            }
+            try!(file.sync_all());
+        }
+        try!(fs::rename(&tmp, &self.path));
+
+        self.file = try!(OpenOptions::new().append(true).open(&self.path));
+        self.lines = self.memory.order.len();
+        Ok(())
+    }
+}
+
+impl DedupStore for FileDedupStore {
+    fn contains(&mut self, id: UpdateId) -> Result<bool> {
+        self.memory.contains(id)
+    }
+
+    fn insert(&mut self, id: UpdateId) -> Result<()> {
+        try!(writeln!(self.file, "{}", id));
+        try!(self.file.sync_data());
+        self.memory.add(id);
+        self.lines += 1;
+
+        if self.lines > 2 * self.memory.capacity {
+            try!(self.compact());
+        }
+        Ok(())
+    }
+}
+
+// ===========================================================================
+// Unit tests
+// ===========================================================================
+#[cfg(test)]
+mod test;
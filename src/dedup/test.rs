@@ -0,0 +1,42 @@
+use super::*;
+use std::env;
+use std::fs;
+use std::process;
+use types::UpdateId;
+
+#[test]
+fn memory_window() {
+    let mut store = MemoryDedupStore::new(2);
+    store.insert(UpdateId(1)).unwrap();
+    store.insert(UpdateId(2)).unwrap();
+    store.insert(UpdateId(2)).unwrap();
+    assert!(store.contains(UpdateId(1)).unwrap());
+
+    store.insert(UpdateId(3)).unwrap();
+    assert!(!store.contains(UpdateId(1)).unwrap());
+    assert!(store.contains(UpdateId(2)).unwrap());
+    assert!(store.contains(UpdateId(3)).unwrap());
+}
+
+#[test]
+fn file_store() {
+    let path = env::temp_dir().join(format!("telegram-bot-{}.dedup", process::id()));
+    let _ = fs::remove_file(&path);
+
+    {
+        let mut store = FileDedupStore::open(&path, 3).unwrap();
+        for id in 1..10 {
+            store.insert(UpdateId(id)).unwrap();
+        }
+    }
+
+    // Simulate a crash while writing
+    OpenOptions::new().append(true).open(&path).unwrap().write_all(b"1").unwrap();
+
+    let mut store = FileDedupStore::open(&path, 3).unwrap();
+    assert!(!store.contains(UpdateId(6)).unwrap());
+    assert!(store.contains(UpdateId(7)).unwrap());
+    assert!(store.contains(UpdateId(9)).unwrap());
+    assert!(fs::read_to_string(&path).unwrap().lines().count() <= 7);
+    fs::remove_file(&path).unwrap();
+}
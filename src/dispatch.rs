@@ -0,0 +1,268 @@
+//! Handling updates concurrently.
+//!
+//! `Listener::listen` calls the handler for one update after another, so a
+//! slow handler delays the answers to all chats. `Listener::listen_concurrently`
+//! passes the updates to a pool of worker threads instead. All updates from
+//! the same chat are handled by the same worker, so they are still handled in
+//! the order they were received.
+//!
+//! An update is only confirmed to Telegram (and saved in the offset store)
+//! once its handler and the handlers of all earlier updates finished. Because
+//! Telegram sends at most 100 unconfirmed updates at once, only 99 updates
+//! after the oldest running one are handled while it is still running.
+//!
+//! ```no_run
+//! use telegram_bot::*;
+//!
+//! let api = Api::from_env("TELEGRAM_BOT_TOKEN").unwrap();
+//! let reply_api = api.clone();
+//! let mut listener = api.listener(ListeningMethod::LongPoll(None));
+//!
+//! listener.listen_concurrently(8, move |u| {
+//!     if let Some(m) = u.message {
+//!         // Takes a while, but other chats don't have to wait.
+//!         try!(m.reply(&reply_api, "Hi!"));
+//!     }
+//!     Ok(ListeningAction::Continue)
+//! }).unwrap();
+//! ```
+
+use std::cmp;
+use std::collections::BTreeSet;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use types::{Update, UpdateId};
+use {Error, Listener, ListeningAction, ListeningMethod, Result};
+
+/// How long to wait for a handler to finish before polling again while
+/// updates are being handled.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+type Completion = (UpdateId, Result<ListeningAction>);
+
+impl Listener {
+    /// Receive updates and handle them with the given closure on `workers`
+    /// threads. See the `dispatch` module.
+    ///
+    /// Like `listen`, this stops listening when a handler returns `Err` or
+    /// `Ok(ListeningAction::Stop)`, but only after all updates which are
+    /// currently handled by other workers are finished. If a handler fails,
+    /// the failed update and all later ones are not confirmed, so they are
+    /// received again the next time (use a dedup store to skip the ones
+    /// which were handled successfully).
+    ///
+    /// A dedup store set with `set_dedup_store` is only asked and updated by
+    /// the listener itself, i.e. `DedupStore::handle` isn't called.
+    pub fn listen_concurrently<H>(&mut self, workers: usize, handler: H) -> Result<()>
+        where H: Fn(Update) -> Result<ListeningAction> + Send + Sync + 'static
+    {
+        match self.method {
+            ListeningMethod::LongPoll(timeout) => {
+                try!(self.load_offset());
+                let timeout = timeout.or(Some(30));
+
+                let mut pool = WorkerPool::new(cmp::max(workers, 1), Arc::new(handler));
+                let mut state = DispatchState {
+                    dispatched: self.confirmed,
+                    running: BTreeSet::new(),
+                    failed: None,
+                    stopped: false,
+                };
+
+                while !state.stopped && state.failed.is_none() {
+                    let updates = match self.poll(self.confirmed, timeout) {
+                        Ok(Some(val)) => val,
+                        Ok(None) => break,
+                        Err(e) => {
+                            error!("{:?}", e);
+                            try!(self.wait_for_workers(&mut pool, &mut state, POLL_INTERVAL));
+                            continue
+                        }
+                    };
+
+                    let mut received_new = false;
+                    for u in updates {
+                        // Updates which are still running are received again
+                        if u.update_id < state.dispatched {
+                            continue;
+                        }
+                        state.dispatched = u.update_id.next();
+                        received_new = true;
+
+                        if try!(self.is_duplicate(u.update_id)) {
+                            continue;
+                        }
+                        state.running.insert(u.update_id);
+                        pool.dispatch(u);
+                    }
+
+                    // If nothing new arrived, the next poll would return
+                    // immediately with the running updates, so we wait a bit.
+                    let wait = if received_new || state.running.is_empty() {
+                        Duration::from_secs(0)
+                    } else {
+                        POLL_INTERVAL
+                    };
+                    try!(self.wait_for_workers(&mut pool, &mut state, wait));
+                }
+
+                // Let the workers finish the updates they already received
+                for completion in pool.shutdown() {
+                    try!(self.complete(&mut state, completion));
+                }
+                try!(self.confirm_completed(&state));
+
+                // Send a last request to confirm the handled updates
+                let _ = try!(self.send_get_updates(self.confirmed, None, Some(0)));
+
+                match state.failed {
+                    Some((_, e)) => Err(e),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+
+    // Processes all finished updates, waiting up to `wait` for the first one.
+    fn wait_for_workers(&mut self, pool: &mut WorkerPool, state: &mut DispatchState,
+                        wait: Duration) -> Result<()> {
+        let mut next = if wait > Duration::from_secs(0) && !state.running.is_empty() {
+            pool.done.recv_timeout(wait).ok()
+        } else {
+            pool.done.try_recv().ok()
+        };
+
+        while let Some(completion) = next {
+            try!(self.complete(state, completion));
+            next = pool.done.try_recv().ok();
+        }
+        self.confirm_completed(state)
+    }
+
+    fn complete(&mut self, state: &mut DispatchState, (id, res): Completion) -> Result<()> {
+        state.running.remove(&id);
+        match res {
+            Ok(action) => {
+                if let Some(ref mut store) = self.dedup_store {
+                    try!(store.insert(id));
+                }
+                if let ListeningAction::Stop = action {
+                    state.stopped = true;
+                }
+            }
+            Err(e) => {
+                error!("{:?}", e);
+                // Keep the first failed update, later ones are not confirmed
+                // anyway.
+                let earlier = state.failed.as_ref().map_or(false, |&(failed, _)| failed < id);
+                if !earlier {
+                    state.failed = Some((id, e));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Moves the offset up to the first update which is not finished.
+    fn confirm_completed(&mut self, state: &DispatchState) -> Result<()> {
+        let mut offset = state.dispatched;
+        if let Some(first) = state.running.iter().next() {
+            offset = cmp::min(offset, *first);
+        }
+        if let Some(&(failed, _)) = state.failed.as_ref() {
+            offset = cmp::min(offset, failed);
+        }
+
+        if offset > self.confirmed {
+            self.confirmed = offset;
+            try!(self.store_offset(offset));
+        }
+        Ok(())
+    }
+}
+
+struct DispatchState {
+    // All updates before this id were passed to a worker (or skipped).
+    dispatched: UpdateId,
+    running: BTreeSet<UpdateId>,
+    failed: Option<(UpdateId, Error)>,
+    stopped: bool,
+}
+
+struct WorkerPool {
+    workers: Vec<(Sender<Update>, JoinHandle<()>)>,
+    done: Receiver<Completion>,
+}
+
+impl WorkerPool {
+    fn new<H>(size: usize, handler: Arc<H>) -> WorkerPool
+        where H: Fn(Update) -> Result<ListeningAction> + Send + Sync + 'static
+    {
+        let (done_tx, done_rx) = mpsc::channel();
+        let workers = (0..size).map(|_| {
+            let (tx, rx) = mpsc::channel::<Update>();
+            let handler = handler.clone();
+            let done_tx = done_tx.clone();
+
+            let thread = thread::spawn(move || {
+                for u in rx {
+                    let id = u.update_id;
+                    let res = panic::catch_unwind(AssertUnwindSafe(|| handler(u)))
+                        .unwrap_or_else(|_| {
+                            Err(Error::InvalidState(format!("handler panicked on update {}", id)))
+                        });
+                    if done_tx.send((id, res)).is_err() {
+                        return;
+                    }
+                }
+            });
+            (tx, thread)
+        }).collect();
+
+        WorkerPool {
+            workers: workers,
+            done: done_rx,
+        }
+    }
+
+    // Passes the update to the worker responsible for its chat. Updates
+    // without a chat from the same user (answers to polls and payment
+    // queries) are kept in order, other updates are distributed evenly.
+    fn dispatch(&self, update: Update) {
+        let user = update.poll_answer.as_ref().and_then(|a| a.user.as_ref())
+            .or(update.shipping_query.as_ref().map(|q| &q.from))
+            .or(update.pre_checkout_query.as_ref().map(|q| &q.from));
+        let key = match (update.message.as_ref(), user) {
+            (Some(m), _) => m.chat.id().0,
+            (None, Some(user)) => user.id.0,
+            (None, None) => update.update_id.0,
+        };
+        let index = (key.wrapping_abs() as usize) % self.workers.len();
+
+        // Workers only stop when their channel is closed.
+        let _ = self.workers[index].0.send(update);
+    }
+
+    // Stops all workers after they handled their remaining updates and
+    // returns the results.
+    fn shutdown(self) -> Vec<Completion> {
+        let WorkerPool { workers, done } = self;
+        let mut threads = Vec::new();
+        for (tx, thread) in workers {
+            drop(tx);
+            threads.push(thread);
+        }
+
+        // Ends when all workers are gone
+        let completions = done.iter().collect();
+
+        for thread in threads {
+            let _ = thread.join();
+        }
+        completions
+    }
+}
@@ -1,6 +1,6 @@
 use std::fmt;
-use rustc_serialize::json;
 use std::env;
+use std::time::Duration;
 
 /// Telegram-Bot Result
 pub type Result<T> = ::std::result::Result<T, Error>;
@@ -12,21 +12,32 @@ pub enum Error {
     Http(::hyper::error::Error),
     /// IO related error (mainly reading the http result)
     Io(::std::io::Error),
-    /// Error while decoding JSON data
-    JsonDecode(json::DecoderError),
-    /// Error while encoding JSON data
-    JsonEncode(json::EncoderError),
+    /// Error while encoding or decoding JSON data
+    Json(::serde_json::Error),
     /// Telegram server reponsded with an error + description
     Api(String),
+    /// Telegram server asks to wait the given number of seconds before
+    /// sending the request again (flood control).
+    RetryAfter(::types::Integer),
+    /// The group was migrated to a supergroup with the given id. The request
+    /// has to be sent to the new chat.
+    ChatMigrated(::types::ChatId),
     /// This should never happen (it possibly could if the telegram servers
     /// would respond with garbage)
     InvalidState(String),
     /// Occurs, if the given bot token would not result in a valid request URL.
     InvalidTokenFormat(::url::ParseError),
+    /// The given URL (e.g. for a webhook) is not valid.
+    InvalidUrl(::url::ParseError),
     /// The given environment variable could not be fetched.
     InvalidEnvironmentVar(env::VarError),
     /// The given path is not valid.
     InvalidPath(String),
+    /// The given string is not a valid IP address range.
+    InvalidIpRange(String),
+    /// Sending the request would exceed the rate limit. Contains the time to
+    /// wait before trying again.
+    RateLimited(Duration),
 }
 
 impl ::std::error::Error for Error {
@@ -34,13 +45,17 @@ impl ::std::error::Error for Error {
         match *self {
             Error::Http(ref e) => e.description(),
             Error::Io(ref e) => e.description(),
-            Error::JsonDecode(ref e) => e.description(),
-            Error::JsonEncode(ref e) => e.description(),
+            Error::Json(ref e) => e.description(),
             Error::Api(ref s) => &s,
+            Error::RetryAfter(_) => "too many requests",
+            Error::ChatMigrated(_) => "group chat was upgraded to a supergroup chat",
             Error::InvalidState(ref s) => &s,
             Error::InvalidTokenFormat(ref e) => e.description(),
+            Error::InvalidUrl(ref e) => e.description(),
             Error::InvalidEnvironmentVar(ref e) => e.description(),
             Error::InvalidPath(ref s) => &s,
+            Error::InvalidIpRange(_) => "invalid IP address range",
+            Error::RateLimited(_) => "rate limit exceeded",
         }
     }
 }
@@ -50,13 +65,24 @@ impl fmt::Display for Error {
         match *self {
             Error::Http(ref e) => e.fmt(f),
             Error::Io(ref e) => e.fmt(f),
-            Error::JsonDecode(ref e) => e.fmt(f),
-            Error::JsonEncode(ref e) => e.fmt(f),
+            Error::Json(ref e) => e.fmt(f),
             Error::Api(ref s) => s.fmt(f),
+            Error::RetryAfter(secs) => {
+                write!(f, "too many requests, retry after {}s", secs)
+            }
+            Error::ChatMigrated(id) => {
+                write!(f, "group chat was upgraded to the supergroup chat {}", id)
+            }
             Error::InvalidState(ref s) => s.fmt(f),
             Error::InvalidTokenFormat(ref e) => e.fmt(f),
+            Error::InvalidUrl(ref e) => write!(f, "invalid URL: {}", e),
             Error::InvalidEnvironmentVar(ref e) => e.fmt(f),
             Error::InvalidPath(ref s) => s.fmt(f),
+            Error::InvalidIpRange(ref s) => write!(f, "invalid IP address range: {}", s),
+            Error::RateLimited(d) => {
+                write!(f, "rate limit exceeded, retry in {}.{:03}s",
+                       d.as_secs(), d.subsec_nanos() / 1_000_000)
+            }
         }
     }
 }
@@ -73,7 +99,6 @@ macro_rules! from_impl {
 
 from_impl!(::hyper::error::Error, Http);
 from_impl!(::std::io::Error, Io);
-from_impl!(json::DecoderError, JsonDecode);
-from_impl!(json::EncoderError, JsonEncode);
+from_impl!(::serde_json::Error, Json);
 from_impl!(::url::ParseError, InvalidTokenFormat);
 from_impl!(env::VarError, InvalidEnvironmentVar);
@@ -45,6 +45,8 @@
 //!
 //! The `Api` object has all methods of the Telegram HTTP API, like
 //! `send_message`. For more information see the `Api` struct documentation.
+//! Requests with many optional parameters are easier to build with the types
+//! in the `requests` module, which are sent via `Api::send`.
 //!
 //! Next you want to listen for new updates. This is best done via the `listen`
 //! method on the `Listener` type. To obtain a listener, call `listener` on the
@@ -61,36 +63,54 @@ extern crate log;
 
 extern crate hyper;
 extern crate hyper_rustls;
-extern crate rustc_serialize;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
 extern crate url;
 
 mod error;
 mod util;
+mod convenience;
 pub mod types;
+pub mod requests;
+pub mod ratelimit;
+pub mod broadcast;
+pub mod offset;
+pub mod dedup;
+pub mod record;
+pub mod polls;
+pub mod album;
+pub mod dispatch;
+pub mod webhook;
+#[cfg(feature = "testing")]
+pub mod testing;
 
 pub use types::*;
 pub use error::*;
-use util::Params;
+pub use requests::*;
 
-use rustc_serialize::{json, Decodable};
+use serde::de::DeserializeOwned;
+use std::cmp;
+use std::collections::{BTreeSet, VecDeque};
 use std::env;
 use std::io::Read;
-use std::sync::mpsc;
-use std::time::Duration;
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::thread;
 use hyper::{Client, Url};
 use hyper::client::IntoUrl;
 use hyper::header::{Connection, ContentType, ContentLength};
 use hyper::net::HttpsConnector;
+use url::form_urlencoded;
+use ratelimit::RateLimiter;
+use offset::OffsetStore;
+use dedup::DedupStore;
+use record::Recorder;
 
 /// API-URL prefix
 pub const API_URL : &'static str = "https://api.telegram.org/bot";
 
-// RequestType let you choose between a post request or a multipart request
-enum RequestType {
-    Post,
-}
-
 fn create_default_client() -> Client {
 
     let ssl = hyper_rustls::TlsClient::new();
@@ -101,6 +121,15 @@ fn create_default_client() -> Client {
     c
 }
 
+// A boundary for multipart bodies, which is very unlikely to appear in the
+// uploaded files.
+fn multipart_boundary() -> String {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() * 1_000_000_000 + d.subsec_nanos() as u64)
+        .unwrap_or(0);
+    format!("telegram-bot-boundary-{:x}", nanos)
+}
+
 /// Main type for sending requests to the Telegram bot API.
 ///
 /// You can create an `API` object via `from_token` or `from_env`. A `Listener`
@@ -110,6 +139,7 @@ fn create_default_client() -> Client {
 pub struct Api {
     url: Url,
     client: Client,
+    limiter: Option<RateLimiter>,
 }
 
 impl Clone for Api {
@@ -117,6 +147,7 @@ impl Clone for Api {
         Api {
             url: self.url.clone(),
             client: create_default_client(),
+            limiter: self.limiter.clone(),
         }
     }
 }
@@ -131,13 +162,23 @@ impl Api {
     /// is a valid Telegram token. You can call `get_me` to execute a test
     /// request.
     pub fn from_token(token: &str) -> Result<Api> {
-        let url = match Url::parse(&format!("{}{}/dummy", API_URL, token)) {
+        Self::from_token_and_url(token, API_URL)
+    }
+
+    /// Like `from_token`, but sends all requests to `api_url` instead of the
+    /// official `API_URL`. The token is appended directly to `api_url`, so it
+    /// should end with `/bot`, just like `API_URL` does. This is mainly useful
+    /// to talk to a local Bot API server or to the fake server in the
+    /// `testing` module.
+    pub fn from_token_and_url(token: &str, api_url: &str) -> Result<Api> {
+        let url = match Url::parse(&format!("{}{}/dummy", api_url, token)) {
             Ok(url) => url,
             Err(e) => return Err(Error::InvalidTokenFormat(e)),
         };
         Ok(Api {
             url: url,
             client: create_default_client(),
+            limiter: None,
         })
     }
 
@@ -154,88 +195,295 @@ impl Api {
     }
 
 
+    /// Sets the rate limiter for all messages sent with this `Api` (and its
+    /// clones created afterwards). `None` disables rate limiting, which is the
+    /// default. See the `ratelimit` module for more information.
+    pub fn set_rate_limiter(&mut self, limiter: Option<RateLimiter>) {
+        self.limiter = limiter;
+    }
+
+    /// Returns the current rate limiter, e.g. to `register` chats with it.
+    pub fn rate_limiter(&self) -> Option<&RateLimiter> {
+        self.limiter.as_ref()
+    }
+
+    // =======================================================================
+    // Sending typed requests
+    // =======================================================================
+    /// Sends the given request and returns the "result" of the response.
+    ///
+    /// This is the most flexible way to call the API: see the `requests`
+    /// module for all available requests and their optional parameters.
+    pub fn send<R: Request>(&self, request: R) -> Result<R::Response> {
+        if let (Some(limiter), Some(chat)) = (self.limiter.as_ref(), request.target_chat()) {
+            try!(limiter.acquire_many(chat, request.message_count()));
+        }
+
+        Self::request(&self.client, &self.url, request)
+    }
+
     // =======================================================================
     // Methods corresponding directly to a API method
     // =======================================================================
+    // These are shortcuts for `send` with the corresponding request taking all
+    // parameters positionally.
+
     /// Corresponds to the "getMe" method of the API.
     pub fn get_me(&self) -> Result<User> {
-        // Execute request with empty parameter list
-        self.send_request("getMe", Params::new(), RequestType::Post)
+        self.send(GetMe)
     }
 
     /// Corresponds to the "sendMessage" method of the API.
-    pub fn send_message(&self, chat_id: Integer, text: String,
+    ///
+    /// See `SendMessage` for a more convenient way to set the optional
+    /// parameters.
+    pub fn send_message<C: Into<ChatRef>>(&self, chat_id: C, text: String,
                         parse_mode: Option<ParseMode>,
                         disable_web_page_preview: Option<bool>,
-                        reply_to_message_id: Option<Integer>,
+                        reply_to_message_id: Option<MessageId>,
                         reply_markup: Option<ReplyMarkup>)
                         -> Result<Message> {
-        // Prepare parameters
-        let mut params = Params::new();
-        params.add_get("chat_id", chat_id);
-        params.add_get("text", text);
-        params.add_get_opt("parse_mode", parse_mode);
-        params.add_get_opt("disable_web_page_preview", disable_web_page_preview);
-        params.add_get_opt("reply_to_message_id", reply_to_message_id);
-        try!(params.add_get_json_opt("reply_markup", reply_markup));
+        let mut request = SendMessage::new(chat_id, text);
+        if let Some(mode) = parse_mode {
+            request = request.parse_mode(mode);
+        }
+        if let Some(disable) = disable_web_page_preview {
+            request = request.disable_web_page_preview(disable);
+        }
+        if let Some(id) = reply_to_message_id {
+            request = request.reply_to(id);
+        }
+        if let Some(markup) = reply_markup {
+            request = request.reply_markup(markup);
+        }
 
-        // Execute request
-        self.send_request("sendMessage", params, RequestType::Post)
+        self.send(request)
     }
 
     /// Corresponds to the "forwardMessage" method of the API.
-    pub fn forward_message(&self, chat_id: Integer, from_chat_id: Integer,
-                           message_id: Integer) -> Result<Message> {
-        // Prepare parameters
-        let mut params = Params::new();
-        params.add_get("chat_id", chat_id);
-        params.add_get("from_chat_id", from_chat_id);
-        params.add_get("message_id", message_id);
+    pub fn forward_message<C, F>(&self, chat_id: C, from_chat_id: F,
+                                 message_id: MessageId) -> Result<Message>
+        where C: Into<ChatRef>, F: Into<ChatRef>
+    {
+        self.send(ForwardMessage::new(chat_id, from_chat_id, message_id))
+    }
+
+    /// Corresponds to the "editMessageText" method of the API.
+    pub fn edit_message_text<C: Into<ChatRef>>(&self, chat_id: C,
+                                               message_id: MessageId,
+                                               text: String,
+                                               parse_mode: Option<ParseMode>,
+                                               disable_web_page_preview: Option<bool>)
+                                               -> Result<Message> {
+        let mut request = EditMessageText::new(chat_id, message_id, text);
+        if let Some(mode) = parse_mode {
+            request = request.parse_mode(mode);
+        }
+        if let Some(disable) = disable_web_page_preview {
+            request = request.disable_web_page_preview(disable);
+        }
+
+        self.send(request)
+    }
 
-        // Execute request
-        self.send_request("forwardMessage", params, RequestType::Post)
+    /// Corresponds to the "deleteMessage" method of the API.
+    pub fn delete_message<C: Into<ChatRef>>(&self, chat_id: C,
+                                            message_id: MessageId)
+                                            -> Result<bool> {
+        self.send(DeleteMessage::new(chat_id, message_id))
     }
 
     /// Corresponds to the "sendLocation" method of the API.
-    pub fn send_location(&self, chat_id: Integer, latitude: Float,
-                         longitude: Float, reply_to_message_id: Option<Integer>,
+    pub fn send_location<C: Into<ChatRef>>(&self, chat_id: C, latitude: Float,
+                         longitude: Float, reply_to_message_id: Option<MessageId>,
                          reply_markup: Option<ReplyMarkup>)
                          -> Result<Message> {
-        // Prepare parameters
-        let mut params = Params::new();
-        params.add_get("chat_id", chat_id);
-        params.add_get("latitude", latitude);
-        params.add_get("longitude", longitude);
-        params.add_get_opt("reply_to_message_id", reply_to_message_id);
-        try!(params.add_get_json_opt("reply_markup", reply_markup));
+        let mut request = SendLocation::new(chat_id, latitude, longitude);
+        if let Some(id) = reply_to_message_id {
+            request = request.reply_to(id);
+        }
+        if let Some(markup) = reply_markup {
+            request = request.reply_markup(markup);
+        }
+
+        self.send(request)
+    }
+
+    /// Corresponds to the "sendMediaGroup" method of the API.
+    pub fn send_media_group<C: Into<ChatRef>>(&self, chat_id: C, media: Vec<InputMedia>,
+                                              reply_to_message_id: Option<MessageId>)
+                                              -> Result<Vec<Message>> {
+        let mut request = SendMediaGroup::new(chat_id, media);
+        if let Some(id) = reply_to_message_id {
+            request = request.reply_to(id);
+        }
+
+        self.send(request)
+    }
+
+    /// Corresponds to the "sendPoll" method of the API.
+    ///
+    /// See `SendPoll` for quizzes and the other optional parameters.
+    pub fn send_poll<C: Into<ChatRef>>(&self, chat_id: C, question: String,
+                     options: Vec<String>, is_anonymous: Option<bool>,
+                     reply_to_message_id: Option<MessageId>,
+                     reply_markup: Option<ReplyMarkup>)
+                     -> Result<Message> {
+        let mut request = SendPoll::new(chat_id, question, options);
+        if let Some(anonymous) = is_anonymous {
+            request = request.anonymous(anonymous);
+        }
+        if let Some(id) = reply_to_message_id {
+            request = request.reply_to(id);
+        }
+        if let Some(markup) = reply_markup {
+            request = request.reply_markup(markup);
+        }
+
+        self.send(request)
+    }
+
+    /// Corresponds to the "stopPoll" method of the API.
+    pub fn stop_poll<C: Into<ChatRef>>(&self, chat_id: C, message_id: MessageId)
+                     -> Result<Poll> {
+        self.send(StopPoll::new(chat_id, message_id))
+    }
+
+    /// Corresponds to the "sendInvoice" method of the API.
+    ///
+    /// Use `STARS` as currency for payments in Telegram Stars. See
+    /// `SendInvoice` for the provider token and the other optional
+    /// parameters.
+    pub fn send_invoice<C: Into<ChatRef>>(&self, chat_id: C, title: String,
+                        description: String, payload: String, currency: String,
+                        prices: Vec<LabeledPrice>,
+                        reply_to_message_id: Option<MessageId>,
+                        reply_markup: Option<ReplyMarkup>)
+                        -> Result<Message> {
+        let mut request = SendInvoice::new(chat_id, title, description, payload,
+                                           currency, prices);
+        if let Some(id) = reply_to_message_id {
+            request = request.reply_to(id);
+        }
+        if let Some(markup) = reply_markup {
+            request = request.reply_markup(markup);
+        }
+
+        self.send(request)
+    }
+
+    /// Corresponds to the "createInvoiceLink" method of the API.
+    ///
+    /// See `CreateInvoiceLink` for the optional parameters.
+    pub fn create_invoice_link(&self, title: String, description: String,
+                               payload: String, currency: String,
+                               prices: Vec<LabeledPrice>)
+                               -> Result<String> {
+        self.send(CreateInvoiceLink::new(title, description, payload, currency, prices))
+    }
+
+    /// Corresponds to the "answerShippingQuery" method of the API.
+    ///
+    /// Pass `Ok` with the available shipping options or `Err` with a message
+    /// explaining why the order can't be shipped.
+    pub fn answer_shipping_query(&self, shipping_query_id: String,
+                                 answer: ::std::result::Result<Vec<ShippingOption>, String>)
+                                 -> Result<bool> {
+        self.send(match answer {
+            Ok(options) => AnswerShippingQuery::ok(shipping_query_id, options),
+            Err(message) => AnswerShippingQuery::error(shipping_query_id, message),
+        })
+    }
+
+    /// Corresponds to the "answerPreCheckoutQuery" method of the API.
+    ///
+    /// Pass `Ok` to accept the order or `Err` with a message explaining why
+    /// it can't be completed.
+    pub fn answer_pre_checkout_query(&self, pre_checkout_query_id: String,
+                                     answer: ::std::result::Result<(), String>)
+                                     -> Result<bool> {
+        self.send(match answer {
+            Ok(()) => AnswerPreCheckoutQuery::ok(pre_checkout_query_id),
+            Err(message) => AnswerPreCheckoutQuery::error(pre_checkout_query_id, message),
+        })
+    }
+
+    /// Corresponds to the "refundStarPayment" method of the API.
+    pub fn refund_star_payment(&self, user_id: UserId, telegram_payment_charge_id: String)
+                               -> Result<bool> {
+        self.send(RefundStarPayment::new(user_id, telegram_payment_charge_id))
+    }
+
+    /// Corresponds to the "getStickerSet" method of the API.
+    pub fn get_sticker_set(&self, name: String) -> Result<StickerSet> {
+        self.send(GetStickerSet::new(name))
+    }
 
-        // Execute request
-        self.send_request("sendLocation", params, RequestType::Post)
+    /// Corresponds to the "uploadStickerFile" method of the API.
+    pub fn upload_sticker_file(&self, user_id: UserId, sticker: InputFile,
+                               sticker_format: StickerFormat)
+                               -> Result<File> {
+        self.send(UploadStickerFile::new(user_id, sticker, sticker_format))
+    }
+
+    /// Corresponds to the "createNewStickerSet" method of the API.
+    ///
+    /// See `CreateNewStickerSet` for masks and custom emoji.
+    pub fn create_new_sticker_set(&self, user_id: UserId, name: String, title: String,
+                                  stickers: Vec<InputSticker>)
+                                  -> Result<bool> {
+        self.send(CreateNewStickerSet::new(user_id, name, title, stickers))
+    }
+
+    /// Corresponds to the "addStickerToSet" method of the API.
+    pub fn add_sticker_to_set(&self, user_id: UserId, name: String, sticker: InputSticker)
+                              -> Result<bool> {
+        self.send(AddStickerToSet::new(user_id, name, sticker))
+    }
+
+    /// Corresponds to the "setStickerPositionInSet" method of the API.
+    pub fn set_sticker_position_in_set(&self, sticker: FileId, position: Integer)
+                                       -> Result<bool> {
+        self.send(SetStickerPositionInSet::new(sticker, position))
+    }
+
+    /// Corresponds to the "deleteStickerFromSet" method of the API.
+    pub fn delete_sticker_from_set(&self, sticker: FileId) -> Result<bool> {
+        self.send(DeleteStickerFromSet::new(sticker))
+    }
+
+    /// Corresponds to the "setStickerSetThumbnail" method of the API.
+    pub fn set_sticker_set_thumbnail(&self, name: String, user_id: UserId,
+                                     thumbnail: Option<FileSource>,
+                                     format: StickerFormat)
+                                     -> Result<bool> {
+        let mut request = SetStickerSetThumbnail::new(name, user_id, format);
+        if let Some(thumbnail) = thumbnail {
+            request = request.thumbnail(thumbnail);
+        }
+        self.send(request)
     }
 
     /// Corresponds to the "sendChatAction" method of the API.
-    pub fn send_chat_action(&self, chat_id: Integer, action: ChatAction)
+    pub fn send_chat_action<C: Into<ChatRef>>(&self, chat_id: C, action: ChatAction)
                             -> Result<bool> {
-        let mut params = Params::new();
-        params.add_get("chat_id", chat_id);
-        params.add_get("action", action);
-
-        // Execute request
-        self.send_request("sendChatAction", params, RequestType::Post)
+        self.send(SendChatAction::new(chat_id, action))
     }
 
     /// Corresponds to the "getUserProfilePhotos" method of the API.
-    pub fn get_user_profile_photos(&self, user_id: Integer,
+    pub fn get_user_profile_photos(&self, user_id: UserId,
                                    offset: Option<Integer>,
                                    limit: Option<Integer>)
                                    -> Result<UserProfilePhotos> {
-        let mut params = Params::new();
-        params.add_get("user_id", user_id);
-        params.add_get_opt("offset", offset);
-        params.add_get_opt("limit", limit);
+        let mut request = GetUserProfilePhotos::new(user_id);
+        if let Some(offset) = offset {
+            request = request.offset(offset);
+        }
+        if let Some(limit) = limit {
+            request = request.limit(limit);
+        }
 
-        // Execute request
-        self.send_request("getUserProfilePhotos", params, RequestType::Post)
+        self.send(request)
     }
 
     /// Corresponds to the "getUpdates" method of the API.
@@ -243,34 +491,45 @@ impl Api {
     /// **Note:**
     /// The method will not set the offset parameter on its own. To receive
     /// updates in a more high level way, see `listener`.
-    pub fn get_updates(&self, offset: Option<Integer>,
+    pub fn get_updates(&self, offset: Option<UpdateId>,
                        limit: Option<Integer>, timeout: Option<Integer>)
                        -> Result<Vec<Update>> {
-        // Prepare parameters
-        let mut params = Params::new();
-        params.add_get_opt("offset", offset);
-        params.add_get_opt("limit", limit);
-        params.add_get_opt("timeout", timeout);
+        let mut request = GetUpdates::new();
+        if let Some(offset) = offset {
+            request = request.offset(offset);
+        }
+        if let Some(limit) = limit {
+            request = request.limit(limit);
+        }
+        if let Some(timeout) = timeout {
+            request = request.timeout(timeout);
+        }
 
-        // Execute request
-        self.send_request("getUpdates", params, RequestType::Post)
+        self.send(request)
     }
 
-    /// Corresponds to the `setWebhook` method of the API.
+    /// Corresponds to the `setWebhook` method of the API. Use `SetWebhook`
+    /// for more options, like uploading a self-signed certificate.
     ///
     /// **Note:**
-    /// This library does not yet offer the feature to listen via webhook. This
-    /// is just the raw telegram API request and will do nothing more. Use only
-    /// if you know what you're doing.
+    /// This is just the raw telegram API request and will do nothing more.
+    /// Use a `webhook::WebhookServer` to receive the updates.
     pub fn set_webhook<U: IntoUrl>(&self, url: Option<U>) -> Result<bool> {
-        let u = url.map_or("".into(), |u| u.into_url().unwrap().to_string());
+        self.send(match url {
+            Some(u) => SetWebhook::new(try!(u.into_url().map_err(Error::InvalidUrl))),
+            None => SetWebhook::remove(),
+        })
+    }
 
-        // Prepare parameters
-        let mut params = Params::new();
-        params.add_get("url", u);
+    /// Corresponds to the `deleteWebhook` method of the API. Pending updates
+    /// are kept, use `DeleteWebhook` to drop them.
+    pub fn delete_webhook(&self) -> Result<bool> {
+        self.send(DeleteWebhook::new())
+    }
 
-        // Execute request
-        self.send_request("setWebhook", params, RequestType::Post)
+    /// Corresponds to the `getWebhookInfo` method of the API.
+    pub fn get_webhook_info(&self) -> Result<WebhookInfo> {
+        self.send(GetWebhookInfo)
     }
 
     // =======================================================================
@@ -319,9 +578,15 @@ impl Api {
     pub fn listener(&self, method: ListeningMethod) -> Listener {
         Listener {
             method: method,
-            confirmed: 0,
+            confirmed: UpdateId(0),
             url: self.url.clone(),
-            client: create_default_client()
+            client: Arc::new(create_default_client()),
+            shutdown: ShutdownHandle::default(),
+            allowed_updates: None,
+            offset_store: None,
+            dedup_store: None,
+            recorder: None,
+            poller: None,
         }
     }
 
@@ -329,19 +594,15 @@ impl Api {
     // Private methods
     // =======================================================================
 
-    fn send_request<T: Decodable>(&self, method: &str,
-                                  p: Params, typ: RequestType) -> Result<T> {
-        Self::request(&self.client, &self.url, method, p, typ)
-    }
+    fn request<R: Request>(client: &Client, url: &Url, request: R)
+                           -> Result<R::Response> {
+        let mut params = Params::new();
+        try!(request.params(&mut params));
 
-    fn request<T: Decodable>(client: &Client, url: &Url,
-                             method: &str, p: Params, typ: RequestType) -> Result<T> {
-        match typ {
-            RequestType::Post => Self::post_request(client, url, method, p),
-        }
+        Self::post_request(client, url, request.method(), params)
     }
 
-    fn post_request<T: Decodable>(client: &Client, url: &Url,
+    fn post_request<T: DeserializeOwned>(client: &Client, url: &Url,
                                   method: &str, p: Params) -> Result<T> {
         // Prepare URL for request: Clone and change the last path fragment
         // to the method name and append GET parameters.
@@ -351,18 +612,25 @@ impl Api {
             segments_mut.pop().push(method.into()); // Change last into method name
         }
 
-        // Change the parameters to a well formed url-encoded string.
-        // Change connect("&") to join("&") when rust 1.3 becomes stable
-        let bodyparams = p.get_params().into_iter().map(|&(k, ref  v)| {
-            format!("{}={}", k, &**v)
-        }).collect::<Vec<_>>().join("&");
+        // Requests with files have to be sent as multipart, all others
+        // are sent as a well formed url-encoded string.
+        let (bodyparams, content_type) = if p.get_files().is_empty() {
+            let body = form_urlencoded::Serializer::new(String::new())
+                .extend_pairs(p.get_params().iter().map(|&(k, ref v)| (k, &**v)))
+                .finish();
+            (body.into_bytes(), ContentType::form_url_encoded())
+        } else {
+            let boundary = multipart_boundary();
+            let mime = format!("multipart/form-data; boundary={}", boundary);
+            (p.to_multipart(&boundary), ContentType(mime.parse().unwrap()))
+        };
 
         // Create the request with the body and headers
         let req = client
             .post(url)
-            .body(&*bodyparams)
+            .body(&bodyparams[..])
             .header(Connection::close())
-            .header(ContentType::form_url_encoded())
+            .header(content_type)
             .header(ContentLength(bodyparams.len() as u64));
 
         // Send request and check if it failed
@@ -373,7 +641,19 @@ impl Api {
         try!(resp.read_to_string(&mut body));
 
         // Try to decode response as JSON representing a Response
-        match try!(json::decode(&body)) {
+        match try!(serde_json::from_str(&body)) {
+            // Errors with additional parameters get their own error variants
+            // so they can be handled easily.
+            Response { ok: false, parameters: Some(ResponseParameters {
+                retry_after: Some(secs), ..
+            }), ..} => {
+                Err(Error::RetryAfter(secs))
+            },
+            Response { ok: false, parameters: Some(ResponseParameters {
+                migrate_to_chat_id: Some(id), ..
+            }), ..} => {
+                Err(Error::ChatMigrated(id))
+            },
             // If the response says that there was an error: Return API-Error
             // with the given description.
             Response { ok: false, description: Some(desc), ..} => {
@@ -392,8 +672,8 @@ impl Api {
 }
 
 /// Different method how to listen for new updates. Currently `LongPoll` is
-/// the only method supported by this library. The Telegram API offers a
-/// webhook method which is not yet implemented here.
+/// the only method supported by `Listener`. To receive updates via webhook,
+/// use the `webhook` module.
 pub enum ListeningMethod {
     LongPoll(Option<Integer>),
 }
@@ -417,21 +697,220 @@ pub enum ListeningAction {
 /// it's usually sufficient for any purpose to create a `Listener` only once.
 pub struct Listener {
     method: ListeningMethod,
-    confirmed: Integer,
+    confirmed: UpdateId,
     url: Url,
-    client: Client,
+    client: Arc<Client>,
+    shutdown: ShutdownHandle,
+    allowed_updates: Option<BTreeSet<AllowedUpdate>>,
+    offset_store: Option<Box<dyn OffsetStore>>,
+    dedup_store: Option<Box<dyn DedupStore>>,
+    recorder: Option<Recorder>,
+    poller: Option<Poller>,
 }
 
 
 impl Listener {
+    /// Only receive updates of the given kinds, see
+    /// `GetUpdates::allowed_updates`. The kinds are not derived from what
+    /// the handler does with the updates, so they have to include every
+    /// kind the handler expects.
+    pub fn set_allowed_updates<'a, I>(&mut self, allowed: I)
+        where I: IntoIterator<Item = &'a AllowedUpdate>
+    {
+        self.allowed_updates = Some(allowed.into_iter().cloned().collect());
+    }
+
+    /// Sets a store to persist the update offset. The offset is loaded when
+    /// listening starts and saved after every handled update, so a restarted
+    /// bot continues exactly after the last handled update. See the `offset`
+    /// module.
+    pub fn set_offset_store<S: OffsetStore + 'static>(&mut self, store: S) {
+        self.offset_store = Some(Box::new(store));
+    }
+
+    /// Sets a store to record the ids of handled updates. Updates recorded
+    /// there are skipped, so the handler is never called twice for the same
+    /// update, even after a crash. See the `dedup` module.
+    pub fn set_dedup_store<S: DedupStore + 'static>(&mut self, store: S) {
+        self.dedup_store = Some(Box::new(store));
+    }
+
+    /// Records all received updates, to replay them later. See the `record`
+    /// module.
+    pub fn set_recorder(&mut self, recorder: Recorder) {
+        self.recorder = Some(recorder);
+    }
+
+    fn is_duplicate(&mut self, id: UpdateId) -> Result<bool> {
+        match self.dedup_store {
+            Some(ref mut store) => store.contains(id),
+            None => Ok(false),
+        }
+    }
+
+    // Calls the handler unless the update was handled already.
+    fn handle<H>(&mut self, update: Update, handler: &mut H) -> Result<ListeningAction>
+        where H: FnMut(Update) -> Result<ListeningAction>
+    {
+        let store = match self.dedup_store {
+            Some(ref mut store) => store,
+            None => return handler(update),
+        };
+
+        if try!(store.contains(update.update_id)) {
+            debug!("Skipping already handled update {}", update.update_id);
+            return Ok(ListeningAction::Continue);
+        }
+        store.handle(update, handler)
+    }
+
+    // Continues after the stored offset, if it's newer than our own.
+    fn load_offset(&mut self) -> Result<()> {
+        if let Some(ref mut store) = self.offset_store {
+            if let Some(offset) = try!(store.load()).map(UpdateId) {
+                if offset > self.confirmed {
+                    self.confirmed = offset;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn store_offset(&mut self, offset: UpdateId) -> Result<()> {
+        match self.offset_store {
+            Some(ref mut store) => store.store(offset.0),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns a handle to stop this listener from another thread.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    /// Consumes `self` and listens in a new thread. The returned
+    /// `ListenerThread` can be used to stop the listener and to get the
+    /// result of `listen`.
+    pub fn spawn<H>(mut self, handler: H) -> ListenerThread
+        where H: FnMut(Update) -> Result<ListeningAction> + Send + 'static
+    {
+        let shutdown = self.shutdown_handle();
+        let thread = thread::spawn(move || self.listen(handler));
+
+        ListenerThread {
+            shutdown: shutdown,
+            thread: thread,
+        }
+    }
 
-    fn send_get_updates(&self, offset: Integer, timeout: Option<Integer>, limit: Option<Integer>)
+    fn get_updates_request(&self, offset: UpdateId, timeout: Option<Integer>,
+                           limit: Option<Integer>) -> GetUpdates {
+        let mut request = GetUpdates::new().offset(offset);
+        if let Some(ref allowed) = self.allowed_updates {
+            request = request.allowed_updates(allowed);
+        }
+        if let Some(timeout) = timeout {
+            request = request.timeout(timeout);
+        }
+        if let Some(limit) = limit {
+            request = request.limit(limit);
+        }
+        request
+    }
+
+    fn send_get_updates(&self, offset: UpdateId, timeout: Option<Integer>,
+                        limit: Option<Integer>)
                         -> Result<Vec<Update>> {
-        let mut params = Params::new();
-        params.add_get("offset", offset);
-        params.add_get_opt("timeout", timeout);
-        params.add_get_opt("limit", limit);
-        Api::request(&self.client, &self.url, "getUpdates", params, RequestType::Post)
+        let request = self.get_updates_request(offset, timeout, limit);
+        Api::request(&self.client, &self.url, request)
+    }
+
+    // Receives updates like `send_get_updates`, but returns `None` as soon as
+    // a shutdown is requested. The request can't be cancelled, so it's sent
+    // from the poll thread of the listener, which finishes it in the
+    // background in this case.
+    fn poll(&mut self, offset: UpdateId, timeout: Option<Integer>)
+            -> Result<Option<Vec<Update>>> {
+        let request = GetRawUpdates(self.get_updates_request(offset, timeout, None));
+        if self.poller.is_none() {
+            self.poller = Some(Poller::start(self.client.clone(), self.url.clone()));
+        }
+        let event = {
+            let poller = self.poller.as_mut().unwrap();
+            if !self.shutdown.register(poller.events_tx.clone()) {
+                return Ok(None);
+            }
+            let event = poller.request(request);
+            self.shutdown.unregister();
+            event
+        };
+
+        match event {
+            PollEvent::Updates(_, res) => {
+                let updates = try!(res);
+                if let Some(ref mut recorder) = self.recorder {
+                    for u in &updates {
+                        try!(recorder.record(u));
+                    }
+                }
+
+                let mut decoded = Vec::with_capacity(updates.len());
+                for u in updates {
+                    decoded.push(try!(serde_json::from_value(u)));
+                }
+                Ok(Some(decoded))
+            }
+            PollEvent::Shutdown => Ok(None),
+        }
+    }
+
+    /// Returns an iterator over new updates, an alternative to `listen`.
+    ///
+    /// An update counts as handled when the next one is requested. To stop
+    /// listening, leave the loop: The last update is then not handled (like
+    /// returning an `Err` from a `listen` handler), unless `Updates::confirm`
+    /// was called (like returning `Ok(ListeningAction::Stop)`). Handled
+    /// updates are confirmed to Telegram when the iterator is dropped.
+    ///
+    /// Errors while receiving updates are returned by the iterator; calling
+    /// `next` again tries again. The iterator ends after a shutdown (see
+    /// `shutdown_handle`).
+    ///
+    /// ```no_run
+    /// # use telegram_bot::*;
+    /// # fn run() -> Result<()> {
+    /// let api = try!(Api::from_env("TELEGRAM_BOT_TOKEN"));
+    /// let mut listener = api.listener(ListeningMethod::LongPoll(None));
+    /// let mut updates = listener.updates();
+    ///
+    /// while let Some(update) = updates.next() {
+    ///     let update = try!(update);
+    ///     if let Some(m) = update.message {
+    ///         try!(m.reply(&api, "Hi!"));
+    ///         if let MessageType::Text(ref text) = m.msg {
+    ///             if text == "/quit" {
+    ///                 try!(updates.confirm());
+    ///                 break;
+    ///             }
+    ///         }
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn updates<'a>(&'a mut self) -> Updates<'a> {
+        let timeout = match self.method {
+            ListeningMethod::LongPoll(timeout) => timeout.or(Some(30)),
+        };
+
+        Updates {
+            handled_until: self.confirmed,
+            listener: self,
+            timeout: timeout,
+            buffer: VecDeque::new(),
+            current: None,
+            loaded: false,
+        }
     }
 
     /// Receive and handle updates with the given closure.
@@ -452,12 +931,16 @@ impl Listener {
     /// **Note:**
     /// If you are listening via `LongPoll` method and your handler panics or
     /// the program is aborted in an abnormal way (e.g. `SIGKILL`), the handler
-    /// might receive some already handled updates a second time.
+    /// might receive some already handled updates a second time. Use an
+    /// offset store (see `set_offset_store`) to make this less likely and a
+    /// dedup store (see `set_dedup_store`) to prevent it completely.
     pub fn listen<H>(&mut self, mut handler: H) -> Result<()>
         where H: FnMut(Update) -> Result<ListeningAction>
     {
         match self.method {
             ListeningMethod::LongPoll(timeout) => {
+                try!(self.load_offset());
+
                 // `handled_until` will hold the id of the last handled update
                 let mut handled_until = self.confirmed;
 
@@ -467,8 +950,14 @@ impl Listener {
                 loop {
                     // Receive updates with correct offset. We don't specify a
                     // limit (Telegram limits to 100 automatically).
-                    let updates = match self.send_get_updates(handled_until, timeout, None) {
-                        Ok(val) => val,
+                    let updates = match self.poll(handled_until, timeout) {
+                        Ok(Some(val)) => val,
+                        Ok(None) => {
+                            // Shutdown requested: Confirm the handled updates
+                            let _ = try!(self.send_get_updates(handled_until, None, Some(0)));
+                            self.confirmed = handled_until;
+                            return Ok(());
+                        }
                         Err(e) => {
                             // TODO Add better logic here to distinguish between
                             //      transient and persistent errors.
@@ -485,7 +974,7 @@ impl Listener {
                         let update_id = u.update_id;
 
                         // Execute the handler and save it's result.
-                        let res = handler(u);
+                        let res = self.handle(u, &mut handler);
                         // If an error was returned: Confirm the update before
                         // (if necessary) and return the given error.
                         if let Err(e) = res {
@@ -502,12 +991,17 @@ impl Listener {
                         // The update is now considered "handled". The
                         // if-condition should always be true.
                         if update_id >= handled_until {
-                            handled_until = update_id + 1;
+                            handled_until = update_id.next();
+                            try!(self.store_offset(handled_until));
                         }
 
-                        // If an Ok(Stop) was returned, stop listening now with
-                        // confirmed update.
-                        if let Ok(ListeningAction::Stop) = res {
+                        // If an Ok(Stop) was returned or a shutdown was
+                        // requested, stop listening now with confirmed update.
+                        let stop = match res {
+                            Ok(ListeningAction::Stop) => true,
+                            _ => self.shutdown.is_shutdown(),
+                        };
+                        if stop {
                             // Send a last request to confirm already handled
                             // updates.
                             // We don't specify a timeout (Telegram timeout 0 seconds by default)
@@ -526,6 +1020,8 @@ impl Listener {
     /// Consumes `self` and returns a sender-receiver pair. You can receive
     /// new updates through the Receiver. Each update needs to be confirmed
     /// with a `Result<ListeningAction>` before the next update can be handled.
+    /// When the listener stops, the receiver gets `ListenerEvent::Stopped` or
+    /// `ListenerEvent::Error` with the error `listen` returned.
     ///
     /// This means that handling updates isn't done in parallel. The only
     /// advantage of this function over the `listen` function is that you can
@@ -536,26 +1032,286 @@ impl Listener {
     /// **Note:** Remember to send a result through the `Sender` after each
     /// update!
     pub fn channel(mut self)
-        -> (mpsc::Sender<Result<ListeningAction>>, mpsc::Receiver<Update>)
+        -> (mpsc::Sender<Result<ListeningAction>>, mpsc::Receiver<ListenerEvent>)
     {
         // Create channels for sending updates and handle result
         let (update_tx, update_rx) = mpsc::channel();
         let (res_tx, res_rx) = mpsc::channel();
 
-        // Listen for new updates in a new thread. Sadly we cannot easily
-        // return the result of `listen`, so we just discard it.
+        // Listen for new updates in a new thread and report the result when
+        // listening stops.
         thread::spawn(move || {
-            let _ = self.listen(|u| {
+            let res = self.listen(|u| {
                 // Send received update and return if the receiver hung up.
-                if let Err(_) = update_tx.send(u) {
+                if let Err(_) = update_tx.send(ListenerEvent::Update(u)) {
                     return Ok(ListeningAction::Stop);
                 }
 
                 // Receive handle result. If the channel hung up: Stop.
                 res_rx.recv().unwrap_or(Ok(ListeningAction::Stop))
             });
+
+            let _ = update_tx.send(match res {
+                Ok(()) => ListenerEvent::Stopped,
+                Err(e) => ListenerEvent::Error(e),
+            });
         });
 
         (res_tx, update_rx)
     }
 }
+
+/// Iterator over new updates, see `Listener::updates`.
+pub struct Updates<'a> {
+    listener: &'a mut Listener,
+    timeout: Option<Integer>,
+    buffer: VecDeque<Update>,
+    // The update returned last, if it's not handled yet.
+    current: Option<UpdateId>,
+    handled_until: UpdateId,
+    loaded: bool,
+}
+
+impl<'a> Updates<'a> {
+    /// Marks the update returned last as handled and confirms all handled
+    /// updates to Telegram right away.
+    pub fn confirm(&mut self) -> Result<()> {
+        try!(self.mark_handled());
+        let _ = try!(self.listener.send_get_updates(self.handled_until, None, Some(0)));
+        self.listener.confirmed = self.handled_until;
+        Ok(())
+    }
+
+    fn mark_handled(&mut self) -> Result<()> {
+        if let Some(id) = self.current {
+            if let Some(ref mut store) = self.listener.dedup_store {
+                try!(store.insert(id));
+            }
+            try!(self.skip(id));
+        }
+        Ok(())
+    }
+
+    fn skip(&mut self, id: UpdateId) -> Result<()> {
+        self.current = None;
+        if id >= self.handled_until {
+            self.handled_until = id.next();
+            try!(self.listener.store_offset(self.handled_until));
+        }
+        Ok(())
+    }
+
+    fn next_update(&mut self) -> Result<Option<Update>> {
+        if !self.loaded {
+            try!(self.listener.load_offset());
+            self.handled_until = cmp::max(self.handled_until, self.listener.confirmed);
+            self.loaded = true;
+        }
+        try!(self.mark_handled());
+
+        loop {
+            while let Some(u) = self.buffer.pop_front() {
+                let id = u.update_id;
+                if try!(self.listener.is_duplicate(id)) {
+                    debug!("Skipping already handled update {}", id);
+                    try!(self.skip(id));
+                } else {
+                    self.current = Some(id);
+                    return Ok(Some(u));
+                }
+            }
+
+            match try!(self.listener.poll(self.handled_until, self.timeout)) {
+                Some(updates) => {
+                    self.listener.confirmed = self.handled_until;
+                    self.buffer.extend(updates);
+                }
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for Updates<'a> {
+    type Item = Result<Update>;
+
+    fn next(&mut self) -> Option<Result<Update>> {
+        match self.next_update() {
+            Ok(Some(u)) => Some(Ok(u)),
+            Ok(None) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+impl<'a> Drop for Updates<'a> {
+    fn drop(&mut self) {
+        // Confirm the handled updates. We can't report errors here, but the
+        // worst case is that some updates are received again.
+        if self.handled_until > self.listener.confirmed {
+            if self.listener.send_get_updates(self.handled_until, None, Some(0)).is_ok() {
+                self.listener.confirmed = self.handled_until;
+            }
+        }
+    }
+}
+
+/// Sent by the thread created by `Listener::channel`.
+#[derive(Debug)]
+pub enum ListenerEvent {
+    /// A new update. Send a result through the `Sender` after handling it.
+    Update(Update),
+    /// Listening stopped because of an error (e.g. returned by the handler).
+    /// Nothing is sent afterwards.
+    Error(Error),
+    /// Listening stopped regularly. Nothing is sent afterwards.
+    Stopped,
+}
+
+/// Stops a `Listener` from another thread. Cloning a `ShutdownHandle` is
+/// cheap; all clones stop the same listener.
+///
+/// A running poll is interrupted immediately, an update which is currently
+/// handled is finished first. The handled updates are confirmed before
+/// `listen` returns `Ok(())`. A listener which was shut down stops again
+/// immediately when it's used for listening later.
+#[derive(Clone, Default)]
+pub struct ShutdownHandle {
+    state: Arc<Mutex<ShutdownState>>,
+}
+
+#[derive(Default)]
+struct ShutdownState {
+    requested: bool,
+    // Wakes up the listener while it's waiting for updates.
+    poll: Option<mpsc::Sender<PollEvent>>,
+}
+
+enum PollEvent {
+    // The answer to the request with the given number.
+    Updates(u64, Result<Vec<serde_json::Value>>),
+    Shutdown,
+}
+
+// The thread sending the getUpdates requests of a `Listener`. It ends when
+// the listener is dropped, after finishing the request it's sending.
+struct Poller {
+    requests: mpsc::Sender<(u64, GetRawUpdates)>,
+    events: mpsc::Receiver<PollEvent>,
+    events_tx: mpsc::Sender<PollEvent>,
+    // Number of the last request
+    sent: u64,
+}
+
+impl Poller {
+    fn start(client: Arc<Client>, url: Url) -> Poller {
+        let (requests_tx, requests) = mpsc::channel::<(u64, GetRawUpdates)>();
+        let (events_tx, events) = mpsc::channel();
+        let tx = events_tx.clone();
+        thread::spawn(move || {
+            for (number, request) in requests {
+                let res = Api::request(&client, &url, request);
+                if tx.send(PollEvent::Updates(number, res)).is_err() {
+                    return;
+                }
+            }
+        });
+
+        Poller {
+            requests: requests_tx,
+            events: events,
+            events_tx: events_tx,
+            sent: 0,
+        }
+    }
+
+    // Sends the request and waits for its answer or a shutdown. Answers to
+    // earlier requests, which were interrupted by a shutdown, are skipped.
+    fn request(&mut self, request: GetRawUpdates) -> PollEvent {
+        self.sent += 1;
+        if self.requests.send((self.sent, request)).is_err() {
+            let e = Error::InvalidState("poll thread stopped".into());
+            return PollEvent::Updates(self.sent, Err(e));
+        }
+
+        loop {
+            match self.events.recv() {
+                Ok(PollEvent::Updates(number, res)) => {
+                    if number == self.sent {
+                        return PollEvent::Updates(number, res);
+                    }
+                }
+                Ok(PollEvent::Shutdown) | Err(_) => return PollEvent::Shutdown,
+            }
+        }
+    }
+}
+
+// `GetUpdates` returning the updates as JSON, so they can be recorded
+// exactly as they were received.
+struct GetRawUpdates(GetUpdates);
+
+impl Request for GetRawUpdates {
+    type Response = Vec<serde_json::Value>;
+
+    fn method(&self) -> &'static str {
+        self.0.method()
+    }
+
+    fn params(&self, params: &mut Params) -> Result<()> {
+        self.0.params(params)
+    }
+}
+
+impl ShutdownHandle {
+    /// Asks the listener to stop.
+    pub fn shutdown(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.requested = true;
+        if let Some(poll) = state.poll.take() {
+            let _ = poll.send(PollEvent::Shutdown);
+        }
+    }
+
+    /// Whether a shutdown was requested.
+    pub fn is_shutdown(&self) -> bool {
+        self.state.lock().unwrap().requested
+    }
+
+    // Returns false if a shutdown was requested already.
+    fn register(&self, poll: mpsc::Sender<PollEvent>) -> bool {
+        let mut state = self.state.lock().unwrap();
+        state.poll = Some(poll);
+        !state.requested
+    }
+
+    fn unregister(&self) {
+        self.state.lock().unwrap().poll = None;
+    }
+}
+
+/// A `Listener` running in its own thread, see `Listener::spawn`.
+pub struct ListenerThread {
+    shutdown: ShutdownHandle,
+    thread: thread::JoinHandle<Result<()>>,
+}
+
+impl ListenerThread {
+    /// Returns a handle to stop the listener, e.g. from yet another thread.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    /// Asks the listener to stop. Use `join` to wait until it stopped.
+    pub fn shutdown(&self) {
+        self.shutdown.shutdown()
+    }
+
+    /// Waits until the listener stopped and returns the result of `listen`.
+    pub fn join(self) -> Result<()> {
+        match self.thread.join() {
+            Ok(res) => res,
+            Err(_) => Err(Error::InvalidState("listener thread panicked".into())),
+        }
+    }
+}
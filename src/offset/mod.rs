@@ -0,0 +1,115 @@
+//! Persistent storage for the update offset of a `Listener`.
+//!
+//! A `Listener` remembers which updates were handled already and confirms
+//! them to Telegram with the next `getUpdates` call. If the bot is killed
+//! before that call, the handled updates are delivered again after a restart.
+//! With an `OffsetStore` (see `Listener::set_offset_store`), the offset is
+//! saved after every handled update and loaded when the listener starts, so
+//! no update is handled twice:
+//!
+//! ```no_run
+//! use telegram_bot::*;
+//! use telegram_bot::offset::FileOffsetStore;
+//!
+//! let api = Api::from_env("TELEGRAM_BOT_TOKEN").unwrap();
+//! let mut listener = api.listener(ListeningMethod::LongPoll(None));
+//! listener.set_offset_store(FileOffsetStore::new("bot.offset"));
+//! ```
+
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use types::Integer;
+use {Error, Result};
+
+/// Stores the offset of a `Listener`, i.e. the id of the first update which
+/// was not handled yet.
+pub trait OffsetStore: Send {
+    /// Returns the stored offset or `None` if nothing was stored yet.
+    fn load(&mut self) -> Result<Option<Integer>>;
+
+    /// Saves the offset. Called after every handled update.
+    fn store(&mut self, offset: Integer) -> Result<()>;
+}
+
+/// Keeps the offset in memory. Clones share the same offset, so it can be
+/// inspected while a `Listener` uses the store. Mainly useful for testing.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryOffsetStore {
+    offset: Arc<Mutex<Option<Integer>>>,
+}
+
+impl MemoryOffsetStore {
+    pub fn new() -> MemoryOffsetStore {
+        MemoryOffsetStore::default()
+    }
+
+    /// The currently stored offset.
+    pub fn offset(&self) -> Option<Integer> {
+        *self.offset.lock().unwrap()
+    }
+}
+
+impl OffsetStore for MemoryOffsetStore {
+    fn load(&mut self) -> Result<Option<Integer>> {
+        Ok(self.offset())
+    }
+
+    fn store(&mut self, offset: Integer) -> Result<()> {
+        *self.offset.lock().unwrap() = Some(offset);
+        Ok(())
+    }
+}
+
+/// Saves the offset as text in a file. The file is replaced atomically, so a
+/// crash while saving never leaves a broken file behind.
+#[derive(Debug, Clone)]
+pub struct FileOffsetStore {
+    path: PathBuf,
+}
+
+impl FileOffsetStore {
+    /// Creates a store using the given file. The file doesn't need to exist.
+    pub fn new<P: AsRef<Path>>(path: P) -> FileOffsetStore {
+        FileOffsetStore {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+}
+
+impl OffsetStore for FileOffsetStore {
+    fn load(&mut self) -> Result<Option<Integer>> {
+        let mut content = String::new();
+        match File::open(&self.path) {
+            Ok(mut file) => try!(file.read_to_string(&mut content)),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        content.trim().parse().map(Some).map_err(|_| {
+            Error::InvalidState(format!("invalid offset file {}", self.path.display()))
+        })
+    }
+
+    fn store(&mut self, offset: Integer) -> Result<()> {
+        // Write to a temporary file first and rename it afterwards
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+
+        {
+            let mut file = try!(File::create(&tmp));
+            try!(write!(file, "{}", offset));
+            try!(file.sync_all());
+        }
+        try!(fs::rename(&tmp, &self.path));
+        Ok(())
+    }
+}
+
+// ===========================================================================
+// Unit tests
+// ===========================================================================
+#[cfg(test)]
+mod test;
@@ -0,0 +1,31 @@
+use super::*;
+use std::env;
+use std::fs;
+use std::process;
+
+#[test]
+fn memory_store() {
+    let store = MemoryOffsetStore::new();
+    let mut clone = store.clone();
+    assert_eq!(clone.load().unwrap(), None);
+
+    clone.store(42).unwrap();
+    assert_eq!(store.offset(), Some(42));
+}
+
+#[test]
+fn file_store() {
+    let path = env::temp_dir().join(format!("telegram-bot-{}.offset", process::id()));
+    let _ = fs::remove_file(&path);
+
+    let mut store = FileOffsetStore::new(&path);
+    assert_eq!(store.load().unwrap(), None);
+
+    store.store(123456789).unwrap();
+    store.store(123456790).unwrap();
+    assert_eq!(FileOffsetStore::new(&path).load().unwrap(), Some(123456790));
+
+    fs::write(&path, "garbage").unwrap();
+    assert!(store.load().is_err());
+    fs::remove_file(&path).unwrap();
+}
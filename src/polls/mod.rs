@@ -0,0 +1,178 @@
+//! Collecting the results of polls and quizzes.
+//!
+//! Telegram only reports how many users chose each option. For non-anonymous
+//! polls sent by the bot, every vote is also sent as a `PollAnswer` update,
+//! so a `PollTally` can aggregate who voted for what and, for quizzes, who
+//! answered correctly:
+//!
+//! ```no_run
+//! use telegram_bot::*;
+//! use telegram_bot::polls::PollTally;
+//!
+//! let api = Api::from_env("TELEGRAM_BOT_TOKEN").unwrap();
+//! let mut tally = PollTally::new();
+//!
+//! let sent = SendPoll::quiz(1234, "2 + 2?", vec!["3", "4", "5"], 1)
+//!     .anonymous(false)
+//!     .send(&api).unwrap();
+//! if let MessageType::Poll(ref poll) = sent.msg {
+//!     tally.track(poll);
+//! }
+//!
+//! let mut listener = api.listener(ListeningMethod::LongPoll(None));
+//! listener.listen(|u| {
+//!     tally.handle(&u);
+//!     Ok(ListeningAction::Continue)
+//! }).unwrap();
+//! ```
+
+use std::collections::{BTreeMap, HashMap};
+
+use types::{Integer, Poll, PollAnswer, Update, UserId};
+
+/// Polls have at most this many options. Votes for options beyond the ones
+/// of a tracked poll, or beyond this number, are ignored.
+pub const MAX_OPTIONS: usize = 12;
+
+/// Aggregates the votes of polls. See the `polls` module.
+#[derive(Debug, Clone, Default)]
+pub struct PollTally {
+    polls: HashMap<String, Tracked>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Tracked {
+    // Latest state sent by Telegram, if any
+    poll: Option<Poll>,
+    votes: BTreeMap<UserId, Vec<Integer>>,
+}
+
+/// The aggregated results of a poll.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PollResults {
+    /// Number of votes per option. Taken from the latest state of the poll
+    /// if it is tracked, otherwise counted from the received answers.
+    pub counts: Vec<Integer>,
+    /// The options chosen by every user who voted.
+    pub votes: BTreeMap<UserId, Vec<Integer>>,
+    /// Users who chose the correct option of a quiz. Always empty for
+    /// regular polls and for polls which are not tracked.
+    pub correct: Vec<UserId>,
+}
+
+impl PollResults {
+    /// Indices of the options with the most votes. Empty if nobody voted.
+    pub fn winners(&self) -> Vec<usize> {
+        let max = self.counts.iter().cloned().max().unwrap_or(0);
+        if max == 0 {
+            return Vec::new();
+        }
+        (0..self.counts.len()).filter(|&i| self.counts[i] == max).collect()
+    }
+}
+
+impl PollTally {
+    pub fn new() -> PollTally {
+        PollTally::default()
+    }
+
+    /// Starts tracking a poll, usually the one in the message returned by
+    /// `SendPoll`. This is needed to know the correct answer of a quiz.
+    /// Newer states of tracked polls are taken from `Poll` updates.
+    pub fn track(&mut self, poll: &Poll) {
+        self.polls.entry(poll.id.clone()).or_insert_with(Tracked::default).poll =
+            Some(poll.clone());
+    }
+
+    /// Records a vote. A retracted vote (without options) removes the
+    /// previous vote of the user. Votes of anonymous group administrators
+    /// have no user and are ignored, just like options which don't exist
+    /// (see `MAX_OPTIONS`).
+    pub fn add_answer(&mut self, answer: &PollAnswer) {
+        let user = match answer.user {
+            Some(ref user) => user.id,
+            None => return,
+        };
+
+        let tracked = self.polls.entry(answer.poll_id.clone())
+            .or_insert_with(Tracked::default);
+        if answer.option_ids.is_empty() {
+            tracked.votes.remove(&user);
+            return;
+        }
+
+        let count = tracked.poll.as_ref().map_or(MAX_OPTIONS, |p| p.options.len());
+        let options: Vec<Integer> = answer.option_ids.iter().cloned()
+            .filter(|&o| o >= 0 && (o as usize) < count)
+            .collect();
+        if !options.is_empty() {
+            tracked.votes.insert(user, options);
+        }
+    }
+
+    /// Passes the "poll_answer" of the update to `add_answer` and the new
+    /// state of a tracked poll in the "poll" field to `track`. Returns
+    /// whether the update was about a poll.
+    pub fn handle(&mut self, update: &Update) -> bool {
+        if let Some(ref answer) = update.poll_answer {
+            self.add_answer(answer);
+        }
+        if let Some(ref poll) = update.poll {
+            if self.polls.contains_key(&poll.id) {
+                self.track(poll);
+            }
+        }
+        update.poll_answer.is_some() || update.poll.is_some()
+    }
+
+    /// The results of the poll with the given id, if it is tracked or has
+    /// received answers.
+    pub fn results(&self, poll_id: &str) -> Option<PollResults> {
+        self.polls.get(poll_id).map(Tracked::results)
+    }
+
+    /// Stops tracking the poll and returns its final results.
+    pub fn remove(&mut self, poll_id: &str) -> Option<PollResults> {
+        self.polls.remove(poll_id).map(|t| t.results())
+    }
+}
+
+impl Tracked {
+    fn results(&self) -> PollResults {
+        let counts = match self.poll {
+            Some(ref poll) => poll.options.iter().map(|o| o.voter_count).collect(),
+            None => {
+                let mut counts = Vec::new();
+                for option in self.votes.values().flat_map(|v| v.iter()) {
+                    let i = *option as usize;
+                    if counts.len() <= i {
+                        counts.resize(i + 1, 0);
+                    }
+                    counts[i] += 1;
+                }
+                counts
+            }
+        };
+
+        let right = self.poll.as_ref().and_then(|p| p.correct_option_id);
+        let correct = match right {
+            Some(right) => self.votes.iter()
+                .filter(|&(_, options)| options == &[right])
+                .map(|(&user, _)| user)
+                .collect(),
+            None => Vec::new(),
+        };
+
+        PollResults {
+            counts: counts,
+            votes: self.votes.clone(),
+            correct: correct,
+        }
+    }
+}
+
+// ===========================================================================
+// Unit tests
+// ===========================================================================
+#[cfg(test)]
+mod test;
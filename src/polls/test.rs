@@ -0,0 +1,107 @@
+use super::*;
+use types::{PollKind, PollOption, User};
+
+fn user(id: Integer) -> User {
+    User {
+        id: UserId(id),
+        first_name: format!("User {}", id),
+        last_name: None,
+        username: None,
+    }
+}
+
+fn answer(user_id: Integer, options: Vec<Integer>) -> PollAnswer {
+    PollAnswer {
+        poll_id: "1".into(),
+        user: Some(user(user_id)),
+        voter_chat: None,
+        option_ids: options,
+    }
+}
+
+fn quiz(counts: &[Integer]) -> Poll {
+    Poll {
+        id: "1".into(),
+        question: "2 + 2?".into(),
+        options: counts.iter().enumerate().map(|(i, &count)| PollOption {
+            text: (i + 3).to_string(),
+            voter_count: count,
+        }).collect(),
+        total_voter_count: counts.iter().sum(),
+        is_closed: false,
+        is_anonymous: false,
+        kind: PollKind::Quiz,
+        allows_multiple_answers: false,
+        correct_option_id: Some(1),
+        explanation: None,
+        explanation_entities: None,
+        open_period: None,
+        close_date: None,
+    }
+}
+
+#[test]
+fn count_answers() {
+    let mut tally = PollTally::new();
+    assert_eq!(tally.results("1"), None);
+
+    tally.add_answer(&answer(1, vec![0, 2]));
+    tally.add_answer(&answer(2, vec![2]));
+    tally.add_answer(&answer(3, vec![1]));
+    // Retracted vote
+    tally.add_answer(&answer(3, vec![]));
+
+    let results = tally.results("1").unwrap();
+    assert_eq!(results.counts, vec![1, 0, 2]);
+    assert_eq!(results.votes.len(), 2);
+    assert_eq!(results.votes[&UserId(1)], vec![0, 2]);
+    assert_eq!(results.winners(), vec![2]);
+    assert!(results.correct.is_empty());
+}
+
+#[test]
+fn quiz_results() {
+    let mut tally = PollTally::new();
+    tally.track(&quiz(&[0, 0, 0]));
+
+    let mut update = Update {
+        update_id: ::UpdateId(1),
+        message: None,
+        poll: None,
+        poll_answer: Some(answer(1, vec![1])),
+        shipping_query: None,
+        pre_checkout_query: None,
+    };
+    assert!(tally.handle(&update));
+    update.poll_answer = Some(answer(2, vec![0]));
+    assert!(tally.handle(&update));
+    update.poll_answer = None;
+    update.poll = Some(quiz(&[1, 1, 0]));
+    assert!(tally.handle(&update));
+    update.poll = None;
+    assert!(!tally.handle(&update));
+
+    let results = tally.remove("1").unwrap();
+    assert_eq!(results.counts, vec![1, 1, 0]);
+    assert_eq!(results.winners(), vec![0, 1]);
+    assert_eq!(results.correct, vec![UserId(1)]);
+    assert_eq!(tally.results("1"), None);
+}
+
+#[test]
+fn invalid_options() {
+    let mut tally = PollTally::new();
+    tally.add_answer(&answer(1, vec![-1]));
+    tally.add_answer(&answer(2, vec![Integer::max_value()]));
+    tally.add_answer(&answer(3, vec![0, 100]));
+    let results = tally.results("1").unwrap();
+    assert_eq!(results.counts, vec![1]);
+    assert_eq!(results.votes.len(), 1);
+
+    // Tracked polls know their options
+    tally.track(&quiz(&[0, 1, 0]));
+    tally.add_answer(&answer(4, vec![3]));
+    tally.add_answer(&answer(5, vec![2]));
+    assert_eq!(tally.results("1").unwrap().votes.keys().collect::<Vec<_>>(),
+               vec![&UserId(3), &UserId(5)]);
+}
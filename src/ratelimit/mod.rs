@@ -0,0 +1,348 @@
+//! Rate limiting for outgoing messages.
+//!
+//! Telegram allows bots to send about 30 messages per second in total, but not
+//! more than one message per second to the same private chat and 20 messages
+//! per minute to the same group. Bots exceeding these limits receive "429 Too
+//! Many Requests" errors. A `RateLimiter` attached to an `Api` (see
+//! `Api::set_rate_limiter`) keeps track of all sent messages with token
+//! buckets and either waits until a message may be sent (`RateLimitMode::Block`)
+//! or returns `Error::RateLimited` immediately (`RateLimitMode::Try`).
+//!
+//! ```no_run
+//! use telegram_bot::*;
+//! use telegram_bot::ratelimit::{RateLimiter, RateLimits};
+//!
+//! let mut api = Api::from_env("TELEGRAM_BOT_TOKEN").unwrap();
+//! api.set_rate_limiter(Some(RateLimiter::new(RateLimits::default())));
+//! ```
+//!
+//! Only requests which create new messages are limited, see
+//! `Request::target_chat`.
+
+use std::cmp;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use types::{Chat, ChatId, ChatRef};
+use {Error, Result};
+
+/// Maximum number of requests in a given time span.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Rate {
+    count: u32,
+    per: Duration,
+}
+
+impl Rate {
+    /// Allows `count` requests in `per`.
+    ///
+    /// # Panics
+    ///
+    /// If `count` or `per` is zero, since such a limit would never allow a
+    /// request.
+    pub fn new(count: u32, per: Duration) -> Rate {
+        assert!(count > 0, "a rate must allow at least one request");
+        assert!(per > Duration::from_secs(0), "the time span of a rate must not be zero");
+        Rate {
+            count: count,
+            per: per,
+        }
+    }
+
+    pub fn per_second(count: u32) -> Rate {
+        Rate::new(count, Duration::from_secs(1))
+    }
+
+    pub fn per_minute(count: u32) -> Rate {
+        Rate::new(count, Duration::from_secs(60))
+    }
+
+    /// Number of requests allowed in `per`. This is also the maximum burst.
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    pub fn per(&self) -> Duration {
+        self.per
+    }
+}
+
+/// The limits a `RateLimiter` enforces. The defaults correspond to the limits
+/// documented by Telegram.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct RateLimits {
+    /// Limit for all messages together. Default: 30 per second.
+    pub global: Rate,
+    /// Limit per private chat. Default: 1 per second.
+    pub private: Rate,
+    /// Limit per group or supergroup. Default: 20 per minute.
+    pub group: Rate,
+    /// Limit per channel. Default: 20 per minute.
+    pub channel: Rate,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        RateLimits {
+            global: Rate::per_second(30),
+            private: Rate::per_second(1),
+            group: Rate::per_minute(20),
+            channel: Rate::per_minute(20),
+        }
+    }
+}
+
+/// What to do if a message would exceed the limits.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RateLimitMode {
+    /// Wait until the message can be sent.
+    Block,
+    /// Return `Error::RateLimited` with the time to wait.
+    Try,
+}
+
+/// Kind of a chat as far as rate limits are concerned.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ChatKind {
+    Private,
+    Group,
+    Channel,
+}
+
+impl ChatKind {
+    /// Guesses the kind of a chat from a `ChatRef`: Positive ids belong to
+    /// users, negative ids to groups. Usernames are only accepted for channels
+    /// and supergroups, so they are treated as channels.
+    pub fn guess(chat: &ChatRef) -> ChatKind {
+        match *chat {
+            ChatRef::Id(ChatId(id)) if id > 0 => ChatKind::Private,
+            ChatRef::Id(_) => ChatKind::Group,
+            ChatRef::Username(_) => ChatKind::Channel,
+        }
+    }
+}
+
+impl<'a> From<&'a Chat> for ChatKind {
+    fn from(chat: &'a Chat) -> ChatKind {
+        if chat.is_user() {
+            ChatKind::Private
+        } else if chat.is_channel() {
+            ChatKind::Channel
+        } else {
+            ChatKind::Group
+        }
+    }
+}
+
+/// Token buckets for all outgoing messages. Cloning a `RateLimiter` is cheap;
+/// all clones share the same buckets.
+#[derive(Clone)]
+pub struct RateLimiter {
+    limits: RateLimits,
+    mode: RateLimitMode,
+    state: Arc<Mutex<State>>,
+}
+
+impl RateLimiter {
+    /// Creates a new limiter in `Block` mode.
+    pub fn new(limits: RateLimits) -> RateLimiter {
+        RateLimiter {
+            limits: limits,
+            mode: RateLimitMode::Block,
+            state: Arc::new(Mutex::new(State {
+                global: Bucket::new(limits.global),
+                chats: HashMap::new(),
+                kinds: HashMap::new(),
+                aliases: HashMap::new(),
+            })),
+        }
+    }
+
+    /// Sets the mode used by `acquire`.
+    pub fn mode(mut self, mode: RateLimitMode) -> RateLimiter {
+        self.mode = mode;
+        self
+    }
+
+    pub fn limits(&self) -> RateLimits {
+        self.limits
+    }
+
+    /// Tells the limiter the kind of a chat. Without this, the kind is
+    /// guessed (see `ChatKind::guess`), which can't distinguish supergroups
+    /// from channels. The username of a channel is also registered, so
+    /// messages addressed by username and by id share the same limit.
+    ///
+    /// Messages sent to the chat before keep counting against its limit,
+    /// which from now on is the one of its kind.
+    pub fn register(&self, chat: &Chat) {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        let id = ChatRef::from(chat);
+        let kind = ChatKind::from(chat);
+        state.kinds.insert(id.clone(), kind);
+
+        if let Chat::Channel { name: Some(ref name), .. } = *chat {
+            let name = ChatRef::from(&**name);
+            if let Some(bucket) = state.chats.remove(&name) {
+                state.chats.entry(id.clone()).or_insert(bucket);
+            }
+            state.aliases.insert(name, id.clone());
+        }
+
+        if let Some(bucket) = state.chats.get_mut(&id) {
+            bucket.set_rate(now, self.rate(kind));
+        }
+    }
+
+    /// Takes a token for a message to `chat`, depending on the mode either
+    /// waiting until one is available or returning `Error::RateLimited`.
+    pub fn acquire(&self, chat: &ChatRef) -> Result<()> {
+        self.acquire_many(chat, 1)
+    }
+
+    /// Takes `count` tokens at once, e.g. for the messages of an album. See
+    /// `try_acquire_many`.
+    pub fn acquire_many(&self, chat: &ChatRef, count: u32) -> Result<()> {
+        loop {
+            match self.try_acquire_many(chat, count) {
+                Ok(()) => return Ok(()),
+                Err(wait) => match self.mode {
+                    RateLimitMode::Block => thread::sleep(wait),
+                    RateLimitMode::Try => return Err(Error::RateLimited(wait)),
+                },
+            }
+        }
+    }
+
+    /// Takes a token for a message to `chat` if one is available. Otherwise
+    /// returns how long to wait until the next try.
+    pub fn try_acquire(&self, chat: &ChatRef) -> ::std::result::Result<(), Duration> {
+        self.try_acquire_many(chat, 1)
+    }
+
+    /// Takes `count` tokens for messages to `chat` if they are available.
+    /// More tokens than a limit allows can be taken once the bucket is
+    /// full; later messages then wait correspondingly longer.
+    pub fn try_acquire_many(&self, chat: &ChatRef, count: u32)
+                            -> ::std::result::Result<(), Duration> {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        let chat = &state.key(chat);
+        let rate = self.rate(state.kind(chat));
+
+        // Forget about chats which haven't been used for a while.
+        if state.chats.len() > 1024 {
+            state.chats.retain(|_, b| !b.is_full(now));
+        }
+
+        let global_wait = state.global.wait_time(now, count);
+        let chat_wait = state.chats.entry(chat.clone())
+            .or_insert_with(|| Bucket::new(rate))
+            .wait_time(now, count);
+        if global_wait > Duration::from_secs(0) || chat_wait > Duration::from_secs(0) {
+            return Err(cmp::max(global_wait, chat_wait));
+        }
+
+        state.global.take(count);
+        state.chats.get_mut(chat).unwrap().take(count);
+        Ok(())
+    }
+
+    fn rate(&self, kind: ChatKind) -> Rate {
+        match kind {
+            ChatKind::Private => self.limits.private,
+            ChatKind::Group => self.limits.group,
+            ChatKind::Channel => self.limits.channel,
+        }
+    }
+}
+
+struct State {
+    global: Bucket,
+    chats: HashMap<ChatRef, Bucket>,
+    kinds: HashMap<ChatRef, ChatKind>,
+    // Ids of registered channels, by username
+    aliases: HashMap<ChatRef, ChatRef>,
+}
+
+impl State {
+    // The chat under which its bucket and kind are stored.
+    fn key(&self, chat: &ChatRef) -> ChatRef {
+        self.aliases.get(chat).unwrap_or(chat).clone()
+    }
+
+    fn kind(&self, chat: &ChatRef) -> ChatKind {
+        self.kinds.get(chat).cloned().unwrap_or_else(|| ChatKind::guess(chat))
+    }
+}
+
+// A token bucket: Starts full and is refilled continuously at the given rate.
+// Taking more tokens than the bucket holds leaves it in debt.
+struct Bucket {
+    rate: Rate,
+    tokens: f64,
+    last: Instant,
+}
+
+impl Bucket {
+    fn new(rate: Rate) -> Bucket {
+        Bucket {
+            rate: rate,
+            tokens: rate.count as f64,
+            last: Instant::now(),
+        }
+    }
+
+    // Adds the tokens generated since the last call.
+    fn refill(&mut self, now: Instant) {
+        if now > self.last {
+            let elapsed = seconds(now - self.last);
+            let per_second = self.rate.count as f64 / seconds(self.rate.per);
+            self.tokens = (self.tokens + elapsed * per_second).min(self.rate.count as f64);
+            self.last = now;
+        }
+    }
+
+    // Switches to another rate, keeping the tokens (at most a full bucket).
+    fn set_rate(&mut self, now: Instant, rate: Rate) {
+        self.refill(now);
+        self.rate = rate;
+        self.tokens = self.tokens.min(rate.count as f64);
+    }
+
+    fn is_full(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        self.tokens >= self.rate.count as f64
+    }
+
+    // Returns how long to wait until `count` tokens (at most a full bucket)
+    // are available (zero if they are already).
+    fn wait_time(&mut self, now: Instant, count: u32) -> Duration {
+        self.refill(now);
+        let needed = cmp::min(count, self.rate.count) as f64;
+        if self.tokens >= needed {
+            return Duration::from_secs(0);
+        }
+
+        let missing = needed - self.tokens;
+        let wait = missing * seconds(self.rate.per) / self.rate.count as f64;
+        Duration::new(wait as u64, ((wait.fract() * 1e9) as u32).max(1))
+    }
+
+    fn take(&mut self, count: u32) {
+        self.tokens -= count as f64;
+    }
+}
+
+fn seconds(d: Duration) -> f64 {
+    d.as_secs() as f64 + d.subsec_nanos() as f64 / 1e9
+}
+
+// ===========================================================================
+// Unit tests
+// ===========================================================================
+#[cfg(test)]
+mod test;
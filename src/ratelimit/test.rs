@@ -0,0 +1,139 @@
+use super::*;
+use std::thread;
+use std::time::Duration;
+use types::{Chat, ChatId, ChatRef};
+use Error;
+
+fn limits(global: u32, per_chat: u32) -> RateLimits {
+    let per_chat = Rate::new(per_chat, Duration::from_millis(200));
+    RateLimits {
+        global: Rate::new(global, Duration::from_millis(200)),
+        private: per_chat,
+        group: per_chat,
+        channel: per_chat,
+    }
+}
+
+#[test]
+fn per_chat_limit() {
+    let limiter = RateLimiter::new(limits(10, 2)).mode(RateLimitMode::Try);
+    let alice = ChatRef::from(1);
+    let bob = ChatRef::from(2);
+
+    assert!(limiter.acquire(&alice).is_ok());
+    assert!(limiter.acquire(&alice).is_ok());
+    match limiter.acquire(&alice) {
+        Err(Error::RateLimited(wait)) => assert!(wait <= Duration::from_millis(100)),
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    // Other chats are not affected
+    assert!(limiter.acquire(&bob).is_ok());
+
+    // Tokens are refilled over time
+    thread::sleep(Duration::from_millis(120));
+    assert!(limiter.acquire(&alice).is_ok());
+}
+
+#[test]
+fn several_messages() {
+    let limiter = RateLimiter::new(limits(10, 3));
+    let alice = ChatRef::from(1);
+    assert!(limiter.try_acquire_many(&alice, 2).is_ok());
+    assert!(limiter.try_acquire_many(&alice, 2).is_err());
+    assert!(limiter.try_acquire(&alice).is_ok());
+
+    // More than the limit is possible, but delays the next message
+    let bob = ChatRef::from(2);
+    assert!(limiter.try_acquire_many(&bob, 5).is_ok());
+    match limiter.try_acquire(&bob) {
+        Err(wait) => assert!(wait > Duration::from_millis(150)),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn global_limit() {
+    let limiter = RateLimiter::new(limits(3, 2));
+    for id in 0..3 {
+        assert!(limiter.try_acquire(&ChatRef::from(id)).is_ok());
+    }
+    assert!(limiter.try_acquire(&ChatRef::from(4)).is_err());
+
+    // In blocking mode, we just wait a bit
+    assert!(limiter.acquire(&ChatRef::from(4)).is_ok());
+}
+
+#[test]
+fn chat_kinds() {
+    assert_eq!(ChatKind::guess(&ChatRef::from(12345)), ChatKind::Private);
+    assert_eq!(ChatKind::guess(&ChatRef::from(-12345)), ChatKind::Group);
+    assert_eq!(ChatKind::guess(&ChatRef::from("@news")), ChatKind::Channel);
+
+    let mut limits = limits(100, 1);
+    limits.channel = Rate::per_second(5);
+    let limiter = RateLimiter::new(limits);
+    let channel = Chat::Channel {
+        id: ChatId(-1001234),
+        title: "News".into(),
+        name: Some("news".into()),
+    };
+    limiter.register(&channel);
+
+    // Registered channels use the channel limit, shared by id and username
+    let by_id = ChatRef::from(&channel);
+    let by_name = ChatRef::from("@news");
+    for _ in 0..3 {
+        assert!(limiter.try_acquire(&by_id).is_ok());
+    }
+    for _ in 0..2 {
+        assert!(limiter.try_acquire(&by_name).is_ok());
+    }
+    assert!(limiter.try_acquire(&by_id).is_err());
+    assert!(limiter.try_acquire(&by_name).is_err());
+
+    // Unknown supergroups are treated as groups
+    assert!(limiter.try_acquire(&ChatRef::from(-1005678)).is_ok());
+    assert!(limiter.try_acquire(&ChatRef::from(-1005678)).is_err());
+}
+
+#[test]
+#[should_panic]
+fn zero_count() {
+    Rate::per_second(0);
+}
+
+#[test]
+#[should_panic]
+fn zero_time_span() {
+    Rate::new(1, Duration::from_secs(0));
+}
+
+#[test]
+fn register_used_chat() {
+    let mut limits = limits(100, 1);
+    limits.channel = Rate::new(5, Duration::from_millis(200));
+    let limiter = RateLimiter::new(limits);
+    let channel = Chat::Channel {
+        id: ChatId(-1001234),
+        title: "News".into(),
+        name: Some("news".into()),
+    };
+
+    // Guessed as a group first
+    let by_id = ChatRef::from(&channel);
+    assert!(limiter.try_acquire(&by_id).is_ok());
+    match limiter.try_acquire(&by_id) {
+        Err(wait) => assert!(wait > Duration::from_millis(100)),
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    // Now the used token is refilled at the channel rate
+    limiter.register(&channel);
+    match limiter.try_acquire(&by_id) {
+        Err(wait) => assert!(wait <= Duration::from_millis(40)),
+        other => panic!("unexpected result: {:?}", other),
+    }
+    thread::sleep(Duration::from_millis(50));
+    assert!(limiter.try_acquire(&by_id).is_ok());
+}
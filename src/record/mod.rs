@@ -0,0 +1,179 @@
+//! Recording updates and replaying them offline.
+//!
+//! To reproduce a problem of a bot, set a `Recorder` on its `Listener` with
+//! `Listener::set_recorder`. Every received update is appended, exactly as it
+//! was sent by Telegram, to a file. Later, a `Replayer` feeds the recorded
+//! updates through a handler without any network access:
+//!
+//! ```no_run
+//! use telegram_bot::*;
+//! use telegram_bot::record::{Recorder, Replayer};
+//!
+//! // In production
+//! let api = Api::from_env("TELEGRAM_BOT_TOKEN").unwrap();
+//! let mut listener = api.listener(ListeningMethod::LongPoll(None));
+//! listener.set_recorder(Recorder::open("updates.jsonl").unwrap());
+//!
+//! // Locally, with the same delays between the updates as in production
+//! Replayer::open("updates.jsonl").unwrap()
+//!     .time_scale(1.0)
+//!     .run(|u| {
+//!         println!("{:?}", u);
+//!         Ok(ListeningAction::Continue)
+//!     })
+//!     .unwrap();
+//! ```
+//!
+//! The file contains one JSON object per line, with the raw update in the
+//! "update" field and the time it was received (in milliseconds since the
+//! unix epoch) in the "received_at" field.
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use serde_json::{self, Value};
+
+use types::{Integer, Update};
+use util::open_lines_for_append;
+use {ListeningAction, Result};
+
+// A line of a recording.
+#[derive(Serialize, Deserialize, Debug)]
+struct Entry<U> {
+    received_at: u64,
+    update: U,
+}
+
+/// Appends received updates to a file. See the `record` module.
+#[derive(Debug)]
+pub struct Recorder {
+    file: File,
+    // Updates before this id were recorded already.
+    next_id: Option<Integer>,
+}
+
+impl Recorder {
+    /// Opens (or creates) the given file. New updates are appended, after
+    /// cutting off a line which was written partially before a crash.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Recorder> {
+        let file = try!(open_lines_for_append(path));
+        Ok(Recorder {
+            file: file,
+            next_id: None,
+        })
+    }
+
+    /// Appends the update (either an `Update` or the raw JSON value).
+    /// Updates which are received again because they were not confirmed yet
+    /// are only recorded once.
+    pub fn record<U: Serialize>(&mut self, update: &U) -> Result<()> {
+        let update = try!(serde_json::to_value(update));
+        if let Some(id) = update.get("update_id").and_then(Value::as_i64) {
+            if self.next_id.map_or(false, |next| id < next) {
+                return Ok(());
+            }
+            self.next_id = Some(id + 1);
+        }
+
+        let entry = Entry {
+            received_at: now(),
+            update: update,
+        };
+        let mut line = try!(serde_json::to_vec(&entry));
+        line.push(b'\n');
+        try!(self.file.write_all(&line));
+        Ok(())
+    }
+}
+
+/// Passes recorded updates to a handler. See the `record` module.
+#[derive(Debug)]
+pub struct Replayer {
+    entries: Vec<Entry<Value>>,
+    time_scale: Option<f64>,
+}
+
+impl Replayer {
+    /// Reads the recording in the given file. Invalid lines are skipped
+    /// with a warning.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Replayer> {
+        let mut content = String::new();
+        try!(try!(File::open(path)).read_to_string(&mut content));
+
+        // Only complete lines count, the last one might have been written
+        // partially before a crash.
+        let complete = content.rfind('\n').map_or("", |end| &content[..end]);
+        let mut entries = Vec::new();
+        for (i, line) in complete.lines().enumerate().filter(|&(_, l)| !l.trim().is_empty()) {
+            match serde_json::from_str(line) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => warn!("Skipping invalid line {} of the recording: {}", i + 1, e),
+            }
+        }
+
+        Ok(Replayer {
+            entries: entries,
+            time_scale: None,
+        })
+    }
+
+    /// Waits between two updates as long as between receiving them,
+    /// multiplied by `scale`: With 1.0, the updates are replayed in real
+    /// time, with 0.5 twice as fast. By default, there is no waiting at all.
+    pub fn time_scale(mut self, scale: f64) -> Replayer {
+        self.time_scale = Some(scale);
+        self
+    }
+
+    /// Number of recorded updates.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Calls the handler for every recorded update, in order. Like
+    /// `Listener::listen`, this stops when the handler returns `Err` or
+    /// `Ok(ListeningAction::Stop)`. Returns the number of handled updates.
+    pub fn run<H>(self, mut handler: H) -> Result<usize>
+        where H: FnMut(Update) -> Result<ListeningAction>
+    {
+        let mut previous = None;
+        let mut handled = 0;
+        for entry in self.entries {
+            if let (Some(scale), Some(previous)) = (self.time_scale, previous) {
+                let millis = entry.received_at.saturating_sub(previous) as f64 * scale;
+                if millis > 0.0 {
+                    thread::sleep(Duration::from_millis(millis as u64));
+                }
+            }
+            previous = Some(entry.received_at);
+
+            let update = try!(serde_json::from_value(entry.update));
+            let action = try!(handler(update));
+            handled += 1;
+            if let ListeningAction::Stop = action {
+                break;
+            }
+        }
+        Ok(handled)
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() * 1000 + d.subsec_nanos() as u64 / 1_000_000)
+        .unwrap_or(0)
+}
+
+// ===========================================================================
+// Unit tests
+// ===========================================================================
+#[cfg(test)]
+mod test;
@@ -0,0 +1,79 @@
+use super::*;
+use std::env;
+use std::fs::OpenOptions;
+use std::fs;
+use std::process;
+use std::time::Instant;
+use types::UpdateId;
+
+#[test]
+fn record_and_replay() {
+    let path = env::temp_dir().join(format!("telegram-bot-{}.jsonl", process::id()));
+    let _ = fs::remove_file(&path);
+
+    {
+        let mut recorder = Recorder::open(&path).unwrap();
+        for &id in &[1, 2, 2, 1, 3] {
+            let raw = format!(r#"{{"update_id":{},"unknown_field":true}}"#, id);
+            recorder.record(&serde_json::from_str::<Value>(&raw).unwrap()).unwrap();
+        }
+    }
+    // Raw updates are recorded unchanged
+    assert!(fs::read_to_string(&path).unwrap().contains(r#""unknown_field":true"#));
+
+    // Simulate a crash while writing
+    OpenOptions::new().append(true).open(&path).unwrap().write_all(b"{\"rec").unwrap();
+
+    let mut ids = Vec::new();
+    let replayer = Replayer::open(&path).unwrap();
+    assert_eq!(replayer.len(), 3);
+    let handled = replayer.run(|u| {
+        ids.push(u.update_id);
+        Ok(if ids.len() == 2 { ListeningAction::Stop } else { ListeningAction::Continue })
+    }).unwrap();
+    assert_eq!(handled, 2);
+    assert_eq!(ids, vec![UpdateId(1), UpdateId(2)]);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn record_after_crash() {
+    let path = env::temp_dir().join(format!("telegram-bot-{}-crash.jsonl", process::id()));
+    let _ = fs::remove_file(&path);
+
+    let record = |ids: &[Integer]| {
+        let mut recorder = Recorder::open(&path).unwrap();
+        for &id in ids {
+            let raw = format!(r#"{{"update_id":{}}}"#, id);
+            recorder.record(&serde_json::from_str::<Value>(&raw).unwrap()).unwrap();
+        }
+    };
+    record(&[1]);
+    // A partially written line and a corrupted one
+    OpenOptions::new().append(true).open(&path).unwrap()
+        .write_all(b"garbage\n{\"rec").unwrap();
+    record(&[2, 3]);
+
+    let mut ids = Vec::new();
+    let replayer = Replayer::open(&path).unwrap();
+    assert_eq!(replayer.len(), 3);
+    replayer.run(|u| {
+        ids.push(u.update_id);
+        Ok(ListeningAction::Continue)
+    }).unwrap();
+    assert_eq!(ids, vec![UpdateId(1), UpdateId(2), UpdateId(3)]);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn time_scale() {
+    let path = env::temp_dir().join(format!("telegram-bot-{}-scaled.jsonl", process::id()));
+    fs::write(&path, "{\"received_at\":1000,\"update\":{\"update_id\":1}}\n\
+                      {\"received_at\":1400,\"update\":{\"update_id\":2}}\n").unwrap();
+
+    let start = Instant::now();
+    Replayer::open(&path).unwrap().time_scale(0.5)
+        .run(|_| Ok(ListeningAction::Continue)).unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(200));
+    fs::remove_file(&path).unwrap();
+}
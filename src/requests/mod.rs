@@ -0,0 +1,1768 @@
+//! Typed requests for all supported API methods.
+//!
+//! Every Telegram method has a corresponding struct implementing `Request`.
+//! Required parameters are passed to `new`, optional parameters are set with
+//! builder methods. The finished request is sent with `Api::send` or with the
+//! `send` method of the request itself:
+//!
+//! ```no_run
+//! use telegram_bot::*;
+//!
+//! let api = Api::from_env("TELEGRAM_BOT_TOKEN").unwrap();
+//! let message = SendMessage::new(1234, "*Hi!*")
+//!     .parse_mode(ParseMode::Markdown)
+//!     .reply_to(MessageId(42))
+//!     .send(&api);
+//! ```
+//!
+//! Unlike the methods on `Api` taking all parameters positionally, new
+//! optional parameters can be added to these requests without breaking
+//! existing code.
+
+use std::collections::BTreeSet;
+
+use serde::de::DeserializeOwned;
+use url::Url;
+
+use types::*;
+use {Api, Result};
+
+pub use util::{FileSource, InputFile, Params};
+
+/// A request to the Telegram bot API.
+///
+/// Implemented by all request types in this module. You can implement it for
+/// your own types to use API methods this library doesn't know about yet.
+pub trait Request {
+    /// Type of the "result" field in a successful response.
+    type Response: DeserializeOwned;
+
+    /// Name of the API method, e.g. "sendMessage".
+    fn method(&self) -> &'static str;
+
+    /// Adds all parameters of this request to `params`.
+    fn params(&self, params: &mut Params) -> Result<()>;
+
+    /// The chat a new message is sent to by this request, if any. Used to
+    /// apply rate limits (see the `ratelimit` module).
+    fn target_chat(&self) -> Option<&ChatRef> {
+        None
+    }
+
+    /// Number of messages sent to the `target_chat`, each of which counts
+    /// against the rate limits.
+    fn message_count(&self) -> u32 {
+        1
+    }
+
+    /// Sends this request with the given `Api`. Same as `api.send(self)`.
+    fn send(self, api: &Api) -> Result<Self::Response> where Self: Sized {
+        api.send(self)
+    }
+}
+
+// ---------------------------------------------------------------------------
+/// Corresponds to the "getMe" method of the API.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct GetMe;
+
+impl Request for GetMe {
+    type Response = User;
+
+    fn method(&self) -> &'static str {
+        "getMe"
+    }
+
+    fn params(&self, _: &mut Params) -> Result<()> {
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------
+/// Corresponds to the "sendMessage" method of the API.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SendMessage {
+    chat_id: ChatRef,
+    text: String,
+    parse_mode: Option<ParseMode>,
+    disable_web_page_preview: Option<bool>,
+    reply_to_message_id: Option<MessageId>,
+    reply_markup: Option<ReplyMarkup>,
+}
+
+impl SendMessage {
+    pub fn new<C, T>(chat: C, text: T) -> SendMessage
+        where C: Into<ChatRef>, T: Into<String>
+    {
+        SendMessage {
+            chat_id: chat.into(),
+            text: text.into(),
+            parse_mode: None,
+            disable_web_page_preview: None,
+            reply_to_message_id: None,
+            reply_markup: None,
+        }
+    }
+
+    pub fn parse_mode(mut self, parse_mode: ParseMode) -> Self {
+        self.parse_mode = Some(parse_mode);
+        self
+    }
+
+    pub fn disable_web_page_preview(mut self, disable: bool) -> Self {
+        self.disable_web_page_preview = Some(disable);
+        self
+    }
+
+    pub fn reply_to(mut self, message_id: MessageId) -> Self {
+        self.reply_to_message_id = Some(message_id);
+        self
+    }
+
+    pub fn reply_markup<R: Into<ReplyMarkup>>(mut self, markup: R) -> Self {
+        self.reply_markup = Some(markup.into());
+        self
+    }
+}
+
+impl Request for SendMessage {
+    type Response = Message;
+
+    fn method(&self) -> &'static str {
+        "sendMessage"
+    }
+
+    fn params(&self, params: &mut Params) -> Result<()> {
+        params.add_get("chat_id", &self.chat_id);
+        params.add_get("text", &*self.text);
+        params.add_get_opt("parse_mode", self.parse_mode);
+        params.add_get_opt("disable_web_page_preview", self.disable_web_page_preview);
+        params.add_get_opt("reply_to_message_id", self.reply_to_message_id);
+        params.add_get_json_opt("reply_markup", self.reply_markup.as_ref())
+    }
+
+    fn target_chat(&self) -> Option<&ChatRef> {
+        Some(&self.chat_id)
+    }
+}
+
+// ---------------------------------------------------------------------------
+/// Corresponds to the "forwardMessage" method of the API.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ForwardMessage {
+    chat_id: ChatRef,
+    from_chat_id: ChatRef,
+    message_id: MessageId,
+}
+
+impl ForwardMessage {
+    /// Forwards the message with the given id from `from_chat` to `chat`.
+    pub fn new<C, F>(chat: C, from_chat: F, message_id: MessageId) -> ForwardMessage
+        where C: Into<ChatRef>, F: Into<ChatRef>
+    {
+        ForwardMessage {
+            chat_id: chat.into(),
+            from_chat_id: from_chat.into(),
+            message_id: message_id,
+        }
+    }
+}
+
+impl Request for ForwardMessage {
+    type Response = Message;
+
+    fn method(&self) -> &'static str {
+        "forwardMessage"
+    }
+
+    fn params(&self, params: &mut Params) -> Result<()> {
+        params.add_get("chat_id", &self.chat_id);
+        params.add_get("from_chat_id", &self.from_chat_id);
+        params.add_get("message_id", self.message_id);
+        Ok(())
+    }
+
+    fn target_chat(&self) -> Option<&ChatRef> {
+        Some(&self.chat_id)
+    }
+}
+
+// ---------------------------------------------------------------------------
+/// Corresponds to the "editMessageText" method of the API.
+#[derive(Debug, PartialEq, Clone)]
+pub struct EditMessageText {
+    chat_id: ChatRef,
+    message_id: MessageId,
+    text: String,
+    parse_mode: Option<ParseMode>,
+    disable_web_page_preview: Option<bool>,
+}
+
+impl EditMessageText {
+    pub fn new<C, T>(chat: C, message_id: MessageId, text: T) -> EditMessageText
+        where C: Into<ChatRef>, T: Into<String>
+    {
+        EditMessageText {
+            chat_id: chat.into(),
+            message_id: message_id,
+            text: text.into(),
+            parse_mode: None,
+            disable_web_page_preview: None,
+        }
+    }
+
+    pub fn parse_mode(mut self, parse_mode: ParseMode) -> Self {
+        self.parse_mode = Some(parse_mode);
+        self
+    }
+
+    pub fn disable_web_page_preview(mut self, disable: bool) -> Self {
+        self.disable_web_page_preview = Some(disable);
+        self
+    }
+}
+
+impl Request for EditMessageText {
+    type Response = Message;
+
+    fn method(&self) -> &'static str {
+        "editMessageText"
+    }
+
+    fn params(&self, params: &mut Params) -> Result<()> {
+        params.add_get("chat_id", &self.chat_id);
+        params.add_get("message_id", self.message_id);
+        params.add_get("text", &*self.text);
+        params.add_get_opt("parse_mode", self.parse_mode);
+        params.add_get_opt("disable_web_page_preview", self.disable_web_page_preview);
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------
+/// Corresponds to the "deleteMessage" method of the API.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DeleteMessage {
+    chat_id: ChatRef,
+    message_id: MessageId,
+}
+
+impl DeleteMessage {
+    pub fn new<C: Into<ChatRef>>(chat: C, message_id: MessageId) -> DeleteMessage {
+        DeleteMessage {
+            chat_id: chat.into(),
+            message_id: message_id,
+        }
+    }
+}
+
+impl Request for DeleteMessage {
+    type Response = bool;
+
+    fn method(&self) -> &'static str {
+        "deleteMessage"
+    }
+
+    fn params(&self, params: &mut Params) -> Result<()> {
+        params.add_get("chat_id", &self.chat_id);
+        params.add_get("message_id", self.message_id);
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------
+/// Corresponds to the "sendLocation" method of the API.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SendLocation {
+    chat_id: ChatRef,
+    latitude: Float,
+    longitude: Float,
+    reply_to_message_id: Option<MessageId>,
+    reply_markup: Option<ReplyMarkup>,
+}
+
+impl SendLocation {
+    pub fn new<C: Into<ChatRef>>(chat: C, latitude: Float, longitude: Float)
+                                 -> SendLocation {
+        SendLocation {
+            chat_id: chat.into(),
+            latitude: latitude,
+            longitude: longitude,
+            reply_to_message_id: None,
+            reply_markup: None,
+        }
+    }
+
+    pub fn reply_to(mut self, message_id: MessageId) -> Self {
+        self.reply_to_message_id = Some(message_id);
+        self
+    }
+
+    pub fn reply_markup<R: Into<ReplyMarkup>>(mut self, markup: R) -> Self {
+        self.reply_markup = Some(markup.into());
+        self
+    }
+}
+
+impl Request for SendLocation {
+    type Response = Message;
+
+    fn method(&self) -> &'static str {
+        "sendLocation"
+    }
+
+    fn params(&self, params: &mut Params) -> Result<()> {
+        params.add_get("chat_id", &self.chat_id);
+        params.add_get("latitude", self.latitude);
+        params.add_get("longitude", self.longitude);
+        params.add_get_opt("reply_to_message_id", self.reply_to_message_id);
+        params.add_get_json_opt("reply_markup", self.reply_markup.as_ref())
+    }
+
+    fn target_chat(&self) -> Option<&ChatRef> {
+        Some(&self.chat_id)
+    }
+}
+
+// ---------------------------------------------------------------------------
+/// Corresponds to the "sendPoll" method of the API.
+///
+/// ```no_run
+/// use telegram_bot::*;
+///
+/// let api = Api::from_env("TELEGRAM_BOT_TOKEN").unwrap();
+/// let message = SendPoll::quiz(1234, "2 + 2?", vec!["3", "4", "5"], 1)
+///     .explanation("Count on your fingers!")
+///     .open_period(60)
+///     .send(&api);
+/// ```
+#[derive(Debug, PartialEq, Clone)]
+pub struct SendPoll {
+    chat_id: ChatRef,
+    question: String,
+    options: Vec<String>,
+    is_anonymous: Option<bool>,
+    kind: Option<PollKind>,
+    allows_multiple_answers: Option<bool>,
+    correct_option_id: Option<Integer>,
+    explanation: Option<String>,
+    explanation_parse_mode: Option<ParseMode>,
+    explanation_entities: Option<Vec<MessageEntity>>,
+    open_period: Option<Integer>,
+    close_date: Option<Integer>,
+    is_closed: Option<bool>,
+    reply_to_message_id: Option<MessageId>,
+    reply_markup: Option<ReplyMarkup>,
+}
+
+// Telegram type "InputPollOption"
+#[derive(Serialize)]
+struct InputPollOption<'a> {
+    text: &'a str,
+}
+
+impl SendPoll {
+    /// A regular poll with the given answer options.
+    pub fn new<C, Q, O>(chat: C, question: Q, options: Vec<O>) -> SendPoll
+        where C: Into<ChatRef>, Q: Into<String>, O: Into<String>
+    {
+        SendPoll {
+            chat_id: chat.into(),
+            question: question.into(),
+            options: options.into_iter().map(Into::into).collect(),
+            is_anonymous: None,
+            kind: None,
+            allows_multiple_answers: None,
+            correct_option_id: None,
+            explanation: None,
+            explanation_parse_mode: None,
+            explanation_entities: None,
+            open_period: None,
+            close_date: None,
+            is_closed: None,
+            reply_to_message_id: None,
+            reply_markup: None,
+        }
+    }
+
+    /// A quiz, where the option with the index `correct_option_id` (starting
+    /// with 0) is the right answer.
+    pub fn quiz<C, Q, O>(chat: C, question: Q, options: Vec<O>,
+                         correct_option_id: Integer) -> SendPoll
+        where C: Into<ChatRef>, Q: Into<String>, O: Into<String>
+    {
+        let mut poll = SendPoll::new(chat, question, options);
+        poll.kind = Some(PollKind::Quiz);
+        poll.correct_option_id = Some(correct_option_id);
+        poll
+    }
+
+    /// Polls are anonymous by default. `PollAnswer` updates are only sent
+    /// for non-anonymous polls.
+    pub fn anonymous(mut self, anonymous: bool) -> Self {
+        self.is_anonymous = Some(anonymous);
+        self
+    }
+
+    /// Allows choosing more than one option. Not supported for quizzes.
+    pub fn allows_multiple_answers(mut self, allow: bool) -> Self {
+        self.allows_multiple_answers = Some(allow);
+        self
+    }
+
+    /// Text shown when a user chooses a wrong answer in a quiz. Formatting
+    /// can be given either with `explanation_parse_mode` or with
+    /// `explanation_entities`.
+    pub fn explanation<T: Into<String>>(mut self, explanation: T) -> Self {
+        self.explanation = Some(explanation.into());
+        self
+    }
+
+    pub fn explanation_parse_mode(mut self, parse_mode: ParseMode) -> Self {
+        self.explanation_parse_mode = Some(parse_mode);
+        self
+    }
+
+    pub fn explanation_entities(mut self, entities: Vec<MessageEntity>) -> Self {
+        self.explanation_entities = Some(entities);
+        self
+    }
+
+    /// Closes the poll automatically after the given number of seconds
+    /// (5-600). Can't be combined with `close_date`.
+    pub fn open_period(mut self, seconds: Integer) -> Self {
+        self.open_period = Some(seconds);
+        self
+    }
+
+    /// Closes the poll automatically at the given unix time.
+    pub fn close_date(mut self, date: Integer) -> Self {
+        self.close_date = Some(date);
+        self
+    }
+
+    /// Sends the poll already closed, e.g. to show results.
+    pub fn closed(mut self, closed: bool) -> Self {
+        self.is_closed = Some(closed);
+        self
+    }
+
+    pub fn reply_to(mut self, message_id: MessageId) -> Self {
+        self.reply_to_message_id = Some(message_id);
+        self
+    }
+
+    pub fn reply_markup<R: Into<ReplyMarkup>>(mut self, markup: R) -> Self {
+        self.reply_markup = Some(markup.into());
+        self
+    }
+}
+
+impl Request for SendPoll {
+    type Response = Message;
+
+    fn method(&self) -> &'static str {
+        "sendPoll"
+    }
+
+    fn params(&self, params: &mut Params) -> Result<()> {
+        let options: Vec<_> = self.options.iter()
+            .map(|text| InputPollOption { text: text })
+            .collect();
+
+        params.add_get("chat_id", &self.chat_id);
+        params.add_get("question", &*self.question);
+        try!(params.add_get_json_opt("options", Some(&options)));
+        params.add_get_opt("is_anonymous", self.is_anonymous);
+        params.add_get_opt("type", self.kind);
+        params.add_get_opt("allows_multiple_answers", self.allows_multiple_answers);
+        params.add_get_opt("correct_option_id", self.correct_option_id);
+        params.add_get_opt("explanation", self.explanation.as_ref());
+        params.add_get_opt("explanation_parse_mode", self.explanation_parse_mode);
+        try!(params.add_get_json_opt("explanation_entities", self.explanation_entities.as_ref()));
+        params.add_get_opt("open_period", self.open_period);
+        params.add_get_opt("close_date", self.close_date);
+        params.add_get_opt("is_closed", self.is_closed);
+        params.add_get_opt("reply_to_message_id", self.reply_to_message_id);
+        params.add_get_json_opt("reply_markup", self.reply_markup.as_ref())
+    }
+
+    fn target_chat(&self) -> Option<&ChatRef> {
+        Some(&self.chat_id)
+    }
+}
+
+// ---------------------------------------------------------------------------
+/// Corresponds to the "stopPoll" method of the API. Returns the final state
+/// of the poll.
+#[derive(Debug, PartialEq, Clone)]
+pub struct StopPoll {
+    chat_id: ChatRef,
+    message_id: MessageId,
+    reply_markup: Option<ReplyMarkup>,
+}
+
+impl StopPoll {
+    pub fn new<C: Into<ChatRef>>(chat: C, message_id: MessageId) -> StopPoll {
+        StopPoll {
+            chat_id: chat.into(),
+            message_id: message_id,
+            reply_markup: None,
+        }
+    }
+
+    pub fn reply_markup<R: Into<ReplyMarkup>>(mut self, markup: R) -> Self {
+        self.reply_markup = Some(markup.into());
+        self
+    }
+}
+
+impl Request for StopPoll {
+    type Response = Poll;
+
+    fn method(&self) -> &'static str {
+        "stopPoll"
+    }
+
+    fn params(&self, params: &mut Params) -> Result<()> {
+        params.add_get("chat_id", &self.chat_id);
+        params.add_get("message_id", self.message_id);
+        params.add_get_json_opt("reply_markup", self.reply_markup.as_ref())
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Parameters shared by "sendInvoice" and "createInvoiceLink"
+#[derive(Debug, PartialEq, Clone)]
+struct InvoiceDetails {
+    title: String,
+    description: String,
+    payload: String,
+    currency: String,
+    prices: Vec<LabeledPrice>,
+    provider_token: Option<String>,
+    max_tip_amount: Option<Integer>,
+    suggested_tip_amounts: Option<Vec<Integer>>,
+    provider_data: Option<String>,
+    photo_url: Option<String>,
+    need_name: Option<bool>,
+    need_phone_number: Option<bool>,
+    need_email: Option<bool>,
+    need_shipping_address: Option<bool>,
+    is_flexible: Option<bool>,
+}
+
+impl InvoiceDetails {
+    fn new(title: String, description: String, payload: String, currency: String,
+           prices: Vec<LabeledPrice>) -> InvoiceDetails {
+        InvoiceDetails {
+            title: title,
+            description: description,
+            payload: payload,
+            currency: currency,
+            prices: prices,
+            provider_token: None,
+            max_tip_amount: None,
+            suggested_tip_amounts: None,
+            provider_data: None,
+            photo_url: None,
+            need_name: None,
+            need_phone_number: None,
+            need_email: None,
+            need_shipping_address: None,
+            is_flexible: None,
+        }
+    }
+
+    fn params(&self, params: &mut Params) -> Result<()> {
+        params.add_get("title", &*self.title);
+        params.add_get("description", &*self.description);
+        params.add_get("payload", &*self.payload);
+        params.add_get("currency", &*self.currency);
+        try!(params.add_get_json_opt("prices", Some(&self.prices)));
+        params.add_get_opt("provider_token", self.provider_token.as_ref());
+        params.add_get_opt("max_tip_amount", self.max_tip_amount);
+        try!(params.add_get_json_opt("suggested_tip_amounts", self.suggested_tip_amounts.as_ref()));
+        params.add_get_opt("provider_data", self.provider_data.as_ref());
+        params.add_get_opt("photo_url", self.photo_url.as_ref());
+        params.add_get_opt("need_name", self.need_name);
+        params.add_get_opt("need_phone_number", self.need_phone_number);
+        params.add_get_opt("need_email", self.need_email);
+        params.add_get_opt("need_shipping_address", self.need_shipping_address);
+        params.add_get_opt("is_flexible", self.is_flexible);
+        Ok(())
+    }
+}
+
+// Builder methods for the optional `InvoiceDetails`
+macro_rules! invoice_options {
+    () => {
+        /// Token of the payment provider. Not needed for payments in
+        /// Telegram Stars.
+        pub fn provider_token<T: Into<String>>(mut self, token: T) -> Self {
+            self.details.provider_token = Some(token.into());
+            self
+        }
+
+        /// Allows tips up to the given amount. Not supported for Telegram
+        /// Stars.
+        pub fn max_tip_amount(mut self, amount: Integer) -> Self {
+            self.details.max_tip_amount = Some(amount);
+            self
+        }
+
+        pub fn suggested_tip_amounts(mut self, amounts: Vec<Integer>) -> Self {
+            self.details.suggested_tip_amounts = Some(amounts);
+            self
+        }
+
+        /// JSON data about the invoice for the payment provider.
+        pub fn provider_data<T: Into<String>>(mut self, data: T) -> Self {
+            self.details.provider_data = Some(data.into());
+            self
+        }
+
+        pub fn photo_url<T: Into<String>>(mut self, url: T) -> Self {
+            self.details.photo_url = Some(url.into());
+            self
+        }
+
+        pub fn need_name(mut self, need: bool) -> Self {
+            self.details.need_name = Some(need);
+            self
+        }
+
+        pub fn need_phone_number(mut self, need: bool) -> Self {
+            self.details.need_phone_number = Some(need);
+            self
+        }
+
+        pub fn need_email(mut self, need: bool) -> Self {
+            self.details.need_email = Some(need);
+            self
+        }
+
+        pub fn need_shipping_address(mut self, need: bool) -> Self {
+            self.details.need_shipping_address = Some(need);
+            self
+        }
+
+        /// The final price depends on the shipping method. The bot receives
+        /// a `ShippingQuery` which has to be answered with
+        /// `AnswerShippingQuery`.
+        pub fn flexible(mut self, flexible: bool) -> Self {
+            self.details.is_flexible = Some(flexible);
+            self
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+/// Corresponds to the "sendInvoice" method of the API.
+///
+/// ```no_run
+/// use telegram_bot::*;
+///
+/// let api = Api::from_env("TELEGRAM_BOT_TOKEN").unwrap();
+/// let message = SendInvoice::stars(1234, "Premium", "One month of premium",
+///                                  "premium-1234", 250)
+///     .send(&api);
+/// ```
+#[derive(Debug, PartialEq, Clone)]
+pub struct SendInvoice {
+    chat_id: ChatRef,
+    details: InvoiceDetails,
+    start_parameter: Option<String>,
+    reply_to_message_id: Option<MessageId>,
+    reply_markup: Option<ReplyMarkup>,
+}
+
+impl SendInvoice {
+    /// An invoice with the given prices, which are summed up. `payload` is
+    /// passed back in the `PreCheckoutQuery` and `SuccessfulPayment` and
+    /// isn't shown to the user.
+    pub fn new<C, T, D, P, U>(chat: C, title: T, description: D, payload: P, currency: U,
+                              prices: Vec<LabeledPrice>) -> SendInvoice
+        where C: Into<ChatRef>, T: Into<String>, D: Into<String>, P: Into<String>,
+              U: Into<String>
+    {
+        SendInvoice {
+            chat_id: chat.into(),
+            details: InvoiceDetails::new(title.into(), description.into(), payload.into(),
+                                         currency.into(), prices),
+            start_parameter: None,
+            reply_to_message_id: None,
+            reply_markup: None,
+        }
+    }
+
+    /// An invoice for `amount` Telegram Stars.
+    pub fn stars<C, T, D, P>(chat: C, title: T, description: D, payload: P, amount: Integer)
+                             -> SendInvoice
+        where C: Into<ChatRef>, T: Into<String>, D: Into<String>, P: Into<String>
+    {
+        let title = title.into();
+        let prices = vec![LabeledPrice::new(&*title, amount)];
+        SendInvoice::new(chat, title, description, payload, STARS, prices)
+    }
+
+    invoice_options!();
+
+    /// Lets forwarded copies of the invoice start the bot with the given
+    /// parameter instead of paying directly.
+    pub fn start_parameter<T: Into<String>>(mut self, parameter: T) -> Self {
+        self.start_parameter = Some(parameter.into());
+        self
+    }
+
+    pub fn reply_to(mut self, message_id: MessageId) -> Self {
+        self.reply_to_message_id = Some(message_id);
+        self
+    }
+
+    /// The first button of the markup has to be a pay button.
+    pub fn reply_markup<R: Into<ReplyMarkup>>(mut self, markup: R) -> Self {
+        self.reply_markup = Some(markup.into());
+        self
+    }
+}
+
+impl Request for SendInvoice {
+    type Response = Message;
+
+    fn method(&self) -> &'static str {
+        "sendInvoice"
+    }
+
+    fn params(&self, params: &mut Params) -> Result<()> {
+        params.add_get("chat_id", &self.chat_id);
+        try!(self.details.params(params));
+        params.add_get_opt("start_parameter", self.start_parameter.as_ref());
+        params.add_get_opt("reply_to_message_id", self.reply_to_message_id);
+        params.add_get_json_opt("reply_markup", self.reply_markup.as_ref())
+    }
+
+    fn target_chat(&self) -> Option<&ChatRef> {
+        Some(&self.chat_id)
+    }
+}
+
+// ---------------------------------------------------------------------------
+/// Corresponds to the "createInvoiceLink" method of the API. Returns a link
+/// to pay the invoice, which can be shared anywhere.
+#[derive(Debug, PartialEq, Clone)]
+pub struct CreateInvoiceLink {
+    details: InvoiceDetails,
+}
+
+impl CreateInvoiceLink {
+    /// See `SendInvoice::new`.
+    pub fn new<T, D, P, U>(title: T, description: D, payload: P, currency: U,
+                           prices: Vec<LabeledPrice>) -> CreateInvoiceLink
+        where T: Into<String>, D: Into<String>, P: Into<String>, U: Into<String>
+    {
+        CreateInvoiceLink {
+            details: InvoiceDetails::new(title.into(), description.into(), payload.into(),
+                                         currency.into(), prices),
+        }
+    }
+
+    /// A link to pay `amount` Telegram Stars.
+    pub fn stars<T, D, P>(title: T, description: D, payload: P, amount: Integer)
+                          -> CreateInvoiceLink
+        where T: Into<String>, D: Into<String>, P: Into<String>
+    {
+        let title = title.into();
+        let prices = vec![LabeledPrice::new(&*title, amount)];
+        CreateInvoiceLink::new(title, description, payload, STARS, prices)
+    }
+
+    invoice_options!();
+}
+
+impl Request for CreateInvoiceLink {
+    type Response = String;
+
+    fn method(&self) -> &'static str {
+        "createInvoiceLink"
+    }
+
+    fn params(&self, params: &mut Params) -> Result<()> {
+        self.details.params(params)
+    }
+}
+
+// ---------------------------------------------------------------------------
+/// Corresponds to the "answerShippingQuery" method of the API.
+#[derive(Debug, PartialEq, Clone)]
+pub struct AnswerShippingQuery {
+    shipping_query_id: String,
+    shipping_options: Option<Vec<ShippingOption>>,
+    error_message: Option<String>,
+}
+
+impl AnswerShippingQuery {
+    /// Offers the given shipping options for the address of the query.
+    pub fn ok<T: Into<String>>(shipping_query_id: T, options: Vec<ShippingOption>)
+                               -> AnswerShippingQuery {
+        AnswerShippingQuery {
+            shipping_query_id: shipping_query_id.into(),
+            shipping_options: Some(options),
+            error_message: None,
+        }
+    }
+
+    /// Rejects the address with a message shown to the user.
+    pub fn error<T, M>(shipping_query_id: T, message: M) -> AnswerShippingQuery
+        where T: Into<String>, M: Into<String>
+    {
+        AnswerShippingQuery {
+            shipping_query_id: shipping_query_id.into(),
+            shipping_options: None,
+            error_message: Some(message.into()),
+        }
+    }
+}
+
+impl Request for AnswerShippingQuery {
+    type Response = bool;
+
+    fn method(&self) -> &'static str {
+        "answerShippingQuery"
+    }
+
+    fn params(&self, params: &mut Params) -> Result<()> {
+        params.add_get("shipping_query_id", &*self.shipping_query_id);
+        params.add_get("ok", self.shipping_options.is_some());
+        try!(params.add_get_json_opt("shipping_options", self.shipping_options.as_ref()));
+        params.add_get_opt("error_message", self.error_message.as_ref());
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------
+/// Corresponds to the "answerPreCheckoutQuery" method of the API.
+#[derive(Debug, PartialEq, Clone)]
+pub struct AnswerPreCheckoutQuery {
+    pre_checkout_query_id: String,
+    error_message: Option<String>,
+}
+
+impl AnswerPreCheckoutQuery {
+    /// Confirms that the order can be completed. The payment is only made
+    /// afterwards, the bot receives a `SuccessfulPayment` message then.
+    pub fn ok<T: Into<String>>(pre_checkout_query_id: T) -> AnswerPreCheckoutQuery {
+        AnswerPreCheckoutQuery {
+            pre_checkout_query_id: pre_checkout_query_id.into(),
+            error_message: None,
+        }
+    }
+
+    /// Cancels the order with a message shown to the user, e.g. because the
+    /// goods are sold out.
+    pub fn error<T, M>(pre_checkout_query_id: T, message: M) -> AnswerPreCheckoutQuery
+        where T: Into<String>, M: Into<String>
+    {
+        AnswerPreCheckoutQuery {
+            pre_checkout_query_id: pre_checkout_query_id.into(),
+            error_message: Some(message.into()),
+        }
+    }
+}
+
+impl Request for AnswerPreCheckoutQuery {
+    type Response = bool;
+
+    fn method(&self) -> &'static str {
+        "answerPreCheckoutQuery"
+    }
+
+    fn params(&self, params: &mut Params) -> Result<()> {
+        params.add_get("pre_checkout_query_id", &*self.pre_checkout_query_id);
+        params.add_get("ok", self.error_message.is_none());
+        params.add_get_opt("error_message", self.error_message.as_ref());
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------
+/// Corresponds to the "refundStarPayment" method of the API.
+#[derive(Debug, PartialEq, Clone)]
+pub struct RefundStarPayment {
+    user_id: UserId,
+    telegram_payment_charge_id: String,
+}
+
+impl RefundStarPayment {
+    /// Refunds the payment in Telegram Stars with the given
+    /// `SuccessfulPayment::telegram_payment_charge_id`.
+    pub fn new<T: Into<String>>(user_id: UserId, telegram_payment_charge_id: T)
+                                -> RefundStarPayment {
+        RefundStarPayment {
+            user_id: user_id,
+            telegram_payment_charge_id: telegram_payment_charge_id.into(),
+        }
+    }
+}
+
+impl Request for RefundStarPayment {
+    type Response = bool;
+
+    fn method(&self) -> &'static str {
+        "refundStarPayment"
+    }
+
+    fn params(&self, params: &mut Params) -> Result<()> {
+        params.add_get("user_id", self.user_id);
+        params.add_get("telegram_payment_charge_id", &*self.telegram_payment_charge_id);
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------
+/// Corresponds to the "getStickerSet" method of the API.
+#[derive(Debug, PartialEq, Clone)]
+pub struct GetStickerSet {
+    name: String,
+}
+
+impl GetStickerSet {
+    pub fn new<N: Into<String>>(name: N) -> GetStickerSet {
+        GetStickerSet {
+            name: name.into(),
+        }
+    }
+}
+
+impl Request for GetStickerSet {
+    type Response = StickerSet;
+
+    fn method(&self) -> &'static str {
+        "getStickerSet"
+    }
+
+    fn params(&self, params: &mut Params) -> Result<()> {
+        params.add_get("name", &*self.name);
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------
+/// Corresponds to the "uploadStickerFile" method of the API. The returned
+/// file can be used for several stickers, in several sets.
+#[derive(Debug, PartialEq, Clone)]
+pub struct UploadStickerFile {
+    user_id: UserId,
+    sticker: InputFile,
+    sticker_format: StickerFormat,
+}
+
+impl UploadStickerFile {
+    /// `user_id` is the owner of the sticker sets the file is used in.
+    pub fn new(user_id: UserId, sticker: InputFile, format: StickerFormat)
+               -> UploadStickerFile {
+        UploadStickerFile {
+            user_id: user_id,
+            sticker: sticker,
+            sticker_format: format,
+        }
+    }
+}
+
+impl Request for UploadStickerFile {
+    type Response = File;
+
+    fn method(&self) -> &'static str {
+        "uploadStickerFile"
+    }
+
+    fn params(&self, params: &mut Params) -> Result<()> {
+        params.add_get("user_id", self.user_id);
+        params.add_get("sticker_format", self.sticker_format);
+        params.add_file("sticker", self.sticker.clone());
+        Ok(())
+    }
+}
+
+/// A sticker to add to a sticker set (Telegram type "InputSticker").
+#[derive(Debug, PartialEq, Clone)]
+pub struct InputSticker {
+    sticker: FileSource,
+    format: StickerFormat,
+    emoji_list: Vec<String>,
+    mask_position: Option<MaskPosition>,
+    keywords: Option<Vec<String>>,
+}
+
+// How an "InputSticker" is encoded, with the file as reference
+#[derive(Serialize)]
+struct InputStickerRef<'a> {
+    sticker: String,
+    format: StickerFormat,
+    emoji_list: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mask_position: Option<&'a MaskPosition>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    keywords: Option<&'a [String]>,
+}
+
+impl InputSticker {
+    /// A sticker from the given file with the given emoji (1-20).
+    pub fn new<F, E>(sticker: F, format: StickerFormat, emoji_list: Vec<E>) -> InputSticker
+        where F: Into<FileSource>, E: Into<String>
+    {
+        InputSticker {
+            sticker: sticker.into(),
+            format: format,
+            emoji_list: emoji_list.into_iter().map(Into::into).collect(),
+            mask_position: None,
+            keywords: None,
+        }
+    }
+
+    /// Where the mask is placed on faces. Only for masks.
+    pub fn mask_position(mut self, position: MaskPosition) -> Self {
+        self.mask_position = Some(position);
+        self
+    }
+
+    /// Search keywords (0-20). Only for regular and custom emoji stickers.
+    pub fn keywords<K: Into<String>>(mut self, keywords: Vec<K>) -> Self {
+        self.keywords = Some(keywords.into_iter().map(Into::into).collect());
+        self
+    }
+
+    // Attaches an upload with the given name.
+    fn attach<'a>(&'a self, name: String, params: &mut Params) -> InputStickerRef<'a> {
+        InputStickerRef {
+            sticker: params.attach(name, &self.sticker),
+            format: self.format,
+            emoji_list: &self.emoji_list,
+            mask_position: self.mask_position.as_ref(),
+            keywords: self.keywords.as_ref().map(|k| &**k),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+/// Corresponds to the "createNewStickerSet" method of the API.
+///
+/// ```no_run
+/// use telegram_bot::*;
+///
+/// let api = Api::from_env("TELEGRAM_BOT_TOKEN").unwrap();
+/// let stickers = vec![
+///     InputSticker::new(InputFile::from_path("happy.webp").unwrap(),
+///                       StickerFormat::Static, vec!["😀"]),
+///     InputSticker::new(FileId::from("CAACAgIAAxkBAAIB"), StickerFormat::Static, vec!["😢"]),
+/// ];
+/// let created = CreateNewStickerSet::new(UserId(1234), "moods_by_my_bot", "Moods", stickers)
+///     .send(&api);
+/// ```
+#[derive(Debug, PartialEq, Clone)]
+pub struct CreateNewStickerSet {
+    user_id: UserId,
+    name: String,
+    title: String,
+    stickers: Vec<InputSticker>,
+    sticker_type: Option<StickerKind>,
+    needs_repainting: Option<bool>,
+}
+
+impl CreateNewStickerSet {
+    /// A new set owned by `user_id` with 1-50 stickers. The name has to end
+    /// with "_by_<bot username>".
+    pub fn new<N, T>(user_id: UserId, name: N, title: T, stickers: Vec<InputSticker>)
+                     -> CreateNewStickerSet
+        where N: Into<String>, T: Into<String>
+    {
+        CreateNewStickerSet {
+            user_id: user_id,
+            name: name.into(),
+            title: title.into(),
+            stickers: stickers,
+            sticker_type: None,
+            needs_repainting: None,
+        }
+    }
+
+    /// Regular stickers by default.
+    pub fn sticker_type(mut self, kind: StickerKind) -> Self {
+        self.sticker_type = Some(kind);
+        self
+    }
+
+    /// Custom emoji are shown in the color of the text. Only for custom
+    /// emoji sets.
+    pub fn needs_repainting(mut self, repaint: bool) -> Self {
+        self.needs_repainting = Some(repaint);
+        self
+    }
+}
+
+impl Request for CreateNewStickerSet {
+    type Response = bool;
+
+    fn method(&self) -> &'static str {
+        "createNewStickerSet"
+    }
+
+    fn params(&self, params: &mut Params) -> Result<()> {
+        let stickers: Vec<_> = self.stickers.iter().enumerate()
+            .map(|(i, s)| s.attach(format!("sticker{}", i), params))
+            .collect();
+
+        params.add_get("user_id", self.user_id);
+        params.add_get("name", &*self.name);
+        params.add_get("title", &*self.title);
+        try!(params.add_get_json_opt("stickers", Some(&stickers)));
+        params.add_get_opt("sticker_type", self.sticker_type);
+        params.add_get_opt("needs_repainting", self.needs_repainting);
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------
+/// Corresponds to the "addStickerToSet" method of the API.
+#[derive(Debug, PartialEq, Clone)]
+pub struct AddStickerToSet {
+    user_id: UserId,
+    name: String,
+    sticker: InputSticker,
+}
+
+impl AddStickerToSet {
+    /// Adds a sticker to the set `name` owned by `user_id`.
+    pub fn new<N: Into<String>>(user_id: UserId, name: N, sticker: InputSticker)
+                                -> AddStickerToSet {
+        AddStickerToSet {
+            user_id: user_id,
+            name: name.into(),
+            sticker: sticker,
+        }
+    }
+}
+
+impl Request for AddStickerToSet {
+    type Response = bool;
+
+    fn method(&self) -> &'static str {
+        "addStickerToSet"
+    }
+
+    fn params(&self, params: &mut Params) -> Result<()> {
+        let sticker = self.sticker.attach("sticker".into(), params);
+
+        params.add_get("user_id", self.user_id);
+        params.add_get("name", &*self.name);
+        params.add_get_json_opt("sticker", Some(&sticker))
+    }
+}
+
+// ---------------------------------------------------------------------------
+/// Corresponds to the "setStickerPositionInSet" method of the API.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SetStickerPositionInSet {
+    sticker: FileId,
+    position: Integer,
+}
+
+impl SetStickerPositionInSet {
+    /// Moves the sticker to the given position (starting with 0) in its set.
+    pub fn new(sticker: FileId, position: Integer) -> SetStickerPositionInSet {
+        SetStickerPositionInSet {
+            sticker: sticker,
+            position: position,
+        }
+    }
+}
+
+impl Request for SetStickerPositionInSet {
+    type Response = bool;
+
+    fn method(&self) -> &'static str {
+        "setStickerPositionInSet"
+    }
+
+    fn params(&self, params: &mut Params) -> Result<()> {
+        params.add_get("sticker", &self.sticker);
+        params.add_get("position", self.position);
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------
+/// Corresponds to the "deleteStickerFromSet" method of the API.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DeleteStickerFromSet {
+    sticker: FileId,
+}
+
+impl DeleteStickerFromSet {
+    pub fn new(sticker: FileId) -> DeleteStickerFromSet {
+        DeleteStickerFromSet {
+            sticker: sticker,
+        }
+    }
+}
+
+impl Request for DeleteStickerFromSet {
+    type Response = bool;
+
+    fn method(&self) -> &'static str {
+        "deleteStickerFromSet"
+    }
+
+    fn params(&self, params: &mut Params) -> Result<()> {
+        params.add_get("sticker", &self.sticker);
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------
+/// Corresponds to the "setStickerSetThumbnail" method of the API.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SetStickerSetThumbnail {
+    name: String,
+    user_id: UserId,
+    thumbnail: Option<FileSource>,
+    format: StickerFormat,
+}
+
+impl SetStickerSetThumbnail {
+    /// Without a `thumbnail`, the first sticker is used as thumbnail.
+    pub fn new<N: Into<String>>(name: N, user_id: UserId, format: StickerFormat)
+                                -> SetStickerSetThumbnail {
+        SetStickerSetThumbnail {
+            name: name.into(),
+            user_id: user_id,
+            thumbnail: None,
+            format: format,
+        }
+    }
+
+    /// The thumbnail in the given format: A 100x100 image, a TGS animation
+    /// or a WEBM video.
+    pub fn thumbnail<F: Into<FileSource>>(mut self, thumbnail: F) -> Self {
+        self.thumbnail = Some(thumbnail.into());
+        self
+    }
+}
+
+impl Request for SetStickerSetThumbnail {
+    type Response = bool;
+
+    fn method(&self) -> &'static str {
+        "setStickerSetThumbnail"
+    }
+
+    fn params(&self, params: &mut Params) -> Result<()> {
+        params.add_get("name", &*self.name);
+        params.add_get("user_id", self.user_id);
+        if let Some(ref thumbnail) = self.thumbnail {
+            params.add_file_source("thumbnail", thumbnail);
+        }
+        params.add_get("format", self.format);
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------
+/// A photo, video, document or audio file of a media group (Telegram type
+/// "InputMedia").
+#[derive(Debug, PartialEq, Clone)]
+pub struct InputMedia {
+    kind: &'static str,
+    media: FileSource,
+    caption: Option<String>,
+    parse_mode: Option<ParseMode>,
+    has_spoiler: Option<bool>,
+    width: Option<Integer>,
+    height: Option<Integer>,
+    duration: Option<Integer>,
+    supports_streaming: Option<bool>,
+    performer: Option<String>,
+    title: Option<String>,
+}
+
+// How an "InputMedia" is encoded, with the file as reference
+#[derive(Serialize)]
+struct InputMediaRef<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    media: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    caption: Option<&'a String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parse_mode: Option<ParseMode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    has_spoiler: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    width: Option<Integer>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    height: Option<Integer>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration: Option<Integer>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    supports_streaming: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    performer: Option<&'a String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<&'a String>,
+}
+
+impl InputMedia {
+    fn new(kind: &'static str, media: FileSource) -> InputMedia {
+        InputMedia {
+            kind: kind,
+            media: media,
+            caption: None,
+            parse_mode: None,
+            has_spoiler: None,
+            width: None,
+            height: None,
+            duration: None,
+            supports_streaming: None,
+            performer: None,
+            title: None,
+        }
+    }
+
+    pub fn photo<F: Into<FileSource>>(media: F) -> InputMedia {
+        InputMedia::new("photo", media.into())
+    }
+
+    pub fn video<F: Into<FileSource>>(media: F) -> InputMedia {
+        InputMedia::new("video", media.into())
+    }
+
+    /// Documents can only be grouped with other documents.
+    pub fn document<F: Into<FileSource>>(media: F) -> InputMedia {
+        InputMedia::new("document", media.into())
+    }
+
+    /// Audio files can only be grouped with other audio files.
+    pub fn audio<F: Into<FileSource>>(media: F) -> InputMedia {
+        InputMedia::new("audio", media.into())
+    }
+
+    pub fn caption<T: Into<String>>(mut self, caption: T) -> Self {
+        self.caption = Some(caption.into());
+        self
+    }
+
+    pub fn parse_mode(mut self, parse_mode: ParseMode) -> Self {
+        self.parse_mode = Some(parse_mode);
+        self
+    }
+
+    /// Covers the photo or video until it's tapped.
+    pub fn spoiler(mut self, spoiler: bool) -> Self {
+        self.has_spoiler = Some(spoiler);
+        self
+    }
+
+    /// Only for videos.
+    pub fn size(mut self, width: Integer, height: Integer) -> Self {
+        self.width = Some(width);
+        self.height = Some(height);
+        self
+    }
+
+    /// Duration in seconds. Only for videos and audio files.
+    pub fn duration(mut self, duration: Integer) -> Self {
+        self.duration = Some(duration);
+        self
+    }
+
+    /// Only for videos.
+    pub fn supports_streaming(mut self, streaming: bool) -> Self {
+        self.supports_streaming = Some(streaming);
+        self
+    }
+
+    /// Only for audio files.
+    pub fn performer<T: Into<String>>(mut self, performer: T) -> Self {
+        self.performer = Some(performer.into());
+        self
+    }
+
+    /// Only for audio files.
+    pub fn title<T: Into<String>>(mut self, title: T) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    // Attaches an upload with the given name.
+    fn attach<'a>(&'a self, name: String, params: &mut Params) -> InputMediaRef<'a> {
+        InputMediaRef {
+            kind: self.kind,
+            media: params.attach(name, &self.media),
+            caption: self.caption.as_ref(),
+            parse_mode: self.parse_mode,
+            has_spoiler: self.has_spoiler,
+            width: self.width,
+            height: self.height,
+            duration: self.duration,
+            supports_streaming: self.supports_streaming,
+            performer: self.performer.as_ref(),
+            title: self.title.as_ref(),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+/// Corresponds to the "sendMediaGroup" method of the API. Returns the sent
+/// messages, which share their `media_group_id`.
+///
+/// ```no_run
+/// use telegram_bot::*;
+///
+/// let api = Api::from_env("TELEGRAM_BOT_TOKEN").unwrap();
+/// let messages = SendMediaGroup::new(1234, vec![
+///     InputMedia::photo(FileId::from("AgACAgQAAxkBAAIBTW")).caption("Before"),
+///     InputMedia::photo(InputFile::from_path("after.jpg").unwrap()).caption("After"),
+/// ]).send(&api);
+/// ```
+#[derive(Debug, PartialEq, Clone)]
+pub struct SendMediaGroup {
+    chat_id: ChatRef,
+    media: Vec<InputMedia>,
+    reply_to_message_id: Option<MessageId>,
+}
+
+impl SendMediaGroup {
+    /// An album of 2-10 files.
+    pub fn new<C: Into<ChatRef>>(chat: C, media: Vec<InputMedia>) -> SendMediaGroup {
+        SendMediaGroup {
+            chat_id: chat.into(),
+            media: media,
+            reply_to_message_id: None,
+        }
+    }
+
+    pub fn reply_to(mut self, message_id: MessageId) -> Self {
+        self.reply_to_message_id = Some(message_id);
+        self
+    }
+}
+
+impl Request for SendMediaGroup {
+    type Response = Vec<Message>;
+
+    fn method(&self) -> &'static str {
+        "sendMediaGroup"
+    }
+
+    fn params(&self, params: &mut Params) -> Result<()> {
+        let media: Vec<_> = self.media.iter().enumerate()
+            .map(|(i, m)| m.attach(format!("media{}", i), params))
+            .collect();
+
+        params.add_get("chat_id", &self.chat_id);
+        try!(params.add_get_json_opt("media", Some(&media)));
+        params.add_get_opt("reply_to_message_id", self.reply_to_message_id);
+        Ok(())
+    }
+
+    fn target_chat(&self) -> Option<&ChatRef> {
+        Some(&self.chat_id)
+    }
+
+    fn message_count(&self) -> u32 {
+        self.media.len() as u32
+    }
+}
+
+// ---------------------------------------------------------------------------
+/// Corresponds to the "sendChatAction" method of the API.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SendChatAction {
+    chat_id: ChatRef,
+    action: ChatAction,
+}
+
+impl SendChatAction {
+    pub fn new<C: Into<ChatRef>>(chat: C, action: ChatAction) -> SendChatAction {
+        SendChatAction {
+            chat_id: chat.into(),
+            action: action,
+        }
+    }
+}
+
+impl Request for SendChatAction {
+    type Response = bool;
+
+    fn method(&self) -> &'static str {
+        "sendChatAction"
+    }
+
+    fn params(&self, params: &mut Params) -> Result<()> {
+        params.add_get("chat_id", &self.chat_id);
+        params.add_get("action", self.action);
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------
+/// Corresponds to the "getUserProfilePhotos" method of the API.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct GetUserProfilePhotos {
+    user_id: UserId,
+    offset: Option<Integer>,
+    limit: Option<Integer>,
+}
+
+impl GetUserProfilePhotos {
+    pub fn new(user_id: UserId) -> GetUserProfilePhotos {
+        GetUserProfilePhotos {
+            user_id: user_id,
+            offset: None,
+            limit: None,
+        }
+    }
+
+    pub fn offset(mut self, offset: Integer) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    pub fn limit(mut self, limit: Integer) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+}
+
+impl Request for GetUserProfilePhotos {
+    type Response = UserProfilePhotos;
+
+    fn method(&self) -> &'static str {
+        "getUserProfilePhotos"
+    }
+
+    fn params(&self, params: &mut Params) -> Result<()> {
+        params.add_get("user_id", self.user_id);
+        params.add_get_opt("offset", self.offset);
+        params.add_get_opt("limit", self.limit);
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------
+/// Corresponds to the "getUpdates" method of the API.
+///
+/// **Note:**
+/// You usually want to use a `Listener` instead, which takes care of the
+/// offset.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct GetUpdates {
+    offset: Option<UpdateId>,
+    limit: Option<Integer>,
+    timeout: Option<Integer>,
+    allowed_updates: Option<BTreeSet<AllowedUpdate>>,
+}
+
+impl GetUpdates {
+    pub fn new() -> GetUpdates {
+        GetUpdates::default()
+    }
+
+    /// Only receive updates from this id on. Earlier updates are confirmed
+    /// and not sent again.
+    pub fn offset(mut self, offset: UpdateId) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    pub fn limit(mut self, limit: Integer) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Integer) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Only receive updates of the given kinds. Telegram remembers this
+    /// setting for later requests. An empty list resets it to the default
+    /// (all kinds except `ChatMember`). Duplicates are sent only once.
+    pub fn allowed_updates<'a, I>(mut self, allowed: I) -> Self
+        where I: IntoIterator<Item = &'a AllowedUpdate>
+    {
+        self.allowed_updates = Some(allowed.into_iter().cloned().collect());
+        self
+    }
+}
+
+impl Request for GetUpdates {
+    type Response = Vec<Update>;
+
+    fn method(&self) -> &'static str {
+        "getUpdates"
+    }
+
+    fn params(&self, params: &mut Params) -> Result<()> {
+        params.add_get_opt("offset", self.offset);
+        params.add_get_opt("limit", self.limit);
+        params.add_get_opt("timeout", self.timeout);
+        try!(params.add_get_json_opt("allowed_updates", self.allowed_updates.as_ref()));
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------
+/// Corresponds to the "setWebhook" method of the API.
+///
+/// **Note:**
+/// Use a `webhook::WebhookServer` to receive the updates.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SetWebhook {
+    url: Option<Url>,
+    certificate: Option<InputFile>,
+    ip_address: Option<String>,
+    max_connections: Option<Integer>,
+    allowed_updates: Option<BTreeSet<AllowedUpdate>>,
+    drop_pending_updates: Option<bool>,
+    secret_token: Option<String>,
+}
+
+impl SetWebhook {
+    /// Sets the webhook to the given URL.
+    pub fn new(url: Url) -> SetWebhook {
+        SetWebhook::with_url(Some(url))
+    }
+
+    /// Removes the current webhook. Same as `DeleteWebhook`.
+    pub fn remove() -> SetWebhook {
+        SetWebhook::with_url(None)
+    }
+
+    fn with_url(url: Option<Url>) -> SetWebhook {
+        SetWebhook {
+            url: url,
+            certificate: None,
+            ip_address: None,
+            max_connections: None,
+            allowed_updates: None,
+            drop_pending_updates: None,
+            secret_token: None,
+        }
+    }
+
+    /// Uploads the public key certificate, if the server uses a self-signed
+    /// one.
+    pub fn certificate(mut self, certificate: InputFile) -> Self {
+        self.certificate = Some(certificate);
+        self
+    }
+
+    /// Sends the updates to this IP address instead of the one resolved via
+    /// DNS.
+    pub fn ip_address<S: Into<String>>(mut self, ip_address: S) -> Self {
+        self.ip_address = Some(ip_address.into());
+        self
+    }
+
+    /// Maximum number of simultaneous connections (1-100) for delivering
+    /// updates. Default: 40.
+    pub fn max_connections(mut self, max_connections: Integer) -> Self {
+        self.max_connections = Some(max_connections);
+        self
+    }
+
+    /// Only receive updates of the given kinds, see
+    /// `GetUpdates::allowed_updates`.
+    pub fn allowed_updates<'a, I>(mut self, allowed: I) -> Self
+        where I: IntoIterator<Item = &'a AllowedUpdate>
+    {
+        self.allowed_updates = Some(allowed.into_iter().cloned().collect());
+        self
+    }
+
+    /// Drops all updates which were not received yet.
+    pub fn drop_pending_updates(mut self, drop: bool) -> Self {
+        self.drop_pending_updates = Some(drop);
+        self
+    }
+
+    /// Telegram sends this token in the "X-Telegram-Bot-Api-Secret-Token"
+    /// header of every webhook request, so the server can verify that a
+    /// request really comes from Telegram. 1-256 characters `A-Z`, `a-z`,
+    /// `0-9`, `_` and `-`.
+    pub fn secret_token<S: Into<String>>(mut self, token: S) -> Self {
+        self.secret_token = Some(token.into());
+        self
+    }
+}
+
+impl Request for SetWebhook {
+    type Response = bool;
+
+    fn method(&self) -> &'static str {
+        "setWebhook"
+    }
+
+    fn params(&self, params: &mut Params) -> Result<()> {
+        params.add_get("url", self.url.as_ref().map_or("", |u| u.as_str()));
+        params.add_get_opt("ip_address", self.ip_address.as_ref());
+        params.add_get_opt("max_connections", self.max_connections);
+        try!(params.add_get_json_opt("allowed_updates", self.allowed_updates.as_ref()));
+        params.add_get_opt("drop_pending_updates", self.drop_pending_updates);
+        params.add_get_opt("secret_token", self.secret_token.as_ref());
+        if let Some(ref certificate) = self.certificate {
+            params.add_file("certificate", certificate.clone());
+        }
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------
+/// Corresponds to the "deleteWebhook" method of the API.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct DeleteWebhook {
+    drop_pending_updates: Option<bool>,
+}
+
+impl DeleteWebhook {
+    pub fn new() -> DeleteWebhook {
+        DeleteWebhook::default()
+    }
+
+    /// Drops all updates which were not received yet.
+    pub fn drop_pending_updates(mut self, drop: bool) -> Self {
+        self.drop_pending_updates = Some(drop);
+        self
+    }
+}
+
+impl Request for DeleteWebhook {
+    type Response = bool;
+
+    fn method(&self) -> &'static str {
+        "deleteWebhook"
+    }
+
+    fn params(&self, params: &mut Params) -> Result<()> {
+        params.add_get_opt("drop_pending_updates", self.drop_pending_updates);
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------
+/// Corresponds to the "getWebhookInfo" method of the API.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct GetWebhookInfo;
+
+impl Request for GetWebhookInfo {
+    type Response = WebhookInfo;
+
+    fn method(&self) -> &'static str {
+        "getWebhookInfo"
+    }
+
+    fn params(&self, _: &mut Params) -> Result<()> {
+        Ok(())
+    }
+}
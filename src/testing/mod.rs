@@ -0,0 +1,1388 @@
+//! A fake Telegram Bot API server for integration tests.
+//!
+//! This module is only available with the `testing` feature. It starts a
+//! small HTTP server on localhost which understands the most important API
+//! methods and keeps all chats, messages and updates in memory. An `Api`
+//! obtained via `FakeServer::api` talks to that server instead of the real
+//! Telegram servers, so bots can be tested end-to-end without network
+//! access:
+//!
+//! ```no_run
+//! use telegram_bot::*;
+//! use telegram_bot::testing::FakeServer;
+//!
+//! let server = FakeServer::start().unwrap();
+//! let api = server.api();
+//!
+//! // Pretend a user wrote something to our bot
+//! let user = User {
+//!     id: UserId(42),
+//!     first_name: "Alice".into(),
+//!     last_name: None,
+//!     username: None,
+//! };
+//! let chat = Chat::Private {
+//!     id: ChatId(42),
+//!     first_name: "Alice".into(),
+//!     last_name: None,
+//!     username: None,
+//! };
+//! server.inject_text(&user, &chat, "/start");
+//!
+//! // Run the bot ...
+//! let mut listener = api.listener(ListeningMethod::LongPoll(None));
+//! listener.listen(|u| {
+//!     if let Some(m) = u.message {
+//!         try!(api.send_message(m.chat.id(), "Welcome!".into(),
+//!                               None, None, None, None));
+//!     }
+//!     Ok(ListeningAction::Stop)
+//! }).unwrap();
+//!
+//! // ... and check what it did.
+//! assert_eq!(server.sent_messages()[0].msg, MessageType::Text("Welcome!".into()));
+//! ```
+//!
+//! Supported methods are `getMe`, `getUpdates`, `sendMessage`,
+//! `forwardMessage`, `editMessageText`, `deleteMessage`, `sendLocation`,
+//! `sendChatAction`, `getUserProfilePhotos`, `setWebhook`, `deleteWebhook`
+//! and `getWebhookInfo`. All other methods are answered with a "Not Found"
+//! API error.
+
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use hyper::server::{Handler, Listening, Request, Response, Server};
+use hyper::status::StatusCode;
+use hyper::uri::RequestUri;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{self, Map, Value};
+use url::form_urlencoded;
+
+use types::{Chat, ChatId, Integer, LabeledPrice, Message, MessageId, ShippingAddress, Update,
+            UpdateId, User, UserId, STARS};
+use {Api, Result};
+
+/// The token every `FakeServer` expects. Requests with another token are
+/// answered with an "Unauthorized" API error.
+pub const TOKEN: &'static str = "123456:fake-token";
+
+/// The longest time (in seconds) a `getUpdates` request is held open by the
+/// fake server. This is shorter than the read timeout of the `Api` client.
+pub const MAX_POLL_TIMEOUT: Integer = 1;
+
+/// A request the fake server received.
+#[derive(Debug, PartialEq, Clone)]
+pub struct RecordedRequest {
+    /// Name of the API method, e.g. "sendMessage".
+    pub method: String,
+    /// All parameters in the order they were sent.
+    pub params: Vec<(String, String)>,
+    /// All uploaded files in the order they were sent.
+    pub files: Vec<RecordedFile>,
+}
+
+/// A file uploaded with a request.
+#[derive(Debug, PartialEq, Clone)]
+pub struct RecordedFile {
+    /// Name of the parameter, e.g. "certificate".
+    pub field: String,
+    pub file_name: String,
+    pub data: Vec<u8>,
+}
+
+impl RecordedRequest {
+    /// Returns the value of the given parameter, if it was sent.
+    pub fn param(&self, key: &str) -> Option<&str> {
+        self.params.iter().find(|&&(ref k, _)| k == key).map(|&(_, ref v)| &**v)
+    }
+}
+
+/// An in-process fake of the Telegram Bot API.
+///
+/// The server is started with `start` and listens on a random port on
+/// localhost until it is dropped. Tests use `inject_text`, `inject_update` or
+/// `push_update` to simulate incoming updates and `sent_messages`,
+/// `messages_in` and `requests` to check what the bot did.
+pub struct FakeServer {
+    shared: Arc<Shared>,
+    listening: Listening,
+    url: String,
+}
+
+impl FakeServer {
+    /// Starts a new fake server listening on a random localhost port.
+    pub fn start() -> Result<FakeServer> {
+        let bot = User {
+            id: UserId(123456),
+            first_name: "Fake Bot".into(),
+            last_name: None,
+            username: Some("fake_bot".into()),
+        };
+
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                bot: to_json(&bot),
+                next_update_id: 1,
+                next_message_id: 1,
+                updates: Vec::new(),
+                chats: HashMap::new(),
+                blocked: HashSet::new(),
+                messages: Vec::new(),
+                requests: Vec::new(),
+                webhook: None,
+                poll_votes: HashMap::new(),
+                invoice_payloads: HashMap::new(),
+                flexible_invoices: HashSet::new(),
+                shipping_queries: HashSet::new(),
+                checkouts: HashMap::new(),
+                charges: HashMap::new(),
+                files: HashSet::new(),
+                sticker_sets: HashMap::new(),
+            }),
+            new_update: Condvar::new(),
+        });
+
+        let server = try!(Server::http("127.0.0.1:0"));
+        // Several threads, so an abandoned long poll doesn't block other
+        // requests.
+        let listening = try!(server.handle_threads(FakeHandler(shared.clone()), 8));
+        let url = format!("http://{}/bot", listening.socket);
+
+        Ok(FakeServer {
+            shared: shared,
+            listening: listening,
+            url: url,
+        })
+    }
+
+    /// The API-URL prefix of this server. Pass it to
+    /// `Api::from_token_and_url` together with `TOKEN`.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Creates an `Api` object talking to this server.
+    pub fn api(&self) -> Api {
+        Api::from_token_and_url(TOKEN, &self.url)
+            .expect("the fake server URL is always valid")
+    }
+
+    /// The user `getMe` returns.
+    pub fn bot(&self) -> User {
+        decode(&self.lock().bot)
+    }
+
+    /// Makes a chat known to the server, so that the bot can send messages
+    /// to it. Chats of injected updates are added automatically.
+    pub fn add_chat(&self, chat: &Chat) {
+        self.lock().chats.insert(chat.id(), to_json(chat));
+    }
+
+    /// Simulates a text message sent by `from` in `chat`. Returns the id of
+    /// the resulting update.
+    pub fn inject_text(&self, from: &User, chat: &Chat, text: &str) -> UpdateId {
+        let mut state = self.lock();
+        state.chats.insert(chat.id(), to_json(chat));
+
+        let message_id = state.next_message_id();
+        let message = object(vec![
+            ("message_id", Value::from(message_id)),
+            ("from", to_json(from)),
+            ("chat", to_json(chat)),
+            ("date", Value::from(now())),
+            ("text", Value::from(text)),
+        ]);
+        state.messages.push(Stored { outgoing: false, message: message.clone() });
+
+        let id = state.push_update(object(vec![("message", message)]));
+        self.shared.new_update.notify_all();
+        id
+    }
+
+    /// Simulates an album of `count` photos sent by `from` in `chat`. Every
+    /// photo is a separate message with the same `media_group_id`, the first
+    /// one has a caption. Returns the ids of the resulting updates.
+    pub fn inject_album(&self, from: &User, chat: &Chat, count: usize) -> Vec<UpdateId> {
+        let mut state = self.lock();
+        state.chats.insert(chat.id(), to_json(chat));
+
+        let group_id = state.next_message_id().to_string();
+        let ids = (0..count).map(|i| {
+            let photo = object(vec![
+                ("file_id", Value::from(state.new_file_id())),
+                ("width", Value::from(800)),
+                ("height", Value::from(600)),
+            ]);
+            let mut fields = vec![
+                ("from", to_json(from)),
+                ("chat", to_json(chat)),
+                ("media_group_id", Value::from(&*group_id)),
+                ("photo", Value::Array(vec![photo])),
+            ];
+            if i == 0 {
+                fields.push(("caption", Value::from(format!("Album {}", group_id))));
+            }
+            state.receive(object(fields))
+        }).collect();
+
+        self.shared.new_update.notify_all();
+        ids
+    }
+
+    /// Queues a raw update. The `update_id` field is set by the server, all
+    /// other fields are delivered as they are. Returns the update id.
+    ///
+    /// The message or channel post of the update is stored, so the bot can
+    /// forward or edit it.
+    ///
+    /// Panics if `update` is not a JSON object.
+    pub fn inject_update(&self, update: Value) -> UpdateId {
+        assert!(update.is_object(), "an update has to be a JSON object");
+
+        let mut state = self.lock();
+        for key in &["message", "channel_post"] {
+            let message = match update.get(key) {
+                Some(message) => message,
+                None => continue,
+            };
+            if let Some(chat) = message.get("chat") {
+                if let Some(id) = chat.get("id").and_then(Value::as_i64) {
+                    state.chats.insert(ChatId(id), chat.clone());
+                }
+            }
+            state.messages.push(Stored { outgoing: false, message: message.clone() });
+        }
+
+        let id = state.push_update(update);
+        self.shared.new_update.notify_all();
+        id
+    }
+
+    /// Queues an update, e.g. one which was received from the real API and
+    /// stored. Like `inject_update`, the `update_id` is set by the server.
+    pub fn push_update(&self, update: &Update) -> UpdateId {
+        self.inject_update(to_json(update))
+    }
+
+    /// Simulates that `from` voted for the given options (an empty list
+    /// retracts the vote) in a poll the bot sent. Like the real API, this
+    /// updates the stored poll and queues a "poll" update with its new
+    /// state, preceded by a "poll_answer" update for non-anonymous polls.
+    /// Returns the id of the last queued update.
+    ///
+    /// Panics if the bot didn't send a poll with the given id.
+    pub fn inject_poll_answer(&self, from: &User, poll_id: &str, option_ids: &[Integer])
+                              -> UpdateId {
+        let mut state = self.lock();
+        let previous = if option_ids.is_empty() {
+            state.poll_votes.remove(&(poll_id.into(), from.id))
+        } else {
+            state.poll_votes.insert((poll_id.into(), from.id), option_ids.to_vec())
+        };
+
+        let voters = state.poll_votes.keys().filter(|k| k.0 == poll_id).count();
+
+        let poll = {
+            let poll = state.messages.iter_mut()
+                .filter(|m| m.outgoing)
+                .filter_map(|m| m.message.get_mut("poll"))
+                .find(|p| p.get("id").and_then(Value::as_str) == Some(poll_id))
+                .expect("the bot didn't send a poll with this id");
+
+            let changes = previous.unwrap_or_default().into_iter().map(|id| (id, -1))
+                .chain(option_ids.iter().map(|&id| (id, 1)));
+            for (id, delta) in changes {
+                let key = format!("/options/{}/voter_count", id);
+                if let Some(count) = poll.pointer_mut(&key) {
+                    *count = Value::from(count.as_i64().unwrap_or(0) + delta);
+                }
+            }
+            poll["total_voter_count"] = Value::from(voters as Integer);
+            poll.clone()
+        };
+
+        if poll.get("is_anonymous") == Some(&Value::from(false)) {
+            state.push_update(object(vec![("poll_answer", object(vec![
+                ("poll_id", Value::from(poll_id)),
+                ("user", to_json(from)),
+                ("option_ids", to_json(&option_ids)),
+            ]))]));
+        }
+        let id = state.push_update(object(vec![("poll", poll)]));
+        self.shared.new_update.notify_all();
+        id
+    }
+
+    /// Simulates that `from` entered the shipping address for a flexible
+    /// invoice the bot sent in the given message by queueing a
+    /// "shipping_query" update. Returns the id of the query.
+    ///
+    /// Panics if there is no flexible invoice with the given message id.
+    pub fn inject_shipping_query(&self, from: &User, chat_id: ChatId, message_id: MessageId,
+                                 address: &ShippingAddress) -> String {
+        let mut state = self.lock();
+        let key = (chat_id.0, message_id.0);
+        assert!(state.flexible_invoices.contains(&key),
+                "the bot didn't send a flexible invoice in this message");
+        let payload = state.invoice_payloads[&key].clone();
+
+        let query_id = state.next_message_id().to_string();
+        state.shipping_queries.insert(query_id.clone());
+        state.push_update(object(vec![("shipping_query", object(vec![
+            ("id", Value::from(&*query_id)),
+            ("from", to_json(from)),
+            ("invoice_payload", Value::from(payload)),
+            ("shipping_address", to_json(address)),
+        ]))]));
+        self.shared.new_update.notify_all();
+        query_id
+    }
+
+    /// Simulates that `from` wants to pay the invoice the bot sent in the
+    /// given message by queueing a "pre_checkout_query" update. If the bot
+    /// answers it with `ok`, a message with the `SuccessfulPayment` is
+    /// queued. Returns the id of the pre-checkout query.
+    ///
+    /// Panics if there is no invoice with the given message id.
+    pub fn inject_checkout(&self, from: &User, chat_id: ChatId, message_id: MessageId)
+                           -> String {
+        let mut state = self.lock();
+        let invoice = state.message_index(chat_id.0, message_id.0)
+            .and_then(|i| state.messages[i].message.get("invoice").cloned())
+            .expect("the bot didn't send an invoice in this message");
+        let payload = state.invoice_payloads[&(chat_id.0, message_id.0)].clone();
+        let chat = state.chats[&chat_id].clone();
+
+        let query_id = state.next_message_id().to_string();
+        let charge_id = format!("fake-charge-{}", query_id);
+        let payment = object(vec![
+            ("currency", invoice["currency"].clone()),
+            ("total_amount", invoice["total_amount"].clone()),
+            ("invoice_payload", Value::from(&*payload)),
+            ("telegram_payment_charge_id", Value::from(charge_id)),
+            ("provider_payment_charge_id", Value::from("")),
+        ]);
+        let message = object(vec![
+            ("from", to_json(from)),
+            ("chat", chat),
+            ("successful_payment", payment),
+        ]);
+        state.checkouts.insert(query_id.clone(), message);
+
+        state.push_update(object(vec![("pre_checkout_query", object(vec![
+            ("id", Value::from(&*query_id)),
+            ("from", to_json(from)),
+            ("currency", invoice["currency"].clone()),
+            ("total_amount", invoice["total_amount"].clone()),
+            ("invoice_payload", Value::from(payload)),
+        ]))]));
+        self.shared.new_update.notify_all();
+        query_id
+    }
+
+    /// Simulates that the user of the given private chat blocked the bot (or
+    /// that the bot was removed from the group). All further messages to the
+    /// chat fail with a "Forbidden" API error.
+    pub fn block_chat(&self, chat_id: ChatId) {
+        self.lock().blocked.insert(chat_id);
+    }
+
+    /// Number of updates which were not yet confirmed by the bot.
+    pub fn pending_updates(&self) -> usize {
+        self.lock().updates.len()
+    }
+
+    /// All messages the bot sent (or forwarded), in order. Edited messages
+    /// are returned in their current state, deleted messages are missing.
+    pub fn sent_messages(&self) -> Vec<Message> {
+        self.lock().messages.iter()
+            .filter(|m| m.outgoing)
+            .map(|m| decode(&m.message))
+            .collect()
+    }
+
+    /// All messages the bot sent to the given chat, in order.
+    pub fn messages_in(&self, chat_id: ChatId) -> Vec<Message> {
+        self.sent_messages().into_iter()
+            .filter(|m| m.chat.id() == chat_id)
+            .collect()
+    }
+
+    /// All requests the server received so far, in order.
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.lock().requests.clone()
+    }
+
+    fn lock<'a>(&'a self) -> MutexGuard<'a, State> {
+        self.shared.state.lock().unwrap()
+    }
+}
+
+impl Drop for FakeServer {
+    fn drop(&mut self) {
+        // Dropping `Listening` would wait for the server thread, which never
+        // terminates on its own.
+        let _ = self.listening.close();
+    }
+}
+
+// ===========================================================================
+// Server internals
+// ===========================================================================
+struct Shared {
+    state: Mutex<State>,
+    new_update: Condvar,
+}
+
+struct State {
+    bot: Value,
+    next_update_id: Integer,
+    next_message_id: Integer,
+    updates: Vec<(Integer, Value)>,
+    chats: HashMap<ChatId, Value>,
+    blocked: HashSet<ChatId>,
+    messages: Vec<Stored>,
+    requests: Vec<RecordedRequest>,
+    webhook: Option<Webhook>,
+    // Options chosen by every user, by poll id
+    poll_votes: HashMap<(String, UserId), Vec<Integer>>,
+    // Payloads of sent invoices, by chat and message id
+    invoice_payloads: HashMap<(Integer, Integer), String>,
+    // Invoices with a price depending on the shipping method
+    flexible_invoices: HashSet<(Integer, Integer)>,
+    // Unanswered shipping queries
+    shipping_queries: HashSet<String>,
+    // Unanswered pre-checkout queries with the message which is sent if the
+    // payment succeeds
+    checkouts: HashMap<String, Value>,
+    // Whether a payment was refunded, by charge id
+    charges: HashMap<String, bool>,
+    // Ids of uploaded files
+    files: HashSet<String>,
+    // Sticker sets with their owner, by name
+    sticker_sets: HashMap<String, (Integer, Value)>,
+}
+
+struct Webhook {
+    url: String,
+    has_custom_certificate: bool,
+    ip_address: Option<String>,
+    max_connections: Integer,
+    allowed_updates: Option<Value>,
+}
+
+struct Stored {
+    outgoing: bool,
+    message: Value,
+}
+
+impl State {
+    /// Queues an incoming message with the given fields (at least "from"
+    /// and "chat").
+    fn receive(&mut self, fields: Value) -> UpdateId {
+        let mut message = object(vec![
+            ("message_id", Value::from(self.next_message_id())),
+            ("date", Value::from(now())),
+        ]);
+        if let (&mut Value::Object(ref mut m), Value::Object(f)) = (&mut message, fields) {
+            m.extend(f);
+        }
+        self.messages.push(Stored { outgoing: false, message: message.clone() });
+        self.push_update(object(vec![("message", message)]))
+    }
+
+    fn new_file_id(&mut self) -> String {
+        let id = format!("fake-file-{}", self.next_message_id());
+        self.files.insert(id.clone());
+        id
+    }
+
+    /// Whether a file in an "Input..." object is a known file id, an URL or
+    /// an uploaded file.
+    fn valid_file(&self, file: &str, uploads: &HashMap<String, usize>) -> bool {
+        if file.starts_with("attach://") {
+            uploads.contains_key(&file["attach://".len()..])
+        } else {
+            self.files.contains(file) || file.starts_with("https://")
+        }
+    }
+
+    /// Creates a sticker of the given set from an "InputSticker" object.
+    fn new_sticker(&mut self, input: &Value, set_name: &str, kind: &str,
+                   uploads: &HashMap<String, usize>)
+                   -> ::std::result::Result<Value, (u16, String)> {
+        let file = input["sticker"].as_str().unwrap_or("");
+        if !self.valid_file(file, uploads) {
+            return Err(bad_request("Bad Request: wrong file identifier/HTTP URL specified"));
+        }
+        let format = input["format"].as_str().unwrap_or("");
+        let emoji = try!(input["emoji_list"].get(0).cloned()
+            .ok_or_else(|| bad_request("Bad Request: STICKER_EMOJI_INVALID")));
+
+        let mut sticker = vec![
+            ("file_id", Value::from(self.new_file_id())),
+            ("width", Value::from(512)),
+            ("height", Value::from(512)),
+            ("type", Value::from(kind)),
+            ("is_animated", Value::from(format == "animated")),
+            ("is_video", Value::from(format == "video")),
+            ("emoji", emoji),
+            ("set_name", Value::from(set_name)),
+        ];
+        if let Some(position) = input.get("mask_position") {
+            sticker.push(("mask_position", position.clone()));
+        }
+        Ok(object(sticker))
+    }
+
+    /// The stickers of the set containing the given sticker.
+    fn sticker_set_of(&mut self, sticker: &str)
+                      -> ::std::result::Result<&mut Vec<Value>, (u16, String)> {
+        self.sticker_sets.values_mut()
+            .filter_map(|&mut (_, ref mut set)| set["stickers"].as_array_mut())
+            .find(|stickers| stickers.iter().any(|s| s["file_id"] == Value::from(sticker)))
+            .ok_or_else(|| bad_request("Bad Request: STICKER_INVALID"))
+    }
+
+    fn next_message_id(&mut self) -> Integer {
+        let id = self.next_message_id;
+        self.next_message_id += 1;
+        id
+    }
+
+    fn push_update(&mut self, mut update: Value) -> UpdateId {
+        let id = self.next_update_id;
+        self.next_update_id += 1;
+
+        if let Value::Object(ref mut o) = update {
+            o.insert("update_id".into(), Value::from(id));
+        }
+        self.updates.push((id, update));
+        UpdateId(id)
+    }
+
+    fn find_chat(&self, chat_id: &str) -> Option<Value> {
+        if let Ok(id) = chat_id.parse::<Integer>() {
+            return self.chats.get(&ChatId(id)).cloned();
+        }
+
+        // Channels and supergroups can be addressed by "@username"
+        self.chats.values()
+            .find(|c| {
+                c.get("username").and_then(Value::as_str)
+                    .map_or(false, |name| format!("@{}", name) == chat_id)
+            })
+            .cloned()
+    }
+
+    fn message_index(&self, chat_id: Integer, message_id: Integer) -> Option<usize> {
+        self.messages.iter()
+            .map(|m| &m.message)
+            .position(|m| {
+                m.get("message_id").and_then(Value::as_i64) == Some(message_id)
+                    && m.pointer("/chat/id").and_then(Value::as_i64)
+                        == Some(chat_id)
+            })
+    }
+
+    fn find_message(&mut self, chat_id: Integer, message_id: Integer)
+                    -> Option<&mut Value> {
+        match self.message_index(chat_id, message_id) {
+            Some(i) => Some(&mut self.messages[i].message),
+            None => None,
+        }
+    }
+
+    /// Stores a message sent by the bot and returns it.
+    fn send(&mut self, chat: Value, content: Vec<(String, Value)>) -> Value {
+        let message_id = self.next_message_id();
+        let mut message = object(vec![
+            ("message_id", Value::from(message_id)),
+            ("from", self.bot.clone()),
+            ("chat", chat),
+            ("date", Value::from(now())),
+        ]);
+        if let Value::Object(ref mut o) = message {
+            o.extend(content);
+        }
+
+        self.messages.push(Stored { outgoing: true, message: message.clone() });
+        message
+    }
+}
+
+/// Result of a single API method: Either the "result" field or an error
+/// code with a description.
+type Reply = ::std::result::Result<Value, (u16, String)>;
+
+struct Params(HashMap<String, String>);
+
+impl Params {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).map(|s| &**s)
+    }
+
+    fn required(&self, key: &str) -> ::std::result::Result<&str, (u16, String)> {
+        self.get(key).filter(|v| !v.is_empty()).ok_or_else(|| {
+            bad_request(&format!("Bad Request: {} is empty", key))
+        })
+    }
+
+    fn integer(&self, key: &str) -> ::std::result::Result<Integer, (u16, String)> {
+        try!(self.required(key)).parse().map_err(|_| {
+            bad_request(&format!("Bad Request: invalid {}", key))
+        })
+    }
+
+    fn optional_integer(&self, key: &str) -> Option<Integer> {
+        self.get(key).and_then(|v| v.parse().ok())
+    }
+}
+
+struct FakeHandler(Arc<Shared>);
+
+impl Handler for FakeHandler {
+    fn handle(&self, mut req: Request, mut res: Response) {
+        let path = match req.uri {
+            RequestUri::AbsolutePath(ref p) => p.clone(),
+            _ => String::new(),
+        };
+        let content_type = req.headers.get_raw("Content-Type")
+            .and_then(|values| values.first())
+            .map(|value| String::from_utf8_lossy(value).into_owned())
+            .unwrap_or_default();
+        let mut body = Vec::new();
+        let _ = req.read_to_end(&mut body);
+
+        // Files can only be uploaded with multipart requests
+        let boundary = content_type.find("boundary=").map(|i| &content_type[i + 9..]);
+        let (pairs, files) = match boundary {
+            Some(boundary) if content_type.starts_with("multipart/form-data") => {
+                parse_multipart(&body, boundary.trim_matches('"'))
+            }
+            _ => (form_urlencoded::parse(&body).into_owned().collect(), Vec::new()),
+        };
+
+        let reply = self.dispatch(&path, pairs, files);
+        let (status, response) = match reply {
+            Ok(result) => (200, object(vec![
+                ("ok", Value::from(true)),
+                ("result", result),
+            ])),
+            Err((code, description)) => (code, object(vec![
+                ("ok", Value::from(false)),
+                ("error_code", Value::from(code as Integer)),
+                ("description", Value::from(description)),
+            ])),
+        };
+
+        *res.status_mut() = StatusCode::from_u16(status);
+        let _ = res.send(response.to_string().as_bytes());
+    }
+}
+
+impl FakeHandler {
+    fn dispatch(&self, path: &str, pairs: Vec<(String, String)>, files: Vec<RecordedFile>)
+                -> Reply {
+        // Path has the form "/bot<token>/<method>"
+        let mut parts = path.trim_left_matches('/').splitn(2, '/');
+        let token = parts.next().unwrap_or("");
+        let method = parts.next().unwrap_or("");
+        if token != format!("bot{}", TOKEN) {
+            return Err((401, "Unauthorized".into()));
+        }
+
+        let params = Params(pairs.iter().cloned().collect());
+        let has_certificate = files.iter().any(|f| f.field == "certificate");
+        // Sizes of the uploaded files, by parameter name
+        let uploads: HashMap<String, usize> = files.iter()
+            .map(|f| (f.field.clone(), f.data.len()))
+            .collect();
+
+        let mut state = self.0.state.lock().unwrap();
+        state.requests.push(RecordedRequest {
+            method: method.into(),
+            params: pairs,
+            files: files,
+        });
+
+        match method {
+            "getMe" => Ok(state.bot.clone()),
+            "getUpdates" => self.get_updates(state, &params),
+            "sendMessage" => {
+                let chat = try!(chat(&state, &params));
+                let text = Value::from(try!(params.required("text")));
+                let mut content = vec![("text".into(), text)];
+                if let Some(id) = params.optional_integer("reply_to_message_id") {
+                    let chat_id = chat.get("id").and_then(Value::as_i64).unwrap();
+                    if let Some(reply) = state.find_message(chat_id, id) {
+                        content.push(("reply_to_message".into(), reply.clone()));
+                    }
+                }
+                Ok(state.send(chat, content))
+            }
+            "forwardMessage" => {
+                let chat = try!(chat(&state, &params));
+                let from_chat_id = try!(chat_id(&state, &params, "from_chat_id"));
+                let message_id = try!(params.integer("message_id"));
+                let original = match state.find_message(from_chat_id, message_id) {
+                    Some(m) => m.clone(),
+                    None => return Err(bad_request(
+                        "Bad Request: message to forward not found")),
+                };
+
+                // Only messages from users can be forwarded so far
+                let (from, date) = match (original.get("from"), original.get("date")) {
+                    (Some(from), Some(date)) => (from.clone(), date.clone()),
+                    _ => return Err(bad_request("Bad Request: message can't be forwarded")),
+                };
+                let mut content = vec![
+                    ("forward_from".into(), from),
+                    ("forward_date".into(), date),
+                ];
+                if let Value::Object(o) = original {
+                    content.extend(o.into_iter().filter(|&(ref k, _)| is_content_field(k)));
+                }
+                Ok(state.send(chat, content))
+            }
+            "editMessageText" => {
+                let text = Value::from(try!(params.required("text")));
+                if params.get("inline_message_id").is_some() {
+                    return Ok(Value::from(true));
+                }
+
+                let chat_id = try!(chat_id(&state, &params, "chat_id"));
+                let message_id = try!(params.integer("message_id"));
+                let date = now();
+                match state.find_message(chat_id, message_id) {
+                    Some(&mut Value::Object(ref mut o)) => {
+                        o.insert("text".into(), text);
+                        o.insert("edit_date".into(), Value::from(date));
+                        Ok(Value::Object(o.clone()))
+                    }
+                    _ => Err(bad_request("Bad Request: message to edit not found")),
+                }
+            }
+            "deleteMessage" => {
+                let chat_id = try!(chat_id(&state, &params, "chat_id"));
+                let message_id = try!(params.integer("message_id"));
+                match state.message_index(chat_id, message_id) {
+                    Some(i) => {
+                        state.messages.remove(i);
+                        Ok(Value::from(true))
+                    }
+                    None => Err(bad_request("Bad Request: message to delete not found")),
+                }
+            }
+            "sendLocation" => {
+                let chat = try!(chat(&state, &params));
+                let location = object(vec![
+                    ("latitude", try!(float(&params, "latitude"))),
+                    ("longitude", try!(float(&params, "longitude"))),
+                ]);
+                Ok(state.send(chat, vec![("location".into(), location)]))
+            }
+            "sendPoll" => {
+                let chat = try!(chat(&state, &params));
+                let question = Value::from(try!(params.required("question")));
+                let options: Vec<Value> = try!(params.required("options")
+                    .ok()
+                    .and_then(|o| serde_json::from_str(o).ok())
+                    .ok_or_else(|| bad_request("Bad Request: can't parse options JSON object")));
+                if options.len() < 2 || options.len() > 10 {
+                    return Err(bad_request("Bad Request: poll must have 2-10 options"));
+                }
+                // Options are either strings or "InputPollOption" objects
+                let options = options.into_iter()
+                    .map(|o| object(vec![
+                        ("text", o.get("text").cloned().unwrap_or(o)),
+                        ("voter_count", Value::from(0)),
+                    ]))
+                    .collect();
+
+                let kind = params.get("type").unwrap_or("regular");
+                let correct_option_id = params.optional_integer("correct_option_id");
+                if kind == "quiz" && correct_option_id.is_none() {
+                    return Err(bad_request("Bad Request: wrong correct option ID specified"));
+                }
+
+                // Poll ids are unique, just like message ids
+                let id = state.next_message_id.to_string();
+                let mut poll = vec![
+                    ("id", Value::from(id)),
+                    ("question", question),
+                    ("options", Value::Array(options)),
+                    ("total_voter_count", Value::from(0)),
+                    ("is_closed", Value::from(params.get("is_closed") == Some("true"))),
+                    ("is_anonymous", Value::from(params.get("is_anonymous") != Some("false"))),
+                    ("type", Value::from(kind)),
+                    ("allows_multiple_answers",
+                     Value::from(params.get("allows_multiple_answers") == Some("true"))),
+                ];
+                if let Some(id) = correct_option_id {
+                    poll.push(("correct_option_id", Value::from(id)));
+                }
+                if let Some(explanation) = params.get("explanation") {
+                    poll.push(("explanation", Value::from(explanation)));
+                }
+                if let Some(entities) = params.get("explanation_entities") {
+                    poll.push(("explanation_entities", try!(serde_json::from_str(entities)
+                        .map_err(|_| bad_request("Bad Request: can't parse entities")))));
+                }
+                for key in &["open_period", "close_date"] {
+                    if let Some(value) = params.optional_integer(key) {
+                        poll.push((key, Value::from(value)));
+                    }
+                }
+                Ok(state.send(chat, vec![("poll".into(), object(poll))]))
+            }
+            "stopPoll" => {
+                let chat_id = try!(chat_id(&state, &params, "chat_id"));
+                let message_id = try!(params.integer("message_id"));
+                let poll = match state.find_message(chat_id, message_id) {
+                    Some(m) => m.get_mut("poll"),
+                    None => None,
+                };
+                match poll {
+                    Some(ref poll) if poll["is_closed"] == Value::from(true) => {
+                        Err(bad_request("Bad Request: poll has already been closed"))
+                    }
+                    Some(poll) => {
+                        poll["is_closed"] = Value::from(true);
+                        Ok(poll.clone())
+                    }
+                    None => Err(bad_request("Bad Request: message with poll to stop not found")),
+                }
+            }
+            "sendInvoice" => {
+                let chat = try!(chat(&state, &params));
+                let (invoice, payload) = try!(invoice(&params));
+                let message = state.send(chat, vec![("invoice".into(), invoice)]);
+                let chat_id = message.pointer("/chat/id").and_then(Value::as_i64).unwrap();
+                let message_id = message["message_id"].as_i64().unwrap();
+                state.invoice_payloads.insert((chat_id, message_id), payload);
+                if params.get("is_flexible") == Some("true") {
+                    state.flexible_invoices.insert((chat_id, message_id));
+                }
+                Ok(message)
+            }
+            "createInvoiceLink" => {
+                try!(invoice(&params));
+                let id = state.next_message_id();
+                Ok(Value::from(format!("https://t.me/$fake-invoice-{}", id)))
+            }
+            "answerShippingQuery" => {
+                let id = try!(params.required("shipping_query_id"));
+                if params.get("ok") == Some("true") {
+                    let options: Vec<Value> = try!(params.required("shipping_options")
+                        .ok()
+                        .and_then(|o| serde_json::from_str(o).ok())
+                        .ok_or_else(|| bad_request(
+                            "Bad Request: can't parse shipping options JSON object")));
+                    if options.is_empty() {
+                        return Err(bad_request("Bad Request: shipping_options is empty"));
+                    }
+                } else {
+                    try!(params.required("error_message"));
+                }
+                if !state.shipping_queries.remove(id) {
+                    return Err(bad_request("Bad Request: query is too old and response \
+                                            timeout expired or query ID is invalid"));
+                }
+                Ok(Value::from(true))
+            }
+            "answerPreCheckoutQuery" => {
+                let id = try!(params.required("pre_checkout_query_id"));
+                let ok = params.get("ok") == Some("true");
+                if !ok {
+                    try!(params.required("error_message"));
+                }
+                let message = try!(state.checkouts.remove(id).ok_or_else(|| bad_request(
+                    "Bad Request: query is too old and response timeout expired or \
+                     query ID is invalid")));
+
+                if ok {
+                    let charge = message.pointer("/successful_payment/telegram_payment_charge_id")
+                        .and_then(Value::as_str).unwrap().to_string();
+                    state.charges.insert(charge, false);
+                    state.receive(message);
+                    self.0.new_update.notify_all();
+                }
+                Ok(Value::from(true))
+            }
+            "refundStarPayment" => {
+                let user_id = try!(params.integer("user_id"));
+                let charge_id = try!(params.required("telegram_payment_charge_id"));
+                let payment = state.messages.iter()
+                    .map(|m| &m.message)
+                    .filter(|m| m.pointer("/from/id").and_then(Value::as_i64) == Some(user_id))
+                    .filter_map(|m| m.get("successful_payment").map(|p| (m, p)))
+                    .find(|&(_, p)| p["telegram_payment_charge_id"] == Value::from(charge_id))
+                    .map(|(m, p)| (m["from"].clone(), m["chat"].clone(), p.clone()));
+                let (from, chat, payment) = try!(payment.ok_or_else(|| {
+                    bad_request("Bad Request: CHARGE_NOT_FOUND")
+                }));
+                if state.charges.insert(charge_id.into(), true) == Some(true) {
+                    return Err(bad_request("Bad Request: CHARGE_ALREADY_REFUNDED"));
+                }
+
+                let refund = object(vec![
+                    ("currency", payment["currency"].clone()),
+                    ("total_amount", payment["total_amount"].clone()),
+                    ("invoice_payload", payment["invoice_payload"].clone()),
+                    ("telegram_payment_charge_id", Value::from(charge_id)),
+                ]);
+                state.receive(object(vec![
+                    ("from", from),
+                    ("chat", chat),
+                    ("refunded_payment", refund),
+                ]));
+                self.0.new_update.notify_all();
+                Ok(Value::from(true))
+            }
+            "uploadStickerFile" => {
+                try!(params.integer("user_id"));
+                try!(params.required("sticker_format"));
+                let size = try!(uploads.get("sticker").ok_or_else(|| {
+                    bad_request("Bad Request: there is no sticker file in the request")
+                }));
+                let file_id = state.new_file_id();
+                Ok(object(vec![
+                    ("file_id", Value::from(file_id)),
+                    ("file_size", Value::from(*size as Integer)),
+                ]))
+            }
+            "createNewStickerSet" => {
+                let user_id = try!(params.integer("user_id"));
+                let name = try!(params.required("name"));
+                let title = try!(params.required("title"));
+                let kind = params.get("sticker_type").unwrap_or("regular");
+
+                let suffix = format!("_by_{}", state.bot["username"].as_str().unwrap());
+                if !name.ends_with(&suffix) {
+                    return Err(bad_request("Bad Request: invalid sticker set name is specified"));
+                }
+                if state.sticker_sets.contains_key(name) {
+                    return Err(bad_request("Bad Request: sticker set name is already occupied"));
+                }
+                let inputs: Vec<Value> = try!(params.get("stickers")
+                    .and_then(|s| serde_json::from_str(s).ok())
+                    .ok_or_else(|| bad_request("Bad Request: can't parse stickers JSON object")));
+                if inputs.is_empty() || inputs.len() > 50 {
+                    return Err(bad_request("Bad Request: STICKERS_INVALID"));
+                }
+
+                let mut stickers = Vec::new();
+                for input in &inputs {
+                    stickers.push(try!(state.new_sticker(input, name, kind, &uploads)));
+                }
+                let set = object(vec![
+                    ("name", Value::from(name)),
+                    ("title", Value::from(title)),
+                    ("sticker_type", Value::from(kind)),
+                    ("stickers", Value::Array(stickers)),
+                ]);
+                state.sticker_sets.insert(name.into(), (user_id, set));
+                Ok(Value::from(true))
+            }
+            "getStickerSet" => {
+                let name = try!(params.required("name"));
+                state.sticker_sets.get(name)
+                    .map(|&(_, ref set)| set.clone())
+                    .ok_or_else(|| bad_request("Bad Request: STICKERSET_INVALID"))
+            }
+            "addStickerToSet" => {
+                let user_id = try!(params.integer("user_id"));
+                let name = try!(params.required("name"));
+                let input: Value = try!(params.get("sticker")
+                    .and_then(|s| serde_json::from_str(s).ok())
+                    .ok_or_else(|| bad_request("Bad Request: can't parse sticker JSON object")));
+                let kind = match state.sticker_sets.get(name) {
+                    Some(&(owner, ref set)) if owner == user_id => {
+                        if set["stickers"].as_array().unwrap().len() >= 120 {
+                            return Err(bad_request("Bad Request: STICKERS_TOO_MUCH"));
+                        }
+                        set["sticker_type"].as_str().unwrap().to_string()
+                    }
+                    _ => return Err(bad_request("Bad Request: STICKERSET_INVALID")),
+                };
+
+                let sticker = try!(state.new_sticker(&input, name, &kind, &uploads));
+                let set = &mut state.sticker_sets.get_mut(name).unwrap().1;
+                set["stickers"].as_array_mut().unwrap().push(sticker);
+                Ok(Value::from(true))
+            }
+            "setStickerPositionInSet" => {
+                let sticker = try!(params.required("sticker"));
+                let position = try!(params.integer("position")) as usize;
+                let stickers = try!(state.sticker_set_of(sticker));
+                let index = stickers.iter().position(|s| s["file_id"] == Value::from(sticker));
+                if position >= stickers.len() {
+                    return Err(bad_request("Bad Request: STICKER_POSITION_INVALID"));
+                }
+                let moved = stickers.remove(index.unwrap());
+                stickers.insert(position, moved);
+                Ok(Value::from(true))
+            }
+            "deleteStickerFromSet" => {
+                let sticker = try!(params.required("sticker"));
+                let stickers = try!(state.sticker_set_of(sticker));
+                stickers.retain(|s| s["file_id"] != Value::from(sticker));
+                Ok(Value::from(true))
+            }
+            "setStickerSetThumbnail" => {
+                let name = try!(params.required("name"));
+                let user_id = try!(params.integer("user_id"));
+                try!(params.required("format"));
+                let thumbnail = match params.get("thumbnail") {
+                    Some(id) => Some(id.to_string()),
+                    None if uploads.contains_key("thumbnail") => Some(state.new_file_id()),
+                    None => None,
+                };
+
+                match state.sticker_sets.get_mut(name) {
+                    Some(&mut (owner, Value::Object(ref mut set))) if owner == user_id => {
+                        match thumbnail {
+                            Some(id) => set.insert("thumbnail".into(), object(vec![
+                                ("file_id", Value::from(id)),
+                                ("width", Value::from(100)),
+                                ("height", Value::from(100)),
+                            ])),
+                            None => set.remove("thumbnail"),
+                        };
+                        Ok(Value::from(true))
+                    }
+                    _ => Err(bad_request("Bad Request: STICKERSET_INVALID")),
+                }
+            }
+            "sendMediaGroup" => {
+                let chat = try!(chat(&state, &params));
+                let media: Vec<Value> = try!(params.required("media")
+                    .ok()
+                    .and_then(|m| serde_json::from_str(m).ok())
+                    .ok_or_else(|| bad_request("Bad Request: can't parse media JSON object")));
+                if media.len() < 2 || media.len() > 10 {
+                    return Err(bad_request(
+                        "Bad Request: media group must include 2-10 items"));
+                }
+
+                let kinds: Vec<&str> = media.iter()
+                    .map(|m| m["type"].as_str().unwrap_or(""))
+                    .collect();
+                for &kind in &kinds {
+                    let valid = match kind {
+                        "photo" | "video" => kinds.iter().all(|&k| k == "photo" || k == "video"),
+                        "document" | "audio" => kinds.iter().all(|&k| k == kind),
+                        _ => false,
+                    };
+                    if !valid {
+                        return Err(bad_request(
+                            "Bad Request: wrong type of the media group item"));
+                    }
+                }
+                for item in &media {
+                    if !state.valid_file(item["media"].as_str().unwrap_or(""), &uploads) {
+                        return Err(bad_request(
+                            "Bad Request: wrong file identifier/HTTP URL specified"));
+                    }
+                }
+
+                let group_id = state.next_message_id().to_string();
+                let mut messages = Vec::new();
+                for item in media {
+                    let kind = item["type"].as_str().unwrap().to_string();
+                    let mut file = vec![("file_id", Value::from(state.new_file_id()))];
+                    if kind == "photo" || kind == "video" {
+                        file.push(("width", item.get("width").cloned()
+                            .unwrap_or(Value::from(800))));
+                        file.push(("height", item.get("height").cloned()
+                            .unwrap_or(Value::from(600))));
+                    }
+                    if kind == "video" || kind == "audio" {
+                        file.push(("duration", item.get("duration").cloned()
+                            .unwrap_or(Value::from(0))));
+                    }
+                    for key in &["performer", "title"] {
+                        if let Some(value) = item.get(*key) {
+                            file.push((key, value.clone()));
+                        }
+                    }
+                    // Photos are sent in several sizes
+                    let file = match &*kind {
+                        "photo" => Value::Array(vec![object(file)]),
+                        _ => object(file),
+                    };
+
+                    let mut content = vec![
+                        ("media_group_id".into(), Value::from(&*group_id)),
+                        (kind, file),
+                    ];
+                    if let Some(caption) = item.get("caption") {
+                        content.push(("caption".into(), caption.clone()));
+                    }
+                    messages.push(state.send(chat.clone(), content));
+                }
+                Ok(Value::Array(messages))
+            }
+            "sendChatAction" => {
+                try!(chat(&state, &params));
+                try!(params.required("action"));
+                Ok(Value::from(true))
+            }
+            "getUserProfilePhotos" => {
+                try!(params.integer("user_id"));
+                Ok(object(vec![
+                    ("total_count", Value::from(0)),
+                    ("photos", Value::Array(Vec::new())),
+                ]))
+            }
+            "setWebhook" => {
+                let url = params.get("url").unwrap_or("");
+                if url.is_empty() {
+                    return Ok(delete_webhook(&mut state, &params));
+                }
+                if !url.starts_with("https://") {
+                    return Err(bad_request(
+                        "Bad Request: bad webhook: HTTPS url must be provided for webhook"));
+                }
+
+                if params.get("drop_pending_updates") == Some("true") {
+                    state.updates.clear();
+                }
+                let allowed_updates = params.get("allowed_updates")
+                    .and_then(|a| serde_json::from_str(a).ok());
+                state.webhook = Some(Webhook {
+                    url: url.into(),
+                    has_custom_certificate: has_certificate,
+                    ip_address: params.get("ip_address").map(Into::into),
+                    max_connections: params.optional_integer("max_connections").unwrap_or(40),
+                    allowed_updates: allowed_updates,
+                });
+                Ok(Value::from(true))
+            }
+            "deleteWebhook" => Ok(delete_webhook(&mut state, &params)),
+            "getWebhookInfo" => {
+                let pending = Value::from(state.updates.len() as Integer);
+                Ok(match state.webhook {
+                    None => object(vec![
+                        ("url", Value::from("")),
+                        ("has_custom_certificate", Value::from(false)),
+                        ("pending_update_count", pending),
+                    ]),
+                    Some(ref webhook) => {
+                        let mut info = vec![
+                            ("url", Value::from(webhook.url.as_str())),
+                            ("has_custom_certificate", Value::from(webhook.has_custom_certificate)),
+                            ("pending_update_count", pending),
+                            ("max_connections", Value::from(webhook.max_connections)),
+                        ];
+                        if let Some(ref ip) = webhook.ip_address {
+                            info.push(("ip_address", Value::from(ip.as_str())));
+                        }
+                        if let Some(ref allowed) = webhook.allowed_updates {
+                            info.push(("allowed_updates", allowed.clone()));
+                        }
+                        object(info)
+                    }
+                })
+            }
+            _ => Err((404, "Not Found".into())),
+        }
+    }
+
+    fn get_updates(&self, mut state: MutexGuard<State>, params: &Params) -> Reply {
+        if state.webhook.is_some() {
+            return Err((409, "Conflict: can't use getUpdates method while webhook is active; \
+                             use deleteWebhook to delete the webhook first".into()));
+        }
+
+        // Confirm all updates before the offset
+        if let Some(offset) = params.optional_integer("offset") {
+            state.updates.retain(|&(id, _)| id >= offset);
+        }
+
+        // Emulate long polling, but not longer than the client would wait
+        let timeout = params.optional_integer("timeout").unwrap_or(0)
+            .min(MAX_POLL_TIMEOUT);
+        let deadline = Instant::now() + Duration::from_secs(timeout as u64);
+        while state.updates.is_empty() {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            state = self.0.new_update.wait_timeout(state, deadline - now).unwrap().0;
+        }
+
+        let limit = match params.optional_integer("limit") {
+            Some(l) if l >= 1 && l <= 100 => l as usize,
+            _ => 100,
+        };
+        Ok(Value::Array(state.updates.iter()
+            .take(limit)
+            .map(|&(_, ref u)| u.clone())
+            .collect()))
+    }
+}
+
+// ===========================================================================
+// Helpers
+// ===========================================================================
+fn delete_webhook(state: &mut State, params: &Params) -> Value {
+    state.webhook = None;
+    if params.get("drop_pending_updates") == Some("true") {
+        state.updates.clear();
+    }
+    Value::from(true)
+}
+
+// Splits a "multipart/form-data" body into parameters and files.
+fn parse_multipart(body: &[u8], boundary: &str) -> (Vec<(String, String)>, Vec<RecordedFile>) {
+    let delimiter = format!("--{}", boundary).into_bytes();
+    let mut pairs = Vec::new();
+    let mut files = Vec::new();
+
+    let mut rest = match find(body, &delimiter) {
+        Some(start) => &body[start + delimiter.len()..],
+        None => return (pairs, files),
+    };
+    // Every part ends with "\r\n" followed by the next delimiter
+    while let Some(end) = find(rest, &delimiter) {
+        let part = &rest[..end];
+        rest = &rest[end + delimiter.len()..];
+
+        let part = part.strip_prefix(b"\r\n").unwrap_or(part);
+        let part = part.strip_suffix(b"\r\n").unwrap_or(part);
+        let split = match find(part, b"\r\n\r\n") {
+            Some(split) => split,
+            None => continue,
+        };
+        let headers = String::from_utf8_lossy(&part[..split]);
+        let content = &part[split + 4..];
+
+        let name = match header_param(&headers, "name") {
+            Some(name) => name,
+            None => continue,
+        };
+        match header_param(&headers, "filename") {
+            Some(file_name) => files.push(RecordedFile {
+                field: name,
+                file_name: file_name,
+                data: content.to_vec(),
+            }),
+            None => pairs.push((name, String::from_utf8_lossy(content).into_owned())),
+        }
+    }
+
+    (pairs, files)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+// Returns a quoted parameter like `name="value"` of a header.
+fn header_param(headers: &str, key: &str) -> Option<String> {
+    let start = format!(" {}=\"", key);
+    headers.find(&start).and_then(|i| {
+        let value = &headers[i + start.len()..];
+        value.find('"').map(|end| value[..end].to_string())
+    })
+}
+
+fn now() -> Integer {
+    SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as Integer)
+        .unwrap_or(0)
+}
+
+fn bad_request(description: &str) -> (u16, String) {
+    (400, description.into())
+}
+
+fn chat(state: &State, params: &Params) -> ::std::result::Result<Value, (u16, String)> {
+    let id = try!(params.required("chat_id"));
+    let chat = try!(state.find_chat(id)
+        .ok_or_else(|| bad_request("Bad Request: chat not found")));
+
+    let chat_id = ChatId(chat.get("id").and_then(Value::as_i64).unwrap());
+    if state.blocked.contains(&chat_id) {
+        return Err((403, "Forbidden: bot was blocked by the user".into()));
+    }
+    Ok(chat)
+}
+
+// The id of the chat in the given parameter, which may also be a username.
+fn chat_id(state: &State, params: &Params, key: &str)
+           -> ::std::result::Result<Integer, (u16, String)> {
+    let chat = try!(state.find_chat(try!(params.required(key)))
+        .ok_or_else(|| bad_request("Bad Request: chat not found")));
+    Ok(chat.get("id").and_then(Value::as_i64).unwrap())
+}
+
+// Checks the parameters of an invoice and returns the "Invoice" object and the
+// payload.
+fn invoice(params: &Params) -> ::std::result::Result<(Value, String), (u16, String)> {
+    let title = try!(params.required("title"));
+    let description = try!(params.required("description"));
+    let payload = try!(params.required("payload"));
+    let currency = try!(params.required("currency"));
+    let prices: Vec<LabeledPrice> = try!(params.get("prices")
+        .and_then(|p| serde_json::from_str(p).ok())
+        .ok_or_else(|| bad_request("Bad Request: can't parse prices JSON object")));
+
+    if currency == STARS {
+        if prices.len() != 1 {
+            return Err(bad_request("Bad Request: STARS_INVOICE_INVALID"));
+        }
+    } else if params.get("provider_token").map_or(true, str::is_empty) {
+        return Err(bad_request("Bad Request: PAYMENT_PROVIDER_INVALID"));
+    }
+
+    let invoice = object(vec![
+        ("title", Value::from(title)),
+        ("description", Value::from(description)),
+        ("start_parameter", Value::from(params.get("start_parameter").unwrap_or(""))),
+        ("currency", Value::from(currency)),
+        ("total_amount", Value::from(prices.iter().map(|p| p.amount).sum::<Integer>())),
+    ]);
+    Ok((invoice, payload.into()))
+}
+
+fn float(params: &Params, key: &str) -> ::std::result::Result<Value, (u16, String)> {
+    try!(params.required(key)).parse::<f64>()
+        .map(Value::from)
+        .map_err(|_| bad_request(&format!("Bad Request: invalid {}", key)))
+}
+
+// Fields of a message which are copied when it's forwarded
+fn is_content_field(key: &str) -> bool {
+    match key {
+        "message_id" | "from" | "chat" | "date" | "edit_date"
+        | "forward_from" | "forward_date" | "reply_to_message" => false,
+        _ => true,
+    }
+}
+
+fn object(fields: Vec<(&str, Value)>) -> Value {
+    Value::Object(fields.into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect::<Map<_, _>>())
+}
+
+fn to_json<T: Serialize>(value: &T) -> Value {
+    serde_json::to_value(value).unwrap()
+}
+
+fn decode<T: DeserializeOwned>(value: &Value) -> T {
+    serde_json::from_value(value.clone()).expect("the fake server stores valid objects")
+}
+
+// ===========================================================================
+// Unit tests
+// ===========================================================================
+#[cfg(test)]
+mod test;
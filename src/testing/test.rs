@@ -0,0 +1,1191 @@
+use super::*;
+use {Api, Chat, ChatId, Error, ListenerEvent, ListeningAction, ListeningMethod,
+     MessageId, MessageType, UpdateId, User, UserId};
+
+fn alice() -> (User, Chat) {
+    let user = User {
+        id: UserId(42),
+        first_name: "Alice".into(),
+        last_name: None,
+        username: Some("alice".into()),
+    };
+    let chat = Chat::Private {
+        id: ChatId(42),
+        first_name: "Alice".into(),
+        last_name: None,
+        username: Some("alice".into()),
+    };
+    (user, chat)
+}
+
+#[test]
+fn get_me() {
+    let server = FakeServer::start().unwrap();
+    assert_eq!(server.api().get_me().unwrap(), server.bot());
+}
+
+#[test]
+fn wrong_token() {
+    let server = FakeServer::start().unwrap();
+    let api = Api::from_token_and_url("invalid", server.url()).unwrap();
+    match api.get_me() {
+        Err(Error::Api(ref desc)) if desc == "Unauthorized" => {},
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn listen_and_reply() {
+    let server = FakeServer::start().unwrap();
+    let api = server.api();
+    let (user, chat) = alice();
+    server.inject_text(&user, &chat, "Hello & welcome");
+    server.inject_text(&user, &chat, "/exit");
+
+    let mut listener = api.listener(ListeningMethod::LongPoll(None));
+    listener.listen(|u| {
+        let m = u.message.unwrap();
+        if let MessageType::Text(t) = m.msg {
+            if t == "/exit" {
+                return Ok(ListeningAction::Stop);
+            }
+            try!(api.send_message(m.chat.id(), t, None, None,
+                                  Some(m.message_id), None));
+        }
+        Ok(ListeningAction::Continue)
+    }).unwrap();
+
+    // Both updates are confirmed and exactly one answer was sent
+    assert_eq!(server.pending_updates(), 0);
+    let sent = server.messages_in(ChatId(42));
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].msg, MessageType::Text("Hello & welcome".into()));
+    assert_eq!(sent[0].from, server.bot());
+    assert!(sent[0].reply.is_some());
+}
+
+#[test]
+fn unknown_chat() {
+    let server = FakeServer::start().unwrap();
+    match server.api().send_message(7, "Hi".into(), None, None, None, None) {
+        Err(Error::Api(ref desc)) if desc == "Bad Request: chat not found" => {},
+        other => panic!("unexpected result: {:?}", other),
+    }
+    assert!(server.sent_messages().is_empty());
+}
+
+#[test]
+fn forward_and_record_requests() {
+    let server = FakeServer::start().unwrap();
+    let api = server.api();
+    let (user, chat) = alice();
+    server.inject_text(&user, &chat, "Forward me");
+
+    let update = api.get_updates(None, None, None).unwrap().remove(0);
+    let m = update.message.unwrap();
+    let forwarded = api.forward_message(42, 42, m.message_id).unwrap();
+    assert_eq!(forwarded.forward.map(|(u, _)| u), Some(user));
+    assert_eq!(forwarded.msg, MessageType::Text("Forward me".into()));
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[1].method, "forwardMessage");
+    assert_eq!(requests[1].param("from_chat_id"), Some("42"));
+}
+
+#[test]
+fn replay_stored_update() {
+    use serde_json;
+    use Update;
+
+    let server = FakeServer::start().unwrap();
+    let api = server.api();
+    let (user, chat) = alice();
+    server.inject_text(&user, &chat, "Store me");
+
+    // Store a received update and deliver it again
+    let update = api.get_updates(None, None, None).unwrap().remove(0);
+    let stored = serde_json::to_string(&update).unwrap();
+    let restored: Update = serde_json::from_str(&stored).unwrap();
+    let id = server.push_update(&restored);
+    assert!(id > update.update_id);
+
+    let updates = api.get_updates(Some(update.update_id.next()), None, None).unwrap();
+    assert_eq!(updates.len(), 1);
+    assert_eq!(updates[0].update_id, id);
+    assert_eq!(updates[0].message, update.message);
+}
+
+#[test]
+fn typed_requests() {
+    use {ParseMode, Request, SendMessage};
+
+    let server = FakeServer::start().unwrap();
+    let api = server.api();
+    let (_, chat) = alice();
+    server.add_chat(&chat);
+
+    let sent = SendMessage::new(42, "*Hi*")
+        .parse_mode(ParseMode::Markdown)
+        .disable_web_page_preview(true)
+        .send(&api)
+        .unwrap();
+    assert_eq!(sent.msg, MessageType::Text("*Hi*".into()));
+
+    let request = server.requests().pop().unwrap();
+    assert_eq!(request.method, "sendMessage");
+    assert_eq!(request.param("parse_mode"), Some("Markdown"));
+    assert_eq!(request.param("disable_web_page_preview"), Some("true"));
+    assert_eq!(request.param("reply_to_message_id"), None);
+}
+
+#[test]
+fn send_to_channel_username() {
+    let server = FakeServer::start().unwrap();
+    server.add_chat(&Chat::Channel {
+        id: ChatId(-1001234),
+        title: "News".into(),
+        name: Some("news".into()),
+    });
+
+    let sent = server.api()
+        .send_message("@news", "Breaking".into(), None, None, None, None)
+        .unwrap();
+    assert_eq!(sent.chat.id(), ChatId(-1001234));
+    assert_eq!(server.requests()[0].param("chat_id"), Some("@news"));
+}
+
+#[test]
+fn channel_username_methods() {
+    use serde_json;
+
+    let server = FakeServer::start().unwrap();
+    let api = server.api();
+    server.add_chat(&Chat::Channel {
+        id: ChatId(-1001234),
+        title: "News".into(),
+        name: Some("news".into()),
+    });
+
+    let sent = api.send_message("@news", "Breaking".into(), None, None, None, None).unwrap();
+    let edited = api.edit_message_text("@news", sent.message_id, "Old news".into(), None, None)
+        .unwrap();
+    assert_eq!(edited.msg, MessageType::Text("Old news".into()));
+    assert!(api.delete_message("@news", sent.message_id).unwrap());
+
+    let poll = api.send_poll("@news", "Why?".into(), vec!["A".into(), "B".into()],
+                             None, None, None).unwrap();
+    assert!(api.stop_poll("@news", poll.message_id).unwrap().is_closed);
+
+    // Channel posts have no sender, which the fake server can't forward
+    let (_, chat) = alice();
+    server.add_chat(&chat);
+    server.inject_update(serde_json::from_str(r#"{"channel_post": {
+        "message_id": 1000, "date": 0, "text": "Post",
+        "chat": {"id": -1001234, "type": "channel", "title": "News", "username": "news"}}}"#)
+        .unwrap());
+    match api.forward_message(42, "@news", MessageId(1000)) {
+        Err(Error::Api(ref desc)) if desc == "Bad Request: message can't be forwarded" => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn convenience_methods() {
+    let server = FakeServer::start().unwrap();
+    let api = server.api();
+    let (user, chat) = alice();
+    server.inject_text(&user, &chat, "Hi bot");
+
+    let incoming = api.get_updates(None, None, None).unwrap().remove(0).message.unwrap();
+    let answer = incoming.reply(&api, "Hi Alice").unwrap();
+    assert_eq!(answer.reply.as_ref().map(|m| m.message_id), Some(incoming.message_id));
+
+    let edited = answer.edit_text(&api, "Hello Alice").unwrap();
+    assert_eq!(edited.msg, MessageType::Text("Hello Alice".into()));
+
+    assert!(chat.typing(&api).unwrap());
+    let second = chat.send(&api, "Bye").unwrap();
+    assert_eq!(server.messages_in(chat.id()).len(), 2);
+
+    assert!(second.delete(&api).unwrap());
+    assert!(second.delete(&api).is_err());
+    let sent = server.messages_in(chat.id());
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].msg, MessageType::Text("Hello Alice".into()));
+
+    let forwarded = incoming.forward_to(&api, &user).unwrap();
+    assert_eq!(forwarded.forward.map(|(u, _)| u), Some(user));
+}
+
+#[test]
+fn broadcast() {
+    use broadcast::{Broadcast, Failure};
+    use std::fs::{self, OpenOptions};
+    use std::io::Write;
+    use SendMessage;
+
+    let server = FakeServer::start().unwrap();
+    let api = server.api();
+    for id in 1..4 {
+        server.add_chat(&Chat::Private {
+            id: ChatId(id),
+            first_name: format!("User {}", id),
+            last_name: None,
+            username: None,
+        });
+    }
+    server.block_chat(ChatId(2));
+
+    let progress = ::std::env::temp_dir()
+        .join(format!("telegram-bot-broadcast-{}.progress", ::std::process::id()));
+    let _ = fs::remove_file(&progress);
+    let chats = vec![ChatId(1), ChatId(2), ChatId(3), ChatId(4), ChatId(1)];
+
+    // A first run which only got through the first two chats
+    let mut calls = 0;
+    let summary = Broadcast::new(chats[..2].to_vec(), |chat| SendMessage::new(chat, "News"))
+        .progress_file(&progress)
+        .run(&api, |_| calls += 1)
+        .unwrap();
+    assert_eq!(calls, 2);
+    assert_eq!(summary.sent, 1);
+
+    // Crashed while writing the progress
+    OpenOptions::new().append(true).open(&progress).unwrap().write_all(b"3 se").unwrap();
+
+    let mut last = None;
+    let summary = Broadcast::new(chats.clone(), |chat| SendMessage::new(chat, "News"))
+        .progress_file(&progress)
+        .run(&api, |p| last = Some(*p))
+        .unwrap();
+
+    assert_eq!(summary.resumed, 2);
+    assert_eq!(summary.sent, 2);
+    assert_eq!(summary.failed, vec![
+        (ChatId(2), Failure::Blocked),
+        (ChatId(4), Failure::ChatNotFound),
+    ]);
+    let last = last.unwrap();
+    assert_eq!((last.total, last.done, last.sent, last.failed), (4, 4, 2, 2));
+
+    // Every reachable chat got the message exactly once
+    assert_eq!(server.messages_in(ChatId(1)).len(), 1);
+    assert_eq!(server.messages_in(ChatId(2)).len(), 0);
+    assert_eq!(server.messages_in(ChatId(3)).len(), 1);
+
+    // Everything was recorded
+    let summary = Broadcast::new(chats, |chat| SendMessage::new(chat, "News"))
+        .progress_file(&progress)
+        .run(&api, |_| {})
+        .unwrap();
+    fs::remove_file(&progress).unwrap();
+    assert_eq!(summary.resumed, 4);
+    assert_eq!(server.messages_in(ChatId(3)).len(), 1);
+}
+
+#[test]
+fn broadcast_retries() {
+    use broadcast::{Broadcast, Failure};
+    use ratelimit::{Rate, RateLimitMode, RateLimiter, RateLimits};
+    use std::fs;
+    use std::time::Duration;
+    use SendMessage;
+
+    let server = FakeServer::start().unwrap();
+    for id in 1..4 {
+        server.add_chat(&Chat::Private {
+            id: ChatId(id),
+            first_name: format!("User {}", id),
+            last_name: None,
+            username: None,
+        });
+    }
+    let progress = ::std::env::temp_dir()
+        .join(format!("telegram-bot-broadcast-{}-retries.progress", ::std::process::id()));
+    let _ = fs::remove_file(&progress);
+    let chats = vec![ChatId(1), ChatId(2), ChatId(3)];
+
+    // Nothing is listening on this port
+    let offline = Api::from_token_and_url(TOKEN, "http://127.0.0.1:1/bot").unwrap();
+    let summary = Broadcast::new(chats.clone(), |chat| SendMessage::new(chat, "News"))
+        .max_retries(1)
+        .retry_delay(Duration::from_millis(10))
+        .progress_file(&progress)
+        .run(&offline, |_| {})
+        .unwrap();
+    assert_eq!(summary.failed.len(), 3);
+    match summary.failed[0].1 {
+        Failure::Transient(_) => {},
+        ref other => panic!("unexpected failure: {:?}", other),
+    }
+
+    // Waiting for the limiter doesn't count as retry
+    let mut api = server.api();
+    let rate = Rate::new(1, Duration::from_millis(100));
+    api.set_rate_limiter(Some(RateLimiter::new(RateLimits {
+        global: rate,
+        private: rate,
+        group: rate,
+        channel: rate,
+    }).mode(RateLimitMode::Try)));
+    let summary = Broadcast::new(chats, |chat| SendMessage::new(chat, "News"))
+        .max_retries(0)
+        .progress_file(&progress)
+        .run(&api, |_| {})
+        .unwrap();
+    fs::remove_file(&progress).unwrap();
+
+    assert_eq!(summary.resumed, 0);
+    assert_eq!(summary.sent, 3);
+    assert!(summary.failed.is_empty());
+}
+
+#[test]
+fn listener_offset_store() {
+    use offset::{MemoryOffsetStore, OffsetStore};
+
+    let server = FakeServer::start().unwrap();
+    let api = server.api();
+    let (user, chat) = alice();
+    server.inject_text(&user, &chat, "already handled");
+    let second = server.inject_text(&user, &chat, "new");
+    server.inject_text(&user, &chat, "newer");
+
+    // The bot crashed after handling the first update, but before confirming
+    // it to the server.
+    let mut store = MemoryOffsetStore::new();
+    store.store(second.0).unwrap();
+
+    let mut received = Vec::new();
+    let mut listener = api.listener(ListeningMethod::LongPoll(None));
+    listener.set_offset_store(store.clone());
+    listener.listen(|u| {
+        received.push(u.update_id);
+        Ok(if received.len() == 2 { ListeningAction::Stop } else { ListeningAction::Continue })
+    }).unwrap();
+
+    assert_eq!(received, vec![second, UpdateId(second.0 + 1)]);
+    assert_eq!(store.offset(), Some(second.0 + 2));
+    assert_eq!(server.pending_updates(), 0);
+}
+
+#[test]
+fn listener_dedup_store() {
+    use dedup::{DedupStore, MemoryDedupStore};
+
+    let server = FakeServer::start().unwrap();
+    let api = server.api();
+    let (user, chat) = alice();
+    let first = server.inject_text(&user, &chat, "already handled");
+    let second = server.inject_text(&user, &chat, "new");
+
+    // The bot crashed after handling the first update, before the offset
+    // was saved.
+    let mut store = MemoryDedupStore::default();
+    store.insert(first).unwrap();
+
+    let mut received = Vec::new();
+    let mut listener = api.listener(ListeningMethod::LongPoll(None));
+    listener.set_dedup_store(store);
+
+    // Failed updates are not recorded and handled again
+    let res = listener.listen(|u| {
+        received.push(u.update_id);
+        Err(Error::InvalidState("handler failed".into()))
+    });
+    assert!(res.is_err());
+    listener.listen(|u| {
+        received.push(u.update_id);
+        Ok(ListeningAction::Stop)
+    }).unwrap();
+
+    assert_eq!(received, vec![second, second]);
+    assert_eq!(server.pending_updates(), 0);
+}
+
+#[test]
+fn record_and_replay() {
+    use record::{Recorder, Replayer};
+    use std::{env, fs, process};
+
+    let path = env::temp_dir().join(format!("telegram-bot-{}-listener.jsonl", process::id()));
+    let _ = fs::remove_file(&path);
+
+    let server = FakeServer::start().unwrap();
+    let api = server.api();
+    let (user, chat) = alice();
+    server.inject_text(&user, &chat, "first");
+    server.inject_text(&user, &chat, "second");
+
+    // The first update fails and is received again
+    let mut received = Vec::new();
+    let mut listener = api.listener(ListeningMethod::LongPoll(None));
+    listener.set_recorder(Recorder::open(&path).unwrap());
+    let res = listener.listen(|_| Err(Error::InvalidState("handler failed".into())));
+    assert!(res.is_err());
+    listener.listen(|u| {
+        received.push(u);
+        Ok(if received.len() == 2 { ListeningAction::Stop } else { ListeningAction::Continue })
+    }).unwrap();
+
+    let mut replayed = Vec::new();
+    let handled = Replayer::open(&path).unwrap().run(|u| {
+        replayed.push(u);
+        Ok(ListeningAction::Continue)
+    }).unwrap();
+    assert_eq!(handled, 2);
+    assert_eq!(replayed, received);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn listen_concurrently() {
+    use offset::MemoryOffsetStore;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    let server = FakeServer::start().unwrap();
+    let api = server.api();
+    let (user, chat) = alice();
+    let group = Chat::Group {
+        id: ChatId(-100),
+        title: "Group".into(),
+        is_supergroup: false,
+        username: None,
+    };
+    server.add_chat(&group);
+    server.inject_text(&user, &chat, "slow");
+    server.inject_text(&user, &chat, "after slow");
+    let last = server.inject_text(&user, &group, "fast");
+
+    let store = MemoryOffsetStore::new();
+    let mut listener = api.listener(ListeningMethod::LongPoll(None));
+    listener.set_offset_store(store.clone());
+
+    let handled = Arc::new(Mutex::new(Vec::new()));
+    let log = handled.clone();
+    listener.listen_concurrently(4, move |u| {
+        let text = match u.message.unwrap().msg {
+            MessageType::Text(text) => text,
+            other => panic!("unexpected message: {:?}", other),
+        };
+
+        // The slow handler must not block the other chat
+        if text == "slow" {
+            for _ in 0..100 {
+                if log.lock().unwrap().contains(&"fast".to_string()) {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(20));
+            }
+        }
+
+        let mut log = log.lock().unwrap();
+        log.push(text);
+        Ok(if log.len() == 3 { ListeningAction::Stop } else { ListeningAction::Continue })
+    }).unwrap();
+
+    assert_eq!(*handled.lock().unwrap(), vec!["fast", "slow", "after slow"]);
+    assert_eq!(store.offset(), Some(last.0 + 1));
+    assert_eq!(server.pending_updates(), 0);
+}
+
+#[test]
+fn listen_concurrently_failed_update() {
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    let server = FakeServer::start().unwrap();
+    let api = server.api();
+    let (user, chat) = alice();
+    let group = Chat::Group {
+        id: ChatId(-100),
+        title: "Group".into(),
+        is_supergroup: false,
+        username: None,
+    };
+    server.add_chat(&group);
+    server.inject_text(&user, &chat, "fail");
+    server.inject_text(&user, &group, "fast");
+
+    // The first update fails after a later one was handled
+    let handled = Arc::new(Mutex::new(Vec::new()));
+    let log = handled.clone();
+    let mut listener = api.listener(ListeningMethod::LongPoll(None));
+    let res = listener.listen_concurrently(4, move |u| {
+        let text = match u.message.unwrap().msg {
+            MessageType::Text(text) => text,
+            other => panic!("unexpected message: {:?}", other),
+        };
+        if text == "fail" {
+            for _ in 0..100 {
+                if !log.lock().unwrap().is_empty() {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(20));
+            }
+            return Err(Error::InvalidState("failed".into()));
+        }
+        log.lock().unwrap().push(text);
+        Ok(ListeningAction::Continue)
+    });
+    assert!(res.is_err());
+    assert_eq!(*handled.lock().unwrap(), vec!["fast"]);
+
+    // Both are received again, the failed one wasn't confirmed
+    let updates = api.get_updates(None, None, Some(0)).unwrap();
+    let texts: Vec<_> = updates.into_iter().map(|u| u.message.unwrap().msg).collect();
+    assert_eq!(texts, vec![MessageType::Text("fail".into()), MessageType::Text("fast".into())]);
+}
+
+#[test]
+fn shutdown_listener() {
+    use std::sync::mpsc;
+    use std::time::{Duration, Instant};
+
+    let server = FakeServer::start().unwrap();
+    let api = server.api();
+    let (user, chat) = alice();
+    server.inject_text(&user, &chat, "hello");
+
+    let (tx, rx) = mpsc::channel();
+    let listener = api.listener(ListeningMethod::LongPoll(Some(30)));
+    let thread = listener.spawn(move |u| {
+        tx.send(u.update_id).unwrap();
+        Ok(ListeningAction::Continue)
+    });
+    rx.recv_timeout(Duration::from_secs(5)).unwrap();
+
+    // The listener is now waiting for more updates
+    let start = Instant::now();
+    thread.shutdown_handle().shutdown();
+    thread.join().unwrap();
+    assert!(start.elapsed() < Duration::from_millis(500));
+    assert_eq!(server.pending_updates(), 0);
+}
+
+#[test]
+fn shutdown_while_polling() {
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    let server = FakeServer::start().unwrap();
+    let api = server.api();
+    let (user, chat) = alice();
+    let last = server.inject_text(&user, &chat, "hello");
+
+    let mut listener = api.listener(ListeningMethod::LongPoll(Some(30)));
+    let handle = listener.shutdown_handle();
+    let (tx, rx) = mpsc::channel();
+    let stopper = thread::spawn(move || {
+        rx.recv().unwrap();
+        thread::sleep(Duration::from_millis(100));
+        handle.shutdown();
+    });
+    listener.listen(|_| {
+        tx.send(()).unwrap();
+        Ok(ListeningAction::Continue)
+    }).unwrap();
+    stopper.join().unwrap();
+
+    // Listening again stops at once, but doesn't forget the handled update
+    listener.listen(|u| panic!("unexpected update: {:?}", u)).unwrap();
+    let request = server.requests().pop().unwrap();
+    assert_eq!(request.param("offset"), Some(&*(last.0 + 1).to_string()));
+}
+
+#[test]
+fn listener_channel() {
+    use std::time::Duration;
+
+    let server = FakeServer::start().unwrap();
+    let api = server.api();
+    let (user, chat) = alice();
+    let first = server.inject_text(&user, &chat, "first");
+    server.inject_text(&user, &chat, "second");
+
+    let (results, events) = api.listener(ListeningMethod::LongPoll(None)).channel();
+    let timeout = Duration::from_secs(5);
+    match events.recv_timeout(timeout).unwrap() {
+        ListenerEvent::Update(u) => assert_eq!(u.update_id, first),
+        other => panic!("unexpected event: {:?}", other),
+    }
+    results.send(Ok(ListeningAction::Continue)).unwrap();
+
+    assert!(match events.recv_timeout(timeout).unwrap() {
+        ListenerEvent::Update(_) => true,
+        _ => false,
+    });
+    results.send(Err(Error::InvalidState("handler failed".into()))).unwrap();
+
+    // The error of the handler is reported and the failed update stays
+    // pending.
+    match events.recv_timeout(timeout).unwrap() {
+        ListenerEvent::Error(Error::InvalidState(ref desc)) if desc == "handler failed" => {}
+        other => panic!("unexpected event: {:?}", other),
+    }
+    assert!(events.recv().is_err());
+    assert_eq!(server.pending_updates(), 1);
+}
+
+#[test]
+fn updates_iterator() {
+    use offset::MemoryOffsetStore;
+
+    let server = FakeServer::start().unwrap();
+    let api = server.api();
+    let (user, chat) = alice();
+    let first = server.inject_text(&user, &chat, "first");
+    let second = server.inject_text(&user, &chat, "second");
+    let third = server.inject_text(&user, &chat, "third");
+
+    let store = MemoryOffsetStore::new();
+    let mut listener = api.listener(ListeningMethod::LongPoll(None));
+    listener.set_offset_store(store.clone());
+
+    // Leaving the loop without confirming: The last update is not handled
+    for update in listener.updates() {
+        let update = update.unwrap();
+        if update.update_id == second {
+            break;
+        }
+    }
+    assert_eq!(store.offset(), Some(first.0 + 1));
+    assert_eq!(server.pending_updates(), 2);
+
+    // With `confirm`, it is
+    let mut received = Vec::new();
+    {
+        let mut updates = listener.updates();
+        while let Some(update) = updates.next() {
+            received.push(update.unwrap().update_id);
+            if received.len() == 2 {
+                updates.confirm().unwrap();
+                break;
+            }
+        }
+    }
+    assert_eq!(received, vec![second, third]);
+    assert_eq!(store.offset(), Some(third.0 + 1));
+    assert_eq!(server.pending_updates(), 0);
+}
+
+#[test]
+fn allowed_updates() {
+    use types::AllowedUpdate;
+
+    let server = FakeServer::start().unwrap();
+    let api = server.api();
+    let (user, chat) = alice();
+    server.inject_text(&user, &chat, "hello");
+
+    let mut listener = api.listener(ListeningMethod::LongPoll(None));
+    listener.set_allowed_updates(&[AllowedUpdate::Message, AllowedUpdate::CallbackQuery]);
+    listener.listen(|_| Ok(ListeningAction::Stop)).unwrap();
+
+    let requests = server.requests();
+    assert!(requests.iter().all(|r| r.method == "getUpdates"));
+    assert_eq!(requests[0].param("allowed_updates"), Some(r#"["message","callback_query"]"#));
+}
+
+#[test]
+fn allowed_updates_duplicates() {
+    use requests::{GetUpdates, SetWebhook};
+    use types::AllowedUpdate;
+    use url::Url;
+
+    let server = FakeServer::start().unwrap();
+    let api = server.api();
+    let (user, chat) = alice();
+    server.inject_text(&user, &chat, "hello");
+
+    let allowed = [AllowedUpdate::CallbackQuery, AllowedUpdate::Message, AllowedUpdate::Message];
+    let mut listener = api.listener(ListeningMethod::LongPoll(None));
+    listener.set_allowed_updates(&allowed);
+    listener.listen(|_| Ok(ListeningAction::Stop)).unwrap();
+
+    api.send(GetUpdates::new().timeout(0).allowed_updates(&allowed)).unwrap();
+    let url = Url::parse("https://example.com/hook").unwrap();
+    api.send(SetWebhook::new(url).allowed_updates(&allowed)).unwrap();
+
+    let requests = server.requests();
+    assert_eq!(requests.last().unwrap().method, "setWebhook");
+    for request in &requests {
+        assert_eq!(request.param("allowed_updates"), Some(r#"["message","callback_query"]"#));
+    }
+}
+
+#[test]
+fn webhook_management() {
+    use requests::{DeleteWebhook, InputFile, SetWebhook};
+    use types::AllowedUpdate;
+    use url::Url;
+
+    let server = FakeServer::start().unwrap();
+    let api = server.api();
+    let (user, chat) = alice();
+
+    match api.set_webhook(Some("not a url")) {
+        Err(Error::InvalidUrl(_)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+    assert!(api.set_webhook(Some("http://example.com/hook")).is_err());
+
+    let url = Url::parse("https://example.com/hook").unwrap();
+    let certificate = InputFile::new("cert.pem", b"-----BEGIN CERTIFICATE-----\r\n".to_vec());
+    assert!(api.send(SetWebhook::new(url)
+                     .certificate(certificate.clone())
+                     .max_connections(10)
+                     .allowed_updates(&[AllowedUpdate::Message])
+                     .secret_token("s3cret")).unwrap());
+
+    let request = server.requests().pop().unwrap();
+    assert_eq!(request.param("secret_token"), Some("s3cret"));
+    assert_eq!(request.files[0].field, "certificate");
+    assert_eq!(request.files[0].file_name, "cert.pem");
+    assert_eq!(&*request.files[0].data, certificate.data());
+
+    server.inject_text(&user, &chat, "hello");
+    let info = api.get_webhook_info().unwrap();
+    assert_eq!(info.url, "https://example.com/hook");
+    assert!(info.has_custom_certificate);
+    assert_eq!(info.pending_update_count, 1);
+    assert_eq!(info.max_connections, Some(10));
+    assert_eq!(info.allowed_updates, Some(vec!["message".to_string()]));
+
+    // Long polling doesn't work while a webhook is set
+    match api.get_updates(None, None, None) {
+        Err(Error::Api(ref desc)) if desc.starts_with("Conflict") => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    assert!(api.send(DeleteWebhook::new().drop_pending_updates(true)).unwrap());
+    let info = api.get_webhook_info().unwrap();
+    assert_eq!(info.url, "");
+    assert_eq!(info.pending_update_count, 0);
+}
+
+#[test]
+fn quiz() {
+    use polls::PollTally;
+    use types::{MessageEntity, MessageEntityKind, PollKind};
+    use {Request, SendPoll};
+
+    let server = FakeServer::start().unwrap();
+    let api = server.api();
+    let (user, chat) = alice();
+    server.add_chat(&chat);
+
+    let sent = SendPoll::quiz(42, "2 + 2?", vec!["3", "4", "5"], 1)
+        .anonymous(false)
+        .explanation("Count again")
+        .explanation_entities(vec![MessageEntity::new(MessageEntityKind::Bold, 0, 5)])
+        .open_period(60)
+        .send(&api).unwrap();
+    let poll = match sent.msg {
+        MessageType::Poll(poll) => poll,
+        other => panic!("unexpected message: {:?}", other),
+    };
+    assert_eq!(poll.kind, PollKind::Quiz);
+    assert_eq!(poll.options.len(), 3);
+    assert_eq!(poll.correct_option_id, Some(1));
+    assert_eq!(poll.explanation_entities.as_ref().unwrap()[0].kind, MessageEntityKind::Bold);
+    assert_eq!(poll.open_period, Some(60));
+    assert_eq!(server.requests()[0].param("options"),
+               Some(r#"[{"text":"3"},{"text":"4"},{"text":"5"}]"#));
+
+    let mut tally = PollTally::new();
+    tally.track(&poll);
+    server.inject_poll_answer(&user, &poll.id, &[0]);
+    server.inject_poll_answer(&user, &poll.id, &[]);
+    let last = server.inject_poll_answer(&user, &poll.id, &[1]);
+
+    let mut listener = api.listener(ListeningMethod::LongPoll(None));
+    let mut answers = 0;
+    listener.listen(|u| {
+        assert!(tally.handle(&u));
+        answers += u.poll_answer.is_some() as usize;
+        Ok(if u.update_id == last { ListeningAction::Stop } else { ListeningAction::Continue })
+    }).unwrap();
+    assert_eq!(answers, 3);
+
+    let results = tally.results(&poll.id).unwrap();
+    assert_eq!(results.counts, vec![0, 1, 0]);
+    assert_eq!(results.correct, vec![UserId(42)]);
+
+    let stopped = api.stop_poll(42, sent.message_id).unwrap();
+    assert!(stopped.is_closed);
+    assert_eq!(stopped.total_voter_count, 1);
+    match api.stop_poll(42, sent.message_id) {
+        Err(Error::Api(ref desc)) if desc == "Bad Request: poll has already been closed" => {},
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn stars_payment() {
+    use types::{LabeledPrice, STARS};
+    use {Request, SendInvoice};
+
+    let server = FakeServer::start().unwrap();
+    let api = server.api();
+    let (user, chat) = alice();
+    server.add_chat(&chat);
+
+    // Payments in other currencies need a provider token
+    match api.send_invoice(42, "Coffee".into(), "A cup of coffee".into(), "coffee".into(),
+                           "EUR".into(), vec![LabeledPrice::new("Coffee", 250)], None, None) {
+        Err(Error::Api(ref desc)) if desc == "Bad Request: PAYMENT_PROVIDER_INVALID" => {},
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    let sent = SendInvoice::stars(42, "Premium", "One month of premium", "premium-42", 250)
+        .send(&api).unwrap();
+    match sent.msg {
+        MessageType::Invoice(ref invoice) => {
+            assert_eq!(invoice.currency, STARS);
+            assert_eq!(invoice.total_amount, 250);
+        }
+        ref other => panic!("unexpected message: {:?}", other),
+    }
+    let link = api.create_invoice_link("Premium".into(), "One month".into(), "p".into(),
+                                       STARS.into(), vec![LabeledPrice::new("Premium", 250)]);
+    assert!(link.unwrap().starts_with("https://t.me/$"));
+
+    server.inject_checkout(&user, ChatId(42), sent.message_id);
+    let mut listener = api.listener(ListeningMethod::LongPoll(None));
+    let mut charge = None;
+    listener.listen(|u| {
+        if let Some(query) = u.pre_checkout_query {
+            assert_eq!(query.invoice_payload, "premium-42");
+            assert_eq!((&*query.currency, query.total_amount), (STARS, 250));
+            try!(api.answer_pre_checkout_query(query.id, Ok(())));
+            return Ok(ListeningAction::Continue);
+        }
+        if let MessageType::SuccessfulPayment(payment) = u.message.unwrap().msg {
+            charge = Some(payment.telegram_payment_charge_id);
+        }
+        Ok(ListeningAction::Stop)
+    }).unwrap();
+
+    let charge = charge.expect("no successful payment received");
+    assert!(api.refund_star_payment(UserId(42), charge.clone()).unwrap());
+    match api.refund_star_payment(UserId(42), charge) {
+        Err(Error::Api(ref desc)) if desc == "Bad Request: CHARGE_ALREADY_REFUNDED" => {},
+        other => panic!("unexpected result: {:?}", other),
+    }
+    let refund = api.get_updates(None, None, None).unwrap().pop().unwrap();
+    match refund.message.unwrap().msg {
+        MessageType::RefundedPayment(refund) => assert_eq!(refund.invoice_payload, "premium-42"),
+        other => panic!("unexpected message: {:?}", other),
+    }
+}
+
+#[test]
+fn shipping_query() {
+    use types::{LabeledPrice, ShippingAddress, ShippingOption};
+    use {Request, SendInvoice};
+
+    let server = FakeServer::start().unwrap();
+    let api = server.api();
+    let (user, chat) = alice();
+    server.add_chat(&chat);
+
+    let sent = SendInvoice::new(42, "Mug", "A coffee mug", "mug-42", "EUR",
+                                vec![LabeledPrice::new("Mug", 1200)])
+        .provider_token("fake-provider")
+        .need_shipping_address(true)
+        .flexible(true)
+        .send(&api).unwrap();
+    let address = ShippingAddress {
+        country_code: "DE".into(),
+        state: "".into(),
+        city: "Berlin".into(),
+        street_line1: "Unter den Linden 1".into(),
+        street_line2: "".into(),
+        post_code: "10117".into(),
+    };
+    let abroad = ShippingAddress { country_code: "US".into(), ..address.clone() };
+    server.inject_shipping_query(&user, ChatId(42), sent.message_id, &address);
+    server.inject_shipping_query(&user, ChatId(42), sent.message_id, &abroad);
+
+    let mut answered = 0;
+    let mut listener = api.listener(ListeningMethod::LongPoll(None));
+    listener.listen(|u| {
+        let query = u.shipping_query.unwrap();
+        assert_eq!(query.invoice_payload, "mug-42");
+        let answer = if query.shipping_address.country_code == "DE" {
+            Ok(vec![ShippingOption {
+                id: "dhl".into(),
+                title: "DHL".into(),
+                prices: vec![LabeledPrice::new("Shipping", 490)],
+            }])
+        } else {
+            Err("We only ship to Germany".into())
+        };
+        assert!(try!(api.answer_shipping_query(query.id, answer)));
+        answered += 1;
+        Ok(if answered == 2 { ListeningAction::Stop } else { ListeningAction::Continue })
+    }).unwrap();
+
+    let requests = server.requests();
+    let answers: Vec<_> = requests.iter()
+        .filter(|r| r.method == "answerShippingQuery")
+        .collect();
+    assert_eq!(answers.len(), 2);
+    assert!(answers[0].param("shipping_options").unwrap().contains(r#""id":"dhl""#));
+    assert_eq!(answers[1].param("error_message"), Some("We only ship to Germany"));
+
+    // Empty answers are rejected
+    let id = server.inject_shipping_query(&user, ChatId(42), sent.message_id, &address);
+    match api.answer_shipping_query(id.clone(), Ok(vec![])) {
+        Err(Error::Api(ref desc)) if desc == "Bad Request: shipping_options is empty" => {},
+        other => panic!("unexpected result: {:?}", other),
+    }
+    match api.answer_shipping_query(id, Err("".into())) {
+        Err(Error::Api(ref desc)) if desc == "Bad Request: error_message is empty" => {},
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn sticker_sets() {
+    use requests::{FileSource, InputFile, InputSticker};
+    use types::{MaskPoint, MaskPosition, StickerFormat, StickerKind};
+
+    let server = FakeServer::start().unwrap();
+    let api = server.api();
+    let owner = UserId(42);
+    let name = "moods_by_fake_bot".to_string();
+
+    let uploaded = api.upload_sticker_file(owner, InputFile::new("happy.webp", vec![1; 64]),
+                                           StickerFormat::Static).unwrap();
+    assert_eq!(uploaded.file_size, Some(64));
+
+    // Names have to end with the username of the bot
+    let stickers = vec![InputSticker::new(uploaded.file_id.clone(), StickerFormat::Static,
+                                          vec!["😀"])];
+    match api.create_new_sticker_set(owner, "moods".into(), "Moods".into(), stickers.clone()) {
+        Err(Error::Api(ref desc))
+            if desc == "Bad Request: invalid sticker set name is specified" => {},
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    // One reused and one new file
+    let mut stickers = stickers;
+    stickers.push(InputSticker::new(InputFile::new("sad.webm", vec![2; 16]),
+                                    StickerFormat::Video, vec!["😢", "😭"])
+        .keywords(vec!["sad"]));
+    assert!(api.create_new_sticker_set(owner, name.clone(), "Moods".into(), stickers).unwrap());
+    let request = server.requests().pop().unwrap();
+    assert_eq!(request.files.len(), 1);
+    assert_eq!(request.files[0].field, "sticker1");
+    assert!(request.param("stickers").unwrap().contains(r#""sticker":"attach://sticker1""#));
+
+    let mask = InputSticker::new(FileSource::Url("https://example.com/mask.png".into()),
+                                 StickerFormat::Static, vec!["🎭"])
+        .mask_position(MaskPosition { point: MaskPoint::Eyes, x_shift: 0.0, y_shift: 0.0,
+                                      scale: 1.0 });
+    assert!(api.add_sticker_to_set(owner, name.clone(), mask).unwrap());
+
+    let set = api.get_sticker_set(name.clone()).unwrap();
+    assert_eq!(set.sticker_type, StickerKind::Regular);
+    assert_eq!(set.stickers.len(), 3);
+    assert!(set.stickers[1].is_video);
+    assert_eq!(set.stickers[1].emoji, Some("😢".into()));
+    assert_eq!(set.stickers[2].mask_position.as_ref().map(|m| m.point), Some(MaskPoint::Eyes));
+    assert_eq!(set.stickers[0].set_name, Some(name.clone()));
+
+    let last = set.stickers[2].file_id.clone();
+    assert!(api.set_sticker_position_in_set(last.clone(), 0).unwrap());
+    assert!(api.delete_sticker_from_set(set.stickers[0].file_id.clone()).unwrap());
+    assert!(api.set_sticker_set_thumbnail(name.clone(), owner,
+                                          Some(InputFile::new("t.webp", vec![3]).into()),
+                                          StickerFormat::Static).unwrap());
+
+    let set = api.get_sticker_set(name).unwrap();
+    assert_eq!(set.stickers.len(), 2);
+    assert_eq!(set.stickers[0].file_id, last);
+    assert!(set.thumbnail.is_some());
+}
+
+#[test]
+fn media_group() {
+    use requests::{InputFile, InputMedia};
+
+    let server = FakeServer::start().unwrap();
+    let api = server.api();
+    let (_, chat) = alice();
+    server.add_chat(&chat);
+
+    let media = vec![
+        InputMedia::photo(InputFile::new("a.jpg", vec![1])),
+        InputMedia::photo(InputFile::new("b.jpg", vec![2])),
+    ];
+    let sent = api.send_media_group(42, media, None).unwrap();
+    let known = match sent[0].msg {
+        MessageType::Photo(ref sizes) => sizes[0].file_id.clone(),
+        ref other => panic!("unexpected message: {:?}", other),
+    };
+
+    // A sent file and a new one
+    let media = vec![
+        InputMedia::photo(known).caption("Before"),
+        InputMedia::video(InputFile::new("after.mp4", vec![2; 8])).duration(3),
+    ];
+    let sent = api.send_media_group(42, media, None).unwrap();
+    let request = server.requests().pop().unwrap();
+    assert_eq!(request.files.len(), 1);
+    assert_eq!(request.files[0].field, "media1");
+    assert!(request.param("media").unwrap().contains(r#""media":"attach://media1""#));
+
+    assert_eq!(sent.len(), 2);
+    assert!(sent[0].media_group_id.is_some());
+    assert_eq!(sent[0].media_group_id, sent[1].media_group_id);
+    match sent[0].msg {
+        MessageType::Photo(_) => {},
+        ref other => panic!("unexpected message: {:?}", other),
+    }
+    assert_eq!(sent[0].caption, Some("Before".into()));
+
+    // Documents can't be mixed with photos
+    let media = vec![
+        InputMedia::photo(InputFile::new("a.jpg", vec![1])),
+        InputMedia::document(InputFile::new("b.pdf", vec![2])),
+    ];
+    match api.send_media_group(42, media, None) {
+        Err(Error::Api(ref desc))
+            if desc == "Bad Request: wrong type of the media group item" => {},
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn listen_grouped() {
+    use std::time::Duration;
+    use album::Grouped;
+    use offset::MemoryOffsetStore;
+
+    let server = FakeServer::start().unwrap();
+    let api = server.api();
+    let (user, chat) = alice();
+    let first = server.inject_text(&user, &chat, "Look at these");
+    let album = server.inject_album(&user, &chat, 3);
+    let last = server.inject_text(&user, &chat, "/exit");
+
+    let store = MemoryOffsetStore::new();
+    let mut events = Vec::new();
+    let mut listener = api.listener(ListeningMethod::LongPoll(None));
+    listener.set_offset_store(store.clone());
+    listener.listen_grouped(Duration::from_millis(200), |event| {
+        let stop = match event {
+            Grouped::Single(ref u) => u.update_id == last,
+            Grouped::Album(_) => false,
+        };
+        events.push(event);
+        Ok(if stop { ListeningAction::Stop } else { ListeningAction::Continue })
+    }).unwrap();
+
+    // The update after the album waited for it
+    assert_eq!(events.len(), 3);
+    match events[0] {
+        Grouped::Single(ref u) => assert_eq!(u.update_id, first),
+        ref other => panic!("unexpected event: {:?}", other),
+    }
+    match events[1] {
+        Grouped::Album(ref a) => {
+            assert_eq!(a.messages.len(), album.len());
+            assert!(a.messages.iter().all(|m| m.media_group_id == Some(a.media_group_id.clone())));
+            assert!(a.messages[0].caption.is_some());
+        }
+        ref other => panic!("unexpected event: {:?}", other),
+    }
+    assert_eq!(store.offset(), Some(last.0 + 1));
+    assert_eq!(server.pending_updates(), 0);
+}
+
+#[test]
+fn listen_grouped_across_polls() {
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+    use serde_json;
+    use album::Grouped;
+
+    let server = Arc::new(FakeServer::start().unwrap());
+    let api = server.api();
+    let (user, chat) = alice();
+    server.inject_album(&user, &chat, 1);
+    let group = api.get_updates(None, Some(1), None).unwrap().remove(0)
+        .message.unwrap().media_group_id.unwrap();
+
+    // The rest of the album arrives while listening
+    let late = server.clone();
+    let sender = thread::spawn(move || {
+        for _ in 0..2 {
+            thread::sleep(Duration::from_millis(200));
+            late.inject_update(serde_json::from_str(&format!(r#"{{"message": {{
+                "message_id": 1, "date": 0, "media_group_id": "{}",
+                "from": {{"id": 42, "first_name": "Alice"}},
+                "chat": {{"id": 42, "type": "private", "first_name": "Alice"}},
+                "photo": [{{"file_id": "late", "width": 800, "height": 600}}]}}}}"#,
+                group)).unwrap());
+        }
+        late.inject_text(&user, &chat, "/exit")
+    });
+
+    let mut events = Vec::new();
+    let mut listener = api.listener(ListeningMethod::LongPoll(None));
+    listener.listen_grouped(Duration::from_millis(500), |event| {
+        let stop = match event {
+            Grouped::Single(_) => true,
+            Grouped::Album(_) => false,
+        };
+        events.push(event);
+        Ok(if stop { ListeningAction::Stop } else { ListeningAction::Continue })
+    }).unwrap();
+    let last = sender.join().unwrap();
+
+    assert_eq!(events.len(), 2);
+    match events[0] {
+        Grouped::Album(ref a) => assert_eq!(a.messages.len(), 3),
+        ref other => panic!("unexpected event: {:?}", other),
+    }
+    match events[1] {
+        Grouped::Single(ref u) => assert_eq!(u.update_id, last),
+        ref other => panic!("unexpected event: {:?}", other),
+    }
+    assert_eq!(server.pending_updates(), 0);
+}
+
+#[test]
+fn listen_grouped_failed_update() {
+    use std::time::Duration;
+    use album::Grouped;
+
+    let server = FakeServer::start().unwrap();
+    let api = server.api();
+    let (user, chat) = alice();
+    server.inject_album(&user, &chat, 2);
+    let failed = server.inject_text(&user, &chat, "fail");
+    server.inject_text(&user, &chat, "after");
+
+    let mut listener = api.listener(ListeningMethod::LongPoll(None));
+    let res = listener.listen_grouped(Duration::from_millis(200), |event| {
+        match event {
+            Grouped::Single(_) => Err(Error::InvalidState("failed".into())),
+            Grouped::Album(_) => Ok(ListeningAction::Continue),
+        }
+    });
+    assert!(res.is_err());
+
+    // Only the album was confirmed
+    assert_eq!(server.pending_updates(), 2);
+    let updates = api.get_updates(None, None, Some(0)).unwrap();
+    assert_eq!(updates[0].update_id, failed);
+}
+
@@ -5,77 +5,142 @@
 //! types, like "Location", map directly to Rust-structs. Other Telegram types,
 //! like "Message", was made more rusty by using enums.
 //!
-//! All types implement `Decodable` and `Encodable`, so they can be serialized
-//! as JSON. Non existing JSON-fields will result in `None` values for `Option`
-//! types. `None` values don't result in JSON fields.
+//! All types implement `Serialize` and `Deserialize`, so they can be
+//! serialized as JSON. Non existing JSON-fields will result in `None` values
+//! for `Option` types. `None` values don't result in JSON fields.
 //!
 
-use rustc_serialize::{Decodable, Encodable, Decoder, Encoder};
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, SerializeStruct, Serializer};
 use std::convert::Into;
 use std::fmt;
 
 // ===========================================================================
-// Helpers
+// Telegram primitive types
 // ===========================================================================
-// Macro to implement "Encodable" quickly. "None" fields won't be encoded.
-macro_rules! impl_encode {
-    (
-        $ty:ident, $count:expr,
-        [$($id:expr => $field:ident),*],
-        [$($o_id:expr => $o_field:ident),*]
-    ) => {
-        impl Encodable for $ty {
-            fn encode<E: Encoder>(&self, e: &mut E) -> Result<(), E::Error> {
-                e.emit_struct(stringify!($ty), $count, |e| {
-                    $(
-                        try!(e.emit_struct_field(stringify!($field), $id, |e| {
-                            self.$field.encode(e)
-                        }));
-                    )*
-                    $(
-                        if let Some(ref v) = self.$o_field {
-                            try!(e.emit_struct_field(
-                                stringify!($o_field), $o_id, |e| {
-                                v.encode(e)
-                            }));
-                        }
-                    )*
-
-                    Ok(())
-                })
+/// The Telegram "Integer": Currently i64.
+pub type Integer = i64;
+/// The Telegram "Float": Currently f32.
+pub type Float = f32;
+
+// Macro to define a newtype around `Integer` which is used as an id.
+macro_rules! integer_id {
+    ($(#[$attr:meta])* pub struct $name:ident;) => {
+        $(#[$attr])*
+        #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+        pub struct $name(pub Integer);
+
+        impl From<Integer> for $name {
+            fn from(id: Integer) -> $name {
+                $name(id)
+            }
+        }
+
+        impl From<$name> for Integer {
+            fn from(id: $name) -> Integer {
+                id.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                self.0.fmt(f)
             }
         }
     }
 }
 
-// Decodes a field with a given name. If successful: Return decoded
-// value. If not: Exit function with error value.
-macro_rules! try_field {
-    ($d:ident, $name:expr) => {
-        try!($d.read_struct_field($name, 0, Decodable::decode))
+integer_id! {
+    /// Unique identifier of a user (or bot).
+    pub struct UserId;
+}
+
+integer_id! {
+    /// Unique identifier of a chat. Ids of private chats are equal to the id
+    /// of the other user.
+    pub struct ChatId;
+}
+
+integer_id! {
+    /// Identifier of a message. Only unique within its chat.
+    pub struct MessageId;
+}
+
+integer_id! {
+    /// Identifier of an update. Increases with every new update.
+    pub struct UpdateId;
+}
+
+impl UpdateId {
+    /// The id after this one. Used as offset, it confirms this update.
+    pub fn next(self) -> UpdateId {
+        UpdateId(self.0 + 1)
     }
 }
 
+/// The private chat with a user has the same id as the user.
+impl From<UserId> for ChatId {
+    fn from(id: UserId) -> ChatId {
+        ChatId(id.0)
+    }
+}
 
-// ===========================================================================
-// Telegram primitive types
-// ===========================================================================
-/// The Telegram "Integer": Currently i64.
-pub type Integer = i64;
-/// The Telegram "Float": Currently f32.
-pub type Float = f32;
+/// Identifier of a file, which can be used to download or resend it.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
+pub struct FileId(pub String);
+
+impl<'a> From<&'a str> for FileId {
+    fn from(id: &'a str) -> FileId {
+        FileId(id.into())
+    }
+}
+
+impl From<String> for FileId {
+    fn from(id: String) -> FileId {
+        FileId(id)
+    }
+}
+
+impl AsRef<str> for FileId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for FileId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
 
 
 // ===========================================================================
 // Types not explicitly mentioned or somehow different from Telegram types
 // ===========================================================================
 /// All API responses are from this type. Mostly used internal.
-#[derive(RustcDecodable, Debug, PartialEq, Clone)]
-pub struct Response<T: Decodable> {
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Response<T> {
     pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub error_code: Option<Integer>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub result: Option<T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parameters: Option<ResponseParameters>,
+}
+
+/// Additional information about why a request was unsuccessful.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub struct ResponseParameters {
+    /// The group has been migrated to a supergroup with this id.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub migrate_to_chat_id: Option<ChatId>,
+    /// The number of seconds left to wait before the request can be repeated
+    /// (flood control).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_after: Option<Integer>,
 }
 
 // ---------------------------------------------------------------------------
@@ -96,26 +161,57 @@ impl From<ReplyKeyboardMarkup> for ReplyMarkup {
     }
 }
 
-impl Encodable for ReplyMarkup {
-    fn encode<E: Encoder>(&self, e: &mut E) -> Result<(), E::Error> {
+impl Serialize for ReplyMarkup {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
         match *self {
-            ReplyMarkup::Keyboard(ref k) => k.encode(e),
+            ReplyMarkup::Keyboard(ref k) => k.serialize(s),
             ReplyMarkup::KeyboardHide(b) => {
-                e.emit_struct("ReplyKeyboardHide", 2, |e| {
-                    try!(e.emit_struct_field("hide_keyboard", 0, |e| {
-                        true.encode(e)
-                    }));
-                    e.emit_struct_field("selective", 1, |e| b.encode(e))
-                })
+                let mut s = try!(s.serialize_struct("ReplyKeyboardHide", 2));
+                try!(s.serialize_field("hide_keyboard", &true));
+                try!(s.serialize_field("selective", &b));
+                s.end()
             },
             ReplyMarkup::ForceReply(b) => {
-                e.emit_struct("ForceReply", 2, |e| {
-                    try!(e.emit_struct_field("force_reply", 0, |e| {
-                        true.encode(e)
-                    }));
-                    e.emit_struct_field("selective", 1, |e| b.encode(e))
-                })
+                let mut s = try!(s.serialize_struct("ForceReply", 2));
+                try!(s.serialize_field("force_reply", &true));
+                try!(s.serialize_field("selective", &b));
+                s.end()
+            },
+        }
+    }
+}
+
+// All fields of the three kinds of reply markup. Which kind it is, is
+// determined by the fields present.
+#[derive(Deserialize)]
+struct RawReplyMarkup {
+    keyboard: Option<Vec<Vec<String>>>,
+    resize_keyboard: Option<bool>,
+    one_time_keyboard: Option<bool>,
+    selective: Option<bool>,
+    hide_keyboard: Option<bool>,
+    force_reply: Option<bool>,
+}
+
+impl<'de> Deserialize<'de> for ReplyMarkup {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let raw = try!(RawReplyMarkup::deserialize(d));
+        match raw {
+            RawReplyMarkup { keyboard: Some(keyboard), .. } => {
+                Ok(ReplyMarkup::Keyboard(ReplyKeyboardMarkup {
+                    keyboard: keyboard,
+                    resize_keyboard: raw.resize_keyboard,
+                    one_time_keyboard: raw.one_time_keyboard,
+                    selective: raw.selective,
+                }))
+            },
+            RawReplyMarkup { hide_keyboard: Some(true), .. } => {
+                Ok(ReplyMarkup::KeyboardHide(raw.selective.unwrap_or(false)))
+            },
+            RawReplyMarkup { force_reply: Some(true), .. } => {
+                Ok(ReplyMarkup::ForceReply(raw.selective.unwrap_or(false)))
             },
+            _ => Err(de::Error::custom("Not a valid reply markup")),
         }
     }
 }
@@ -123,7 +219,8 @@ impl Encodable for ReplyMarkup {
 // ---------------------------------------------------------------------------
 /// Strongly typed ChatAction. Instead of passing a String to the
 /// `send_chat_action` method, this is used.
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
 pub enum ChatAction {
     Typing,
     UploadPhoto,
@@ -135,22 +232,6 @@ pub enum ChatAction {
     FindLocation,
 }
 
-impl Decodable for ChatAction {
-    fn decode<D: Decoder>(d: &mut D) -> Result<Self, D::Error> {
-        Ok(match &*try!(d.read_str()) {
-            "typing" => ChatAction::Typing,
-            "upload_photo" => ChatAction::UploadPhoto,
-            "record_video" => ChatAction::RecordVideo,
-            "upload_video" => ChatAction::UploadVideo,
-            "record_audio" => ChatAction::RecordAudio,
-            "upload_audio" => ChatAction::UploadAudio,
-            "upload_document" => ChatAction::UploadDocument,
-            "find_location" => ChatAction::FindLocation,
-            _ => return Err(d.error("Not a valid chat action")),
-        })
-    }
-}
-
 impl Into<&'static str> for ChatAction {
     fn into(self) -> &'static str {
         match self {
@@ -172,30 +253,76 @@ impl ToString for ChatAction {
     }
 }
 
-impl Encodable for ChatAction {
-    fn encode<E: Encoder>(&self, e: &mut E) -> Result<(), E::Error> {
-        e.emit_str((*self).into())
+// ---------------------------------------------------------------------------
+/// Kind of update a bot wants to receive. Used for the "allowed_updates"
+/// parameter of `getUpdates` and `setWebhook`.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum AllowedUpdate {
+    Message,
+    EditedMessage,
+    ChannelPost,
+    EditedChannelPost,
+    InlineQuery,
+    ChosenInlineResult,
+    CallbackQuery,
+    ShippingQuery,
+    PreCheckoutQuery,
+    Poll,
+    PollAnswer,
+    MyChatMember,
+    ChatMember,
+    ChatJoinRequest,
+}
+
+impl Into<&'static str> for AllowedUpdate {
+    fn into(self) -> &'static str {
+        match self {
+            AllowedUpdate::Message => "message",
+            AllowedUpdate::EditedMessage => "edited_message",
+            AllowedUpdate::ChannelPost => "channel_post",
+            AllowedUpdate::EditedChannelPost => "edited_channel_post",
+            AllowedUpdate::InlineQuery => "inline_query",
+            AllowedUpdate::ChosenInlineResult => "chosen_inline_result",
+            AllowedUpdate::CallbackQuery => "callback_query",
+            AllowedUpdate::ShippingQuery => "shipping_query",
+            AllowedUpdate::PreCheckoutQuery => "pre_checkout_query",
+            AllowedUpdate::Poll => "poll",
+            AllowedUpdate::PollAnswer => "poll_answer",
+            AllowedUpdate::MyChatMember => "my_chat_member",
+            AllowedUpdate::ChatMember => "chat_member",
+            AllowedUpdate::ChatJoinRequest => "chat_join_request",
+        }
+    }
+}
+
+impl ToString for AllowedUpdate {
+    fn to_string(&self) -> String {
+        Into::<&str>::into(*self).into()
     }
 }
 
 // ---------------------------------------------------------------------------
 /// Either a Private or a Group or a Channel. Used in "chat" field of Message. Has some
 /// useful methods for less typing.
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(from = "RawChat", into = "RawChat")]
 pub enum Chat {
     Private {
-        id: Integer,
+        id: ChatId,
         first_name: String,
         last_name: Option<String>,
         username: Option<String>,
     },
     Group {
-        id: Integer,
+        id: ChatId,
         title: String,
-        is_supergroup: bool
+        is_supergroup: bool,
+        /// Only public supergroups have a username.
+        username: Option<String>,
     },
     Channel {
-        id: Integer,
+        id: ChatId,
         title: String,
         name: Option<String>
     },
@@ -203,7 +330,7 @@ pub enum Chat {
 
 impl Chat {
     /// Returns the chat id, which is needed to send messages.
-    pub fn id(&self) -> Integer {
+    pub fn id(&self) -> ChatId {
         match self {
             &Chat::Private { id, .. } => id,
             &Chat::Group { id, .. } => id,
@@ -234,7 +361,7 @@ impl Chat {
     pub fn to_user(&self) -> Option<User> {
         if let &Chat::Private { id, ref first_name, ref last_name, ref username } = self {
             Some(User {
-                id: id,
+                id: UserId(id.0),
                 first_name: first_name.clone(),
                 last_name: last_name.clone(),
                 username: username.clone(),
@@ -243,102 +370,166 @@ impl Chat {
     }
 }
 
-impl Decodable for Chat {
-    fn decode<D: Decoder>(d: &mut D) -> Result<Self, D::Error> {
-        d.read_struct("Chat", 0, |d| {
-            // All kinds of chat have an 'id' and a 'type' fields
-            let id : Integer = try_field!(d, "id");
-            let typ: String = try_field!(d, "type");
+// The chat as it is sent by Telegram: The kind of the chat is given by the
+// "type" field.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum RawChat {
+    Private {
+        id: ChatId,
+        first_name: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        last_name: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        username: Option<String>,
+    },
+    Group {
+        id: ChatId,
+        title: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        username: Option<String>,
+    },
+    Supergroup {
+        id: ChatId,
+        title: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        username: Option<String>,
+    },
+    Channel {
+        id: ChatId,
+        title: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        username: Option<String>,
+    },
+}
 
-            match typ.as_ref() {
-                "private" => {
-                    Ok(Chat::Private {
-                        id: id,
-                        first_name: try_field!(d, "first_name"),
-                        last_name: try_field!(d, "last_name"),
-                        username: try_field!(d, "username"),
-                    })
-                }
-                "group" => {
-                    Ok(Chat::Group {
-                        id: id,
-                        title: try_field!(d, "title"),
-                        is_supergroup: false
-                    })
+impl From<RawChat> for Chat {
+    fn from(chat: RawChat) -> Chat {
+        match chat {
+            RawChat::Private { id, first_name, last_name, username } => {
+                Chat::Private {
+                    id: id,
+                    first_name: first_name,
+                    last_name: last_name,
+                    username: username,
                 }
-                "supergroup" => {
-                    Ok(Chat::Group {
-                        id: id,
-                        title: try_field!(d, "title"),
-                        is_supergroup: true
-                    })
-                }
-                "channel" => {
-                    Ok(Chat::Channel {
-                        id: id,
-                        title: try_field!(d, "title"),
-                        name: try_field!(d, "username"),
-                    })
+            },
+            RawChat::Group { id, title, username } => {
+                Chat::Group { id: id, title: title, is_supergroup: false, username: username }
+            },
+            RawChat::Supergroup { id, title, username } => {
+                Chat::Group { id: id, title: title, is_supergroup: true, username: username }
+            },
+            RawChat::Channel { id, title, username } => {
+                Chat::Channel { id: id, title: title, name: username }
+            },
+        }
+    }
+}
+
+impl From<Chat> for RawChat {
+    fn from(chat: Chat) -> RawChat {
+        match chat {
+            Chat::Private { id, first_name, last_name, username } => {
+                RawChat::Private {
+                    id: id,
+                    first_name: first_name,
+                    last_name: last_name,
+                    username: username,
                 }
-                _ => Err(d.error(&format!("Invalid chat type: {}", typ)))
-            }
-        })
+            },
+            Chat::Group { id, title, is_supergroup: false, username } => {
+                RawChat::Group { id: id, title: title, username: username }
+            },
+            Chat::Group { id, title, is_supergroup: true, username } => {
+                RawChat::Supergroup { id: id, title: title, username: username }
+            },
+            Chat::Channel { id, title, name } => {
+                RawChat::Channel { id: id, title: title, username: name }
+            },
+        }
     }
 }
 
-impl Encodable for Chat {
-    fn encode<E: Encoder>(&self, e: &mut E) -> Result<(), E::Error> {
+// ---------------------------------------------------------------------------
+/// The target of a request: Either the unique id of a chat or the username of
+/// a channel or supergroup (in the format `@channelusername`). Used for the
+/// "chat_id" parameter of most methods.
+///
+/// Can be created from a `ChatId`, an `Integer`, a username (with or without
+/// leading "@") and from references to `Chat`, `Message` or `User`.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Clone)]
+#[serde(untagged)]
+pub enum ChatRef {
+    Id(ChatId),
+    /// The username including the leading "@".
+    Username(String),
+}
 
-        match self {
-            &Chat::Private { id, ref first_name, ref last_name, ref username } => {
-                e.emit_struct("Chat", 5, |e| {
-                    try!(e.emit_struct_field("id", 0, |e| {
-                        id.encode(e)
-                    }));
-                    try!(e.emit_struct_field("type", 1, |e| {
-                        "private".encode(e)
-                    }));
-                    try!(e.emit_struct_field("first_name", 2, |e| {
-                        first_name.encode(e)
-                    }));
-                    try!(e.emit_struct_field("last_name", 3, |e| {
-                        last_name.encode(e)
-                    }));
-                    try!(e.emit_struct_field("type", 4, |e| {
-                        username.encode(e)
-                    }));
-                    Ok(())
-                })
-            },
-            &Chat::Group { id, ref title, is_supergroup} => {
-                e.emit_struct("Chat", 3, |e| {
-                    try!(e.emit_struct_field("id", 0, |e| {
-                        id.encode(e)
-                    }));
-                    try!(e.emit_struct_field("type", 1, |e| {
-                        let typ = if is_supergroup { "supergroup" } else { "group" };
-                        typ.encode(e)
-                    }));
-                    try!(e.emit_struct_field("title", 2, |e| {
-                        title.encode(e)
-                    }));
-                    Ok(())
-                })
-            },
-            &Chat::Channel { id, ref title, ref name} => {
-                e.emit_struct("Channel", 3, |e| {
-                    try!(e.emit_struct_field("id", 0, |e| {
-                        id.encode(e)
-                    }));
-                    try!(e.emit_struct_field("title", 1, |e| {
-                        title.encode(e)
-                    }));
-                    try!(e.emit_struct_field("username", 2, |e| {
-                        name.encode(e)
-                    }));
-                    Ok(())
-                })
-            },
+impl From<ChatId> for ChatRef {
+    fn from(id: ChatId) -> ChatRef {
+        ChatRef::Id(id)
+    }
+}
+
+impl From<Integer> for ChatRef {
+    fn from(id: Integer) -> ChatRef {
+        ChatRef::Id(ChatId(id))
+    }
+}
+
+/// Refers to the private chat with the user.
+impl From<UserId> for ChatRef {
+    fn from(id: UserId) -> ChatRef {
+        ChatRef::Id(id.into())
+    }
+}
+
+impl<'a> From<&'a str> for ChatRef {
+    fn from(username: &'a str) -> ChatRef {
+        if username.starts_with('@') {
+            ChatRef::Username(username.into())
+        } else {
+            ChatRef::Username(format!("@{}", username))
+        }
+    }
+}
+
+impl From<String> for ChatRef {
+    fn from(username: String) -> ChatRef {
+        if username.starts_with('@') {
+            ChatRef::Username(username)
+        } else {
+            ChatRef::from(&*username)
+        }
+    }
+}
+
+impl<'a> From<&'a Chat> for ChatRef {
+    fn from(chat: &'a Chat) -> ChatRef {
+        ChatRef::Id(chat.id())
+    }
+}
+
+/// Refers to the chat the message was sent in.
+impl<'a> From<&'a Message> for ChatRef {
+    fn from(message: &'a Message) -> ChatRef {
+        ChatRef::Id(message.chat.id())
+    }
+}
+
+/// Refers to the private chat with the user.
+impl<'a> From<&'a User> for ChatRef {
+    fn from(user: &'a User) -> ChatRef {
+        ChatRef::Id(user.id.into())
+    }
+}
+
+impl fmt::Display for ChatRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ChatRef::Id(id) => id.fmt(f),
+            ChatRef::Username(ref name) => name.fmt(f),
         }
     }
 }
@@ -346,7 +537,7 @@ impl Encodable for Chat {
 // ---------------------------------------------------------------------------
 #[derive(Debug, PartialEq, Clone)]
 pub struct Message {
-    pub message_id: Integer,
+    pub message_id: MessageId,
     pub from: User,
     pub chat: Chat,
     pub date: Integer,
@@ -358,33 +549,105 @@ pub struct Message {
     pub msg: MessageType,
 
     pub caption: Option<String>,
+    /// Messages of an album (see `Listener::listen_grouped`) share this id.
+    pub media_group_id: Option<String>,
 }
 
 // We need to implement this on our own, because the field "msg" is not a real
-// JSON field.
-impl Decodable for Message {
-    fn decode<D: Decoder>(d: &mut D) -> Result<Self, D::Error> {
-        d.read_struct("Message", 0, |d| {
-            let maybe_forward_from = try!(d.read_struct_field("forward_from", 0, Decodable::decode));
-            let maybe_forward_date = try!(d.read_struct_field("forward_date", 0, Decodable::decode));
-            let maybe_forward = match (maybe_forward_from, maybe_forward_date) {
-                (Some(from), Some(date)) => Some((from, date)),
-                _ => None,
-            };
-            Ok(Message {
-                message_id: try_field!(d, "message_id"),
-                from: try_field!(d, "from"),
-                chat: try_field!(d, "chat"),
-                date: try_field!(d, "date"),
-                forward: maybe_forward,
-                reply: try_field!(d, "reply_to_message"),
-                msg: try!(MessageType::decode(d)),
-                caption: try_field!(d, "caption"),
-            })
+// JSON field and "forward" consists of two fields.
+#[derive(Serialize)]
+struct MessageRef<'a> {
+    message_id: MessageId,
+    from: &'a User,
+    chat: &'a Chat,
+    date: Integer,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    forward_from: Option<&'a User>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    forward_date: Option<Integer>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reply_to_message: Option<&'a Message>,
+    #[serde(flatten)]
+    msg: &'a MessageType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    caption: Option<&'a String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    media_group_id: Option<&'a String>,
+}
+
+#[derive(Deserialize)]
+struct RawMessage {
+    message_id: MessageId,
+    from: User,
+    chat: Chat,
+    date: Integer,
+    forward_from: Option<User>,
+    forward_date: Option<Integer>,
+    reply_to_message: Option<Box<Message>>,
+    #[serde(flatten)]
+    msg: MessageType,
+    caption: Option<String>,
+    media_group_id: Option<String>,
+}
+
+impl Serialize for Message {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        MessageRef {
+            message_id: self.message_id,
+            from: &self.from,
+            chat: &self.chat,
+            date: self.date,
+            forward_from: self.forward.as_ref().map(|f| &f.0),
+            forward_date: self.forward.as_ref().map(|f| f.1),
+            reply_to_message: self.reply.as_ref().map(|r| &**r),
+            msg: &self.msg,
+            caption: self.caption.as_ref(),
+            media_group_id: self.media_group_id.as_ref(),
+        }.serialize(s)
+    }
+}
+
+impl<'de> Deserialize<'de> for Message {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let raw = try!(RawMessage::deserialize(d));
+        let forward = match (raw.forward_from, raw.forward_date) {
+            (Some(from), Some(date)) => Some((from, date)),
+            _ => None,
+        };
+        Ok(Message {
+            message_id: raw.message_id,
+            from: raw.from,
+            chat: raw.chat,
+            date: raw.date,
+            forward: forward,
+            reply: raw.reply_to_message,
+            msg: raw.msg,
+            caption: raw.caption,
+            media_group_id: raw.media_group_id,
         })
     }
 }
 
+/// The content of a message, which is determined by the fields present in the
+/// JSON object.
+///
+/// Telegram sends some fields together (e.g. a GIF has an "animation" and a
+/// "document" for older clients), so they are checked in this order and the
+/// first one present wins:
+///
+/// 1. Media: "animation" (before "document"), "audio", "document",
+///    "paid_media", "photo", "sticker", "story", "video", "video_note",
+///    "voice"
+/// 2. "text"
+/// 3. Other content: "contact", "dice", "game", "poll", "venue" (before
+///    "location"), "location", "invoice", "successful_payment",
+///    "refunded_payment"
+/// 4. Service messages: "pinned_message", "new_chat_members" (before
+///    "new_chat_participant", which only contains the first new member),
+///    "left_chat_participant", "new_chat_title", "new_chat_photo" and the
+///    remaining service fields
+///
+/// A message with none of these fields is `Unknown`.
 #[derive(Debug, PartialEq, Clone)]
 pub enum MessageType {
     Text(String),
@@ -392,11 +655,28 @@ pub enum MessageType {
     Voice(Voice),
     File(Document),
     Photo(Vec<PhotoSize>),
+    /// Media which can only be seen after paying for it.
+    PaidMedia(PaidMediaInfo),
     Sticker(Sticker),
     Video(Video),
     Contact(Contact),
     Location(Location),
+    Venue(Venue),
+    Animation(Animation),
+    VideoNote(VideoNote),
+    Dice(Dice),
+    Poll(Poll),
+    Game(Game),
+    Invoice(Invoice),
+    SuccessfulPayment(SuccessfulPayment),
+    RefundedPayment(RefundedPayment),
+    /// A forwarded story.
+    Story(Story),
+    /// The message was pinned. Contains the pinned message, whose
+    /// "reply_to_message" field is never set.
+    PinnedMessage(Box<Message>),
     NewChatParticipant(User),
+    NewChatMembers(Vec<User>),
     LeftChatParticipant(User),
     NewChatTitle(String),
     NewChatPhoto(Vec<PhotoSize>),
@@ -404,83 +684,304 @@ pub enum MessageType {
     GroupChatCreated,
     SuperGroupChatCreated(GroupToSuperGroupMigration),
     ChannelChatCreated,
+    /// The group was migrated to the supergroup with the given id.
+    MigrateToChatId(ChatId),
+    /// The supergroup was migrated from the group with the given id.
+    MigrateFromChatId(ChatId),
+    MessageAutoDeleteTimerChanged(MessageAutoDeleteTimerChanged),
+    /// Data sent by a Web App.
+    WebAppData(WebAppData),
+    /// The user allowed the bot to write messages after logging in on this
+    /// domain.
+    ConnectedWebsite(String),
+    ProximityAlertTriggered(ProximityAlertTriggered),
+    ForumTopicCreated(ForumTopicCreated),
+    ForumTopicEdited(ForumTopicEdited),
+    ForumTopicClosed,
+    ForumTopicReopened,
+    GeneralForumTopicHidden,
+    GeneralForumTopicUnhidden,
+    VideoChatScheduled(VideoChatScheduled),
+    VideoChatStarted,
+    VideoChatEnded(VideoChatEnded),
     Unknown,
 }
 
-impl Decodable for MessageType {
-    fn decode<D: Decoder>(d: &mut D) -> Result<Self, D::Error> {
-        // Tries to decode a field with the given name. If the field does NOT
-        // exist: Does nothing. If the field does exist: Return the decoded
-        // value. If any other decoder error occured: Return the error.
+// Service messages which don't carry any data are sent as empty objects.
+#[derive(Serialize, Deserialize)]
+struct Empty {}
+
+// All fields which may determine the type of a message. Used to deserialize
+// the flattened `MessageType`.
+#[derive(Deserialize)]
+struct RawMessageType {
+    text: Option<String>,
+    audio: Option<Audio>,
+    voice: Option<Voice>,
+    paid_media: Option<PaidMediaInfo>,
+    photo: Option<Vec<PhotoSize>>,
+    animation: Option<Animation>,
+    document: Option<Document>,
+    sticker: Option<Sticker>,
+    video: Option<Video>,
+    video_note: Option<VideoNote>,
+    contact: Option<Contact>,
+    venue: Option<Venue>,
+    location: Option<Location>,
+    dice: Option<Dice>,
+    poll: Option<Poll>,
+    game: Option<Game>,
+    invoice: Option<Invoice>,
+    successful_payment: Option<SuccessfulPayment>,
+    refunded_payment: Option<RefundedPayment>,
+    story: Option<Story>,
+    pinned_message: Option<Box<Message>>,
+    new_chat_members: Option<Vec<User>>,
+    new_chat_participant: Option<User>,
+    left_chat_participant: Option<User>,
+    new_chat_title: Option<String>,
+    new_chat_photo: Option<Vec<PhotoSize>>,
+    message_auto_delete_timer_changed: Option<MessageAutoDeleteTimerChanged>,
+    web_app_data: Option<WebAppData>,
+    connected_website: Option<String>,
+    proximity_alert_triggered: Option<ProximityAlertTriggered>,
+    forum_topic_created: Option<ForumTopicCreated>,
+    forum_topic_edited: Option<ForumTopicEdited>,
+    forum_topic_closed: Option<Empty>,
+    forum_topic_reopened: Option<Empty>,
+    general_forum_topic_hidden: Option<Empty>,
+    general_forum_topic_unhidden: Option<Empty>,
+    video_chat_scheduled: Option<VideoChatScheduled>,
+    video_chat_started: Option<Empty>,
+    video_chat_ended: Option<VideoChatEnded>,
+    delete_chat_photo: Option<bool>,
+    group_chat_created: Option<bool>,
+    supergroup_chat_created: Option<bool>,
+    migrate_from_chat_id: Option<ChatId>,
+    migrate_to_chat_id: Option<ChatId>,
+    channel_chat_created: Option<bool>,
+}
+
+impl<'de> Deserialize<'de> for MessageType {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let raw = try!(RawMessageType::deserialize(d));
+
+        // Returns the variant if the field is present.
         macro_rules! maybe_field {
-            ($d:ident, $name:expr, $variant:ident) => {{
-                if let Some(val) = try!($d.read_struct_field(
-                    $name, 0, Decodable::decode)) {
+            ($field:ident, $variant:ident) => {{
+                if let Some(val) = raw.$field {
                     return Ok(MessageType::$variant(val));
-                };
+                }
+            }}
+        }
+
+        // Returns the variant without data if the field is present.
+        macro_rules! maybe_empty {
+            ($field:ident, $variant:ident) => {{
+                if raw.$field.is_some() {
+                    return Ok(MessageType::$variant);
+                }
             }}
         }
 
-        // There is always just one of these fields used, so we can infer the
-        // enum variant from it.
-        // These are the message types that carry additional data
-        maybe_field!(d, "text", Text);
-        maybe_field!(d, "audio", Audio);
-        maybe_field!(d, "voice", Voice);
-        maybe_field!(d, "file", File);
-        maybe_field!(d, "photo", Photo);
-        maybe_field!(d, "document", File);
-        maybe_field!(d, "sticker", Sticker);
-        maybe_field!(d, "video", Video);
-        maybe_field!(d, "contact", Contact);
-        maybe_field!(d, "location", Location);
-        maybe_field!(d, "new_chat_participant", NewChatParticipant);
-        maybe_field!(d, "left_chat_participant", LeftChatParticipant);
-        maybe_field!(d, "new_chat_title", NewChatTitle);
-        maybe_field!(d, "new_chat_photo", NewChatPhoto);
+        // See the documentation of `MessageType` for the order.
+        // Media
+        maybe_field!(animation, Animation);
+        maybe_field!(audio, Audio);
+        maybe_field!(document, File);
+        maybe_field!(paid_media, PaidMedia);
+        maybe_field!(photo, Photo);
+        maybe_field!(sticker, Sticker);
+        maybe_field!(story, Story);
+        maybe_field!(video, Video);
+        maybe_field!(video_note, VideoNote);
+        maybe_field!(voice, Voice);
+
+        maybe_field!(text, Text);
+
+        // Other content
+        maybe_field!(contact, Contact);
+        maybe_field!(dice, Dice);
+        maybe_field!(game, Game);
+        maybe_field!(poll, Poll);
+        maybe_field!(venue, Venue);
+        maybe_field!(location, Location);
+        maybe_field!(invoice, Invoice);
+        maybe_field!(successful_payment, SuccessfulPayment);
+        maybe_field!(refunded_payment, RefundedPayment);
+
+        // Service messages
+        maybe_field!(pinned_message, PinnedMessage);
+        maybe_field!(new_chat_members, NewChatMembers);
+        maybe_field!(new_chat_participant, NewChatParticipant);
+        maybe_field!(left_chat_participant, LeftChatParticipant);
+        maybe_field!(new_chat_title, NewChatTitle);
+        maybe_field!(new_chat_photo, NewChatPhoto);
+        maybe_field!(message_auto_delete_timer_changed, MessageAutoDeleteTimerChanged);
+        maybe_field!(web_app_data, WebAppData);
+        maybe_field!(connected_website, ConnectedWebsite);
+        maybe_field!(proximity_alert_triggered, ProximityAlertTriggered);
+        maybe_field!(forum_topic_created, ForumTopicCreated);
+        maybe_field!(forum_topic_edited, ForumTopicEdited);
+        maybe_empty!(forum_topic_closed, ForumTopicClosed);
+        maybe_empty!(forum_topic_reopened, ForumTopicReopened);
+        maybe_empty!(general_forum_topic_hidden, GeneralForumTopicHidden);
+        maybe_empty!(general_forum_topic_unhidden, GeneralForumTopicUnhidden);
+        maybe_field!(video_chat_scheduled, VideoChatScheduled);
+        maybe_empty!(video_chat_started, VideoChatStarted);
+        maybe_field!(video_chat_ended, VideoChatEnded);
 
         // Message types without additional data
-        if let Some(true) = try!(d.read_struct_field(
-            "delete_chat_photo", 0, Decodable::decode)) {
+        if let Some(true) = raw.delete_chat_photo {
             return Ok(MessageType::DeleteChatPhoto);
-        };
-        if let Some(true) = try!(d.read_struct_field(
-            "group_chat_created", 0, Decodable::decode)) {
+        }
+        if let Some(true) = raw.group_chat_created {
             return Ok(MessageType::GroupChatCreated);
-        };
+        }
 
-        if let Some(true) = try!(d.read_struct_field(
-            "supergroup_chat_created", 0, Decodable::decode)) {
-            return Ok(MessageType::SuperGroupChatCreated(GroupToSuperGroupMigration {
-                from: try_field!(d, "migrate_from_chat_id"),
-                to: try_field!(d, "migrate_to_chat_id"),
-            }))
-        };
+        if let Some(true) = raw.supergroup_chat_created {
+            return match (raw.migrate_from_chat_id, raw.migrate_to_chat_id) {
+                (Some(from), Some(to)) => {
+                    Ok(MessageType::SuperGroupChatCreated(GroupToSuperGroupMigration {
+                        from: from,
+                        to: to,
+                    }))
+                },
+                (None, _) => Err(de::Error::missing_field("migrate_from_chat_id")),
+                (_, None) => Err(de::Error::missing_field("migrate_to_chat_id")),
+            };
+        }
+        maybe_field!(migrate_to_chat_id, MigrateToChatId);
+        maybe_field!(migrate_from_chat_id, MigrateFromChatId);
 
-        if let Some(true) = try!(d.read_struct_field(
-            "channel_chat_created", 0, Decodable::decode)) {
+        if let Some(true) = raw.channel_chat_created {
             return Ok(MessageType::ChannelChatCreated);
-        };
+        }
 
         // None of the tested fields is present.
         Ok(MessageType::Unknown)
     }
 }
 
+// Serializes the fields of the message which determine its type. `Unknown`
+// results in no fields at all.
+impl Serialize for MessageType {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        let len = match *self {
+            MessageType::SuperGroupChatCreated(_) => 3,
+            MessageType::Unknown => 0,
+            _ => 1,
+        };
+        let mut s = try!(s.serialize_struct("MessageType", len));
+        match *self {
+            MessageType::Text(ref v) => try!(s.serialize_field("text", v)),
+            MessageType::Audio(ref v) => try!(s.serialize_field("audio", v)),
+            MessageType::Voice(ref v) => try!(s.serialize_field("voice", v)),
+            MessageType::File(ref v) => try!(s.serialize_field("document", v)),
+            MessageType::Photo(ref v) => try!(s.serialize_field("photo", v)),
+            MessageType::PaidMedia(ref v) => try!(s.serialize_field("paid_media", v)),
+            MessageType::Sticker(ref v) => try!(s.serialize_field("sticker", v)),
+            MessageType::Video(ref v) => try!(s.serialize_field("video", v)),
+            MessageType::Contact(ref v) => try!(s.serialize_field("contact", v)),
+            MessageType::Location(ref v) => try!(s.serialize_field("location", v)),
+            MessageType::Venue(ref v) => try!(s.serialize_field("venue", v)),
+            MessageType::Animation(ref v) => try!(s.serialize_field("animation", v)),
+            MessageType::VideoNote(ref v) => try!(s.serialize_field("video_note", v)),
+            MessageType::Dice(ref v) => try!(s.serialize_field("dice", v)),
+            MessageType::Poll(ref v) => try!(s.serialize_field("poll", v)),
+            MessageType::Game(ref v) => try!(s.serialize_field("game", v)),
+            MessageType::Invoice(ref v) => try!(s.serialize_field("invoice", v)),
+            MessageType::SuccessfulPayment(ref v) => {
+                try!(s.serialize_field("successful_payment", v))
+            },
+            MessageType::RefundedPayment(ref v) => {
+                try!(s.serialize_field("refunded_payment", v))
+            },
+            MessageType::Story(ref v) => try!(s.serialize_field("story", v)),
+            MessageType::PinnedMessage(ref v) => try!(s.serialize_field("pinned_message", v)),
+            MessageType::NewChatParticipant(ref v) => {
+                try!(s.serialize_field("new_chat_participant", v))
+            },
+            MessageType::NewChatMembers(ref v) => {
+                try!(s.serialize_field("new_chat_members", v))
+            },
+            MessageType::LeftChatParticipant(ref v) => {
+                try!(s.serialize_field("left_chat_participant", v))
+            },
+            MessageType::NewChatTitle(ref v) => try!(s.serialize_field("new_chat_title", v)),
+            MessageType::NewChatPhoto(ref v) => try!(s.serialize_field("new_chat_photo", v)),
+            MessageType::DeleteChatPhoto => try!(s.serialize_field("delete_chat_photo", &true)),
+            MessageType::GroupChatCreated => try!(s.serialize_field("group_chat_created", &true)),
+            MessageType::SuperGroupChatCreated(ref m) => {
+                try!(s.serialize_field("supergroup_chat_created", &true));
+                try!(s.serialize_field("migrate_from_chat_id", &m.from));
+                try!(s.serialize_field("migrate_to_chat_id", &m.to));
+            },
+            MessageType::ChannelChatCreated => {
+                try!(s.serialize_field("channel_chat_created", &true))
+            },
+            MessageType::MigrateToChatId(ref v) => {
+                try!(s.serialize_field("migrate_to_chat_id", v))
+            },
+            MessageType::MigrateFromChatId(ref v) => {
+                try!(s.serialize_field("migrate_from_chat_id", v))
+            },
+            MessageType::MessageAutoDeleteTimerChanged(ref v) => {
+                try!(s.serialize_field("message_auto_delete_timer_changed", v))
+            },
+            MessageType::WebAppData(ref v) => try!(s.serialize_field("web_app_data", v)),
+            MessageType::ConnectedWebsite(ref v) => {
+                try!(s.serialize_field("connected_website", v))
+            },
+            MessageType::ProximityAlertTriggered(ref v) => {
+                try!(s.serialize_field("proximity_alert_triggered", v))
+            },
+            MessageType::ForumTopicCreated(ref v) => {
+                try!(s.serialize_field("forum_topic_created", v))
+            },
+            MessageType::ForumTopicEdited(ref v) => {
+                try!(s.serialize_field("forum_topic_edited", v))
+            },
+            MessageType::ForumTopicClosed => {
+                try!(s.serialize_field("forum_topic_closed", &Empty {}))
+            },
+            MessageType::ForumTopicReopened => {
+                try!(s.serialize_field("forum_topic_reopened", &Empty {}))
+            },
+            MessageType::GeneralForumTopicHidden => {
+                try!(s.serialize_field("general_forum_topic_hidden", &Empty {}))
+            },
+            MessageType::GeneralForumTopicUnhidden => {
+                try!(s.serialize_field("general_forum_topic_unhidden", &Empty {}))
+            },
+            MessageType::VideoChatScheduled(ref v) => {
+                try!(s.serialize_field("video_chat_scheduled", v))
+            },
+            MessageType::VideoChatStarted => {
+                try!(s.serialize_field("video_chat_started", &Empty {}))
+            },
+            MessageType::VideoChatEnded(ref v) => try!(s.serialize_field("video_chat_ended", v)),
+            MessageType::Unknown => {},
+        }
+        s.end()
+    }
+}
+
 // ---------------------------------------------------------------------------
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
 pub struct GroupToSuperGroupMigration {
-    pub from: Integer,
-    pub to: Integer,
+    pub from: ChatId,
+    pub to: ChatId,
 }
 
 // ---------------------------------------------------------------------------
 /// Strongly typed ParseMode. Instead of passing a String to the
 /// `send_message` method, this is used.
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
 pub enum ParseMode {
     Markdown,
+    #[serde(rename = "HTML")]
     Html,
 }
 
@@ -493,164 +994,723 @@ impl fmt::Display for ParseMode {
     }
 }
 
+// ---------------------------------------------------------------------------
+/// Telegram type "MessageEntity": A special part of a text, e.g. a link or
+/// bold text. Offset and length are measured in UTF-16 code units.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct MessageEntity {
+    #[serde(rename = "type")]
+    pub kind: MessageEntityKind,
+    pub offset: Integer,
+    pub length: Integer,
+    /// Only for `TextLink`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Only for `TextMention`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<User>,
+    /// Only for `Pre`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// Only for `CustomEmoji`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub custom_emoji_id: Option<String>,
+}
+
+impl MessageEntity {
+    /// An entity of the given kind without any additional data.
+    pub fn new(kind: MessageEntityKind, offset: Integer, length: Integer) -> MessageEntity {
+        MessageEntity {
+            kind: kind,
+            offset: offset,
+            length: length,
+            url: None,
+            user: None,
+            language: None,
+            custom_emoji_id: None,
+        }
+    }
+}
+
+/// Used for the "type" field of "MessageEntity".
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageEntityKind {
+    Mention,
+    Hashtag,
+    Cashtag,
+    BotCommand,
+    Url,
+    Email,
+    PhoneNumber,
+    Bold,
+    Italic,
+    Underline,
+    Strikethrough,
+    Spoiler,
+    Blockquote,
+    ExpandableBlockquote,
+    Code,
+    Pre,
+    TextLink,
+    TextMention,
+    CustomEmoji,
+    /// A kind this library doesn't know yet. Can't be sent.
+    #[serde(other)]
+    Unknown,
+}
+
 // ===========================================================================
 // Telegram types directly mapped to Rust types
 // ===========================================================================
 /// Telegram type "User" (directly mapped)
-#[derive(RustcDecodable, Debug, PartialEq, Clone)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct User {
-    pub id: Integer,
+    pub id: UserId,
     pub first_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub last_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub username: Option<String>,
 }
 
-impl_encode!(User, 4,
-    [0 => id, 1 => first_name],
-    [2 => last_name, 3 => username]);
-
 // ---------------------------------------------------------------------------
 /// Telegram type "PhotoSize" (directly mapped)
-#[derive(RustcDecodable, Debug, PartialEq, Clone)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct PhotoSize {
-    pub file_id: String,
+    pub file_id: FileId,
     pub width: Integer,
     pub height: Integer,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub file_size: Option<Integer>,
 }
 
-impl_encode!(PhotoSize, 4,
-    [0 => file_id, 1 => width, 2 => height],
-    [3 => file_size]);
-
 // ---------------------------------------------------------------------------
 /// Telegram type "Audio" (directly mapped)
-#[derive(RustcDecodable, Debug, PartialEq, Clone)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct Audio {
-    pub file_id: String,
+    pub file_id: FileId,
     pub duration: Integer,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub performer: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub mime_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub file_size: Option<Integer>,
 }
 
-impl_encode!(Audio, 6,
-             [0 => file_id, 1 => duration],
-             [2 => performer, 3 => title,
-              4 => mime_type, 5 => file_size]);
-
 // ---------------------------------------------------------------------------
 /// Telegram type "Voice" (directly mapped)
-#[derive(RustcDecodable, Debug, PartialEq, Clone)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct Voice {
-    pub file_id: String,
+    pub file_id: FileId,
     pub duration: Integer,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub mime_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub file_size: Option<Integer>,
 }
 
-impl_encode!(Voice, 4,
-             [0 => file_id, 1 => duration],
-             [2 => mime_type, 3 => file_size]);
-
 
 // ---------------------------------------------------------------------------
 /// Telegram type "Document" (directly mapped)
-#[derive(RustcDecodable, Debug, PartialEq, Clone)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct Document {
-    pub file_id: String,
+    pub file_id: FileId,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "thumbnail")]
     pub thumb: Option<PhotoSize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub file_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub mime_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub file_size: Option<Integer>,
 }
 
-impl_encode!(Document, 5,
-    [0 => file_id, 1 => thumb],
-    [2 => file_name, 3 => mime_type, 4 => file_size]);
-
 // ---------------------------------------------------------------------------
 /// Telegram type "Sticker" (directly mapped)
-#[derive(RustcDecodable, Debug, PartialEq, Clone)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct Sticker {
-    pub file_id: String,
+    pub file_id: FileId,
     pub width: Integer,
     pub height: Integer,
+    #[serde(rename = "type", default)]
+    pub kind: StickerKind,
+    #[serde(default)]
+    pub is_animated: bool,
+    #[serde(default)]
+    pub is_video: bool,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "thumbnail")]
     pub thumb: Option<PhotoSize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub emoji: Option<String>,
+    /// Name of the sticker set the sticker belongs to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub set_name: Option<String>,
+    /// Only for masks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mask_position: Option<MaskPosition>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub file_size: Option<Integer>,
 }
 
-impl_encode!(Sticker, 5,
-    [0 => file_id, 1 => width, 2 => height, 3 => thumb],
-    [4 => file_size]);
+/// Used for the "type" field of "Sticker" and the "sticker_type" field of
+/// "StickerSet".
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum StickerKind {
+    Regular,
+    Mask,
+    CustomEmoji,
+}
+
+impl Default for StickerKind {
+    fn default() -> StickerKind {
+        StickerKind::Regular
+    }
+}
+
+impl fmt::Display for StickerKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", match *self {
+            StickerKind::Regular => "regular",
+            StickerKind::Mask => "mask",
+            StickerKind::CustomEmoji => "custom_emoji",
+        })
+    }
+}
+
+/// Format of a sticker file: A WEBP or PNG image, a TGS animation or a WEBM
+/// video.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum StickerFormat {
+    Static,
+    Animated,
+    Video,
+}
+
+impl fmt::Display for StickerFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", match *self {
+            StickerFormat::Static => "static",
+            StickerFormat::Animated => "animated",
+            StickerFormat::Video => "video",
+        })
+    }
+}
+
+/// Telegram type "MaskPosition" (directly mapped)
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct MaskPosition {
+    pub point: MaskPoint,
+    /// Shift to the right, in widths of the mask.
+    pub x_shift: Float,
+    /// Shift downwards, in heights of the mask.
+    pub y_shift: Float,
+    pub scale: Float,
+}
+
+/// The part of the face a mask is placed relative to.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum MaskPoint {
+    Forehead,
+    Eyes,
+    Mouth,
+    Chin,
+}
+
+// ---------------------------------------------------------------------------
+/// Telegram type "StickerSet" (directly mapped)
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct StickerSet {
+    pub name: String,
+    pub title: String,
+    pub sticker_type: StickerKind,
+    pub stickers: Vec<Sticker>,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "thumb")]
+    pub thumbnail: Option<PhotoSize>,
+}
+
+// ---------------------------------------------------------------------------
+/// Telegram type "File" (directly mapped): A file which can be downloaded.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct File {
+    pub file_id: FileId,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_size: Option<Integer>,
+    /// Path to download the file, valid for at least one hour.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_path: Option<String>,
+}
 
 // ---------------------------------------------------------------------------
 /// Telegram type "Video" (directly mapped)
-#[derive(RustcDecodable, Debug, PartialEq, Clone)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct Video {
-    pub file_id: String,
+    pub file_id: FileId,
     pub width: Integer,
     pub height: Integer,
     pub duration: Integer,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "thumbnail")]
     pub thumb: Option<PhotoSize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub mime_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub file_size: Option<Integer>,
 }
 
-impl_encode!(Video, 7,
-    [0 => file_id, 1 => width, 2 => height, 3 => duration, 4 => thumb],
-    [5 => mime_type, 6 => file_size]);
-
 // ---------------------------------------------------------------------------
 /// Telegram type "Contact" (directly mapped)
-#[derive(RustcDecodable, Debug, PartialEq, Clone)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct Contact {
     pub phone_number: String,
     pub first_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub last_name: Option<String>,
-    pub user_id: Option<Integer>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<UserId>,
 }
 
-impl_encode!(Contact, 4,
-    [0 => phone_number, 1 => first_name],
-    [2 => last_name, 3 => user_id]);
-
 // ---------------------------------------------------------------------------
 /// Telegram type "Location" (directly mapped)
-#[derive(RustcDecodable, RustcEncodable, Debug, PartialEq, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
 pub struct Location {
     pub longitude: Float,
     pub latitude: Float,
 }
 
+// ---------------------------------------------------------------------------
+/// Telegram type "Venue" (directly mapped)
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Venue {
+    pub location: Location,
+    pub title: String,
+    pub address: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub foursquare_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub foursquare_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub google_place_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub google_place_type: Option<String>,
+}
+
+// ---------------------------------------------------------------------------
+/// Telegram type "Animation" (directly mapped)
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Animation {
+    pub file_id: FileId,
+    pub width: Integer,
+    pub height: Integer,
+    pub duration: Integer,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "thumbnail")]
+    pub thumb: Option<PhotoSize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_size: Option<Integer>,
+}
+
+// ---------------------------------------------------------------------------
+/// Telegram type "VideoNote" (directly mapped)
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct VideoNote {
+    pub file_id: FileId,
+    /// Width and height of the video.
+    pub length: Integer,
+    pub duration: Integer,
+    #[serde(skip_serializing_if = "Option::is_none", alias = "thumbnail")]
+    pub thumb: Option<PhotoSize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_size: Option<Integer>,
+}
+
+// ---------------------------------------------------------------------------
+/// Telegram type "PaidMediaInfo" (directly mapped)
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct PaidMediaInfo {
+    /// Number of Telegram Stars to pay to see the media.
+    pub star_count: Integer,
+    pub paid_media: Vec<PaidMedia>,
+}
+
+/// Telegram type "PaidMedia": Either a preview of media which wasn't paid for
+/// yet, or the media itself.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum PaidMedia {
+    Preview {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        width: Option<Integer>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        height: Option<Integer>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        duration: Option<Integer>,
+    },
+    Photo {
+        photo: Vec<PhotoSize>,
+    },
+    Video {
+        video: Video,
+    },
+}
+
+// ---------------------------------------------------------------------------
+/// Telegram type "Dice" (directly mapped)
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Dice {
+    /// The emoji the dice animation is based on, e.g. "🎲".
+    pub emoji: String,
+    pub value: Integer,
+}
+
+// ---------------------------------------------------------------------------
+/// Telegram type "Poll" (directly mapped)
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Poll {
+    pub id: String,
+    pub question: String,
+    pub options: Vec<PollOption>,
+    pub total_voter_count: Integer,
+    pub is_closed: bool,
+    pub is_anonymous: bool,
+    #[serde(rename = "type")]
+    pub kind: PollKind,
+    pub allows_multiple_answers: bool,
+    /// Only set for quizzes sent (or stopped) by the bot.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub correct_option_id: Option<Integer>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub explanation: Option<String>,
+    /// Formatting of the explanation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub explanation_entities: Option<Vec<MessageEntity>>,
+    /// Seconds the poll is active after its creation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub open_period: Option<Integer>,
+    /// Unix time when the poll is closed automatically.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub close_date: Option<Integer>,
+}
+
+/// Either a regular poll or a quiz. Used for the "type" field of "Poll".
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum PollKind {
+    Regular,
+    Quiz,
+}
+
+impl fmt::Display for PollKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", match *self {
+            PollKind::Regular => "regular",
+            PollKind::Quiz => "quiz",
+        })
+    }
+}
+
+/// Telegram type "PollOption" (directly mapped)
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct PollOption {
+    pub text: String,
+    pub voter_count: Integer,
+}
+
+/// Telegram type "PollAnswer" (directly mapped). Only sent for non-anonymous
+/// polls which were sent by the bot.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct PollAnswer {
+    pub poll_id: String,
+    /// The user who voted. Not set if the vote is from an anonymous group
+    /// administrator, see `voter_chat`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<User>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub voter_chat: Option<Chat>,
+    /// The chosen options (starting with 0). Empty if the vote was retracted.
+    pub option_ids: Vec<Integer>,
+}
+
+// ---------------------------------------------------------------------------
+/// Telegram type "Game" (directly mapped)
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Game {
+    pub title: String,
+    pub description: String,
+    pub photo: Vec<PhotoSize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub animation: Option<Animation>,
+}
+
+// ---------------------------------------------------------------------------
+/// Telegram type "Invoice" (directly mapped)
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Invoice {
+    pub title: String,
+    pub description: String,
+    pub start_parameter: String,
+    /// Three-letter ISO 4217 currency code or `STARS` for Telegram Stars.
+    pub currency: String,
+    /// Price in the smallest units of the currency (e.g. cents).
+    pub total_amount: Integer,
+}
+
+// ---------------------------------------------------------------------------
+/// Telegram type "SuccessfulPayment" (directly mapped)
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct SuccessfulPayment {
+    pub currency: String,
+    pub total_amount: Integer,
+    pub invoice_payload: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shipping_option_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order_info: Option<OrderInfo>,
+    /// Needed to refund a payment in Telegram Stars.
+    pub telegram_payment_charge_id: String,
+    pub provider_payment_charge_id: String,
+}
+
+/// Telegram type "RefundedPayment" (directly mapped)
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct RefundedPayment {
+    pub currency: String,
+    pub total_amount: Integer,
+    pub invoice_payload: String,
+    pub telegram_payment_charge_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider_payment_charge_id: Option<String>,
+}
+
+/// Currency code of Telegram Stars, which have to be used for digital goods.
+/// Invoices in Stars have a single price and no provider token.
+pub const STARS: &'static str = "XTR";
+
+/// Telegram type "LabeledPrice" (directly mapped)
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct LabeledPrice {
+    pub label: String,
+    /// Price in the smallest units of the currency (e.g. cents), negative
+    /// for discounts.
+    pub amount: Integer,
+}
+
+impl LabeledPrice {
+    pub fn new<L: Into<String>>(label: L, amount: Integer) -> LabeledPrice {
+        LabeledPrice {
+            label: label.into(),
+            amount: amount,
+        }
+    }
+}
+
+/// Telegram type "ShippingAddress" (directly mapped)
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct ShippingAddress {
+    /// Two-letter ISO 3166-1 alpha-2 country code.
+    pub country_code: String,
+    pub state: String,
+    pub city: String,
+    pub street_line1: String,
+    pub street_line2: String,
+    pub post_code: String,
+}
+
+/// Telegram type "OrderInfo" (directly mapped)
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct OrderInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub phone_number: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shipping_address: Option<ShippingAddress>,
+}
+
+/// Telegram type "ShippingOption" (directly mapped)
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct ShippingOption {
+    pub id: String,
+    pub title: String,
+    pub prices: Vec<LabeledPrice>,
+}
+
+/// Telegram type "ShippingQuery" (directly mapped)
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct ShippingQuery {
+    pub id: String,
+    pub from: User,
+    pub invoice_payload: String,
+    pub shipping_address: ShippingAddress,
+}
+
+/// Telegram type "PreCheckoutQuery" (directly mapped)
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct PreCheckoutQuery {
+    pub id: String,
+    pub from: User,
+    pub currency: String,
+    pub total_amount: Integer,
+    pub invoice_payload: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shipping_option_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order_info: Option<OrderInfo>,
+}
+
+// ---------------------------------------------------------------------------
+/// Telegram type "Story" (directly mapped)
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Story {
+    /// The chat which posted the story.
+    pub chat: Chat,
+    /// Identifier of the story in the chat.
+    pub id: Integer,
+}
+
+// ---------------------------------------------------------------------------
+/// Telegram type "MessageAutoDeleteTimerChanged" (directly mapped)
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub struct MessageAutoDeleteTimerChanged {
+    /// New auto-delete time for messages in the chat, in seconds.
+    pub message_auto_delete_time: Integer,
+}
+
+// ---------------------------------------------------------------------------
+/// Telegram type "WebAppData" (directly mapped)
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct WebAppData {
+    pub data: String,
+    /// Text of the button which opened the Web App.
+    pub button_text: String,
+}
+
+// ---------------------------------------------------------------------------
+/// Telegram type "ProximityAlertTriggered" (directly mapped)
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct ProximityAlertTriggered {
+    pub traveler: User,
+    pub watcher: User,
+    /// Distance between the users in meters.
+    pub distance: Integer,
+}
+
+// ---------------------------------------------------------------------------
+/// Telegram type "ForumTopicCreated" (directly mapped)
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct ForumTopicCreated {
+    pub name: String,
+    /// Color of the topic icon in RGB format.
+    pub icon_color: Integer,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon_custom_emoji_id: Option<String>,
+}
+
+/// Telegram type "ForumTopicEdited" (directly mapped)
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct ForumTopicEdited {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// An empty string if the icon was removed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon_custom_emoji_id: Option<String>,
+}
+
+// ---------------------------------------------------------------------------
+/// Telegram type "VideoChatScheduled" (directly mapped)
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub struct VideoChatScheduled {
+    /// Unix time when the video chat is supposed to be started.
+    pub start_date: Integer,
+}
+
+/// Telegram type "VideoChatEnded" (directly mapped)
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub struct VideoChatEnded {
+    /// Duration of the video chat in seconds.
+    pub duration: Integer,
+}
+
 // ---------------------------------------------------------------------------
 /// Telegram type "Update" (directly mapped)
-#[derive(RustcDecodable, Debug, PartialEq, Clone)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct Update {
-    pub update_id: Integer,
-    pub message: Option<Message>
+    pub update_id: UpdateId,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<Message>,
+    /// New state of a poll. Only sent for stopped polls and polls sent by
+    /// the bot.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub poll: Option<Poll>,
+    /// A changed vote in a non-anonymous poll sent by the bot.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub poll_answer: Option<PollAnswer>,
+    /// Only sent for invoices with a flexible price.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shipping_query: Option<ShippingQuery>,
+    /// Has to be answered within 10 seconds, see
+    /// `Api::answer_pre_checkout_query`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pre_checkout_query: Option<PreCheckoutQuery>,
 }
 
-// impl_encode!(Update, 2,
-//     [0 => update_id],
-//     [1 => message]);
-
 // ---------------------------------------------------------------------------
 /// Telegram type "UserProfilePhotos" (directly mapped)
-#[derive(RustcDecodable, RustcEncodable, Debug, PartialEq, Clone)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct UserProfilePhotos {
     pub total_count: Integer,
     pub photos: Vec<Vec<PhotoSize>>,
 }
 
+// ---------------------------------------------------------------------------
+/// Telegram type "WebhookInfo" (directly mapped)
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct WebhookInfo {
+    /// Empty if no webhook is set.
+    pub url: String,
+    pub has_custom_certificate: bool,
+    /// Number of updates waiting for delivery.
+    pub pending_update_count: Integer,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ip_address: Option<String>,
+    /// Unix time of the last error while delivering an update.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error_date: Option<Integer>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error_message: Option<String>,
+    /// Unix time of the last error while synchronizing with the Telegram
+    /// datacenters.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_synchronization_error_date: Option<Integer>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_connections: Option<Integer>,
+    /// Kept as strings, so kinds unknown to this library don't cause errors.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_updates: Option<Vec<String>>,
+}
+
 // ---------------------------------------------------------------------------
 /// Telegram type "ReplyKeyboardMarkup" (directly mapped)
-#[derive(RustcDecodable, Debug, PartialEq, Clone)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct ReplyKeyboardMarkup {
     pub keyboard: Vec<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub resize_keyboard: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub one_time_keyboard: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub selective: Option<bool>,
 }
 
@@ -665,10 +1725,6 @@ impl Default for ReplyKeyboardMarkup {
     }
 }
 
-impl_encode!(ReplyKeyboardMarkup, 4,
-    [0 => keyboard],
-    [1 => resize_keyboard, 2 => one_time_keyboard, 3 => selective]);
-
 // ===========================================================================
 // Unit tests (mainly encode & decode)
 // ===========================================================================
@@ -1,5 +1,29 @@
 #![allow(unused_imports)]
-use rustc_serialize::json;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json as json;
+use std::fmt::Debug;
+
+// Checks that the value is decoded from its encoding unchanged.
+#[cfg(test)]
+fn round_trip<T>(value: &T) where T: Serialize + DeserializeOwned + PartialEq + Debug {
+    let encoded = json::to_string(value).unwrap();
+    let decoded: T = json::from_str(&encoded).unwrap();
+    assert_eq!(&decoded, value, "encoded as {}", encoded);
+}
+
+// Decodes a message from a private chat with the given additional fields.
+#[cfg(test)]
+fn decode_message_type(fields: &str) -> ::MessageType {
+    let blob = format!(r#"{{
+        "message_id": 74,
+        "from": {{"first_name": "test", "id": 123456789}},
+        "date": 1437821492,
+        "chat": {{"first_name": "test", "id": 123456789, "type": "private"}},
+        {}
+    }}"#, fields);
+    json::from_str::<::Message>(&blob).unwrap().msg
+}
 
 #[test]
 fn reply_keyboard_markup() {
@@ -15,10 +39,10 @@ fn reply_keyboard_markup() {
 
     // Test encoding
     let x = RKM::default();
-    assert_eq!(json::encode(&x).unwrap(), r#"{"keyboard":[]}"#.to_string());
+    assert_eq!(json::to_string(&x).unwrap(), r#"{"keyboard":[]}"#.to_string());
 
     let x = RKM { resize_keyboard: Some(true), ..Default::default() };
-    assert_eq!(json::encode(&x).unwrap(),
+    assert_eq!(json::to_string(&x).unwrap(),
         r#"{"keyboard":[],"resize_keyboard":true}"#.to_string());
 
     let x = RKM {
@@ -26,7 +50,7 @@ fn reply_keyboard_markup() {
         resize_keyboard: Some(false),
         ..Default::default()
     };
-    assert_eq!(json::encode(&x).unwrap(),
+    assert_eq!(json::to_string(&x).unwrap(),
         r#"{"keyboard":[["ABC"],["X","Y"]],"resize_keyboard":false}"#.to_string());
 }
 
@@ -37,14 +61,14 @@ fn keyboard_markup() {
 
     // Test encoding
     let x = RM::Keyboard(RKM::default());
-    assert_eq!(json::encode(&x).unwrap(), r#"{"keyboard":[]}"#.to_string());
+    assert_eq!(json::to_string(&x).unwrap(), r#"{"keyboard":[]}"#.to_string());
 
     let x = RM::KeyboardHide(false);
-    assert_eq!(json::encode(&x).unwrap(),
+    assert_eq!(json::to_string(&x).unwrap(),
         r#"{"hide_keyboard":true,"selective":false}"#.to_string());
 
     let x = RM::ForceReply(true);
-    assert_eq!(json::encode(&x).unwrap(),
+    assert_eq!(json::to_string(&x).unwrap(),
         r#"{"force_reply":true,"selective":true}"#.to_string());
 }
 
@@ -53,7 +77,7 @@ fn decode_group_chat() {
     use Chat;
 
     let blob = r#"{"title":"This is a group chat","id":-12345678,"type":"group"}"#;
-    let chat: Chat = json::decode(&blob).unwrap();
+    let chat: Chat = json::from_str(&blob).unwrap();
     assert!(chat.is_group());
 }
 
@@ -62,7 +86,7 @@ fn decode_supergroup_chat() {
     use Chat;
 
     let blob = r#"{"title":"This is a group chat","id":-12345678,"type":"supergroup"}"#;
-    let chat: Chat = json::decode(&blob).unwrap();
+    let chat: Chat = json::from_str(&blob).unwrap();
     assert!(chat.is_supergroup());
 }
 
@@ -71,7 +95,7 @@ fn decode_channel_chat() {
     use Chat;
 
     let blob = r#"{"title":"This is a group chat","id":-12345678,"type":"channel"}"#;
-    let chat: Chat = json::decode(&blob).unwrap();
+    let chat: Chat = json::from_str(&blob).unwrap();
     assert!(chat.is_channel());
 }
 
@@ -80,7 +104,7 @@ fn decode_channel_with_username_chat() {
     use Chat;
 
     let blob = r#"{"title":"This is a group chat","id":-12345678,"type":"channel", "username": "foo"}"#;
-    let chat: Chat = json::decode(&blob).unwrap();
+    let chat: Chat = json::from_str(&blob).unwrap();
     assert!(chat.is_channel());
 }
 
@@ -90,8 +114,8 @@ fn decode_user_chat() {
     use User;
 
     let blob = r#"{"first_name":"test","id":123456789,"username":"test","type":"private"}"#;
-    let chat: Chat = json::decode(&blob).unwrap();
-    json::decode::<User>(&blob).unwrap();
+    let chat: Chat = json::from_str(&blob).unwrap();
+    json::from_str::<User>(&blob).unwrap();
 
     assert!(chat.is_user());
 }
@@ -117,7 +141,7 @@ fn decode_update() {
         },
         "update_id" : 123456789
     }"#;
-    let _: Update = json::decode(&blob).unwrap();
+    let _: Update = json::from_str(&blob).unwrap();
 }
 
 #[test]
@@ -169,7 +193,7 @@ fn decode_get_updates_response() {
         "ok" : true
     }"#;
 
-    let _: Response<Vec<Update>> = json::decode(&blob).unwrap();
+    let _: Response<Vec<Update>> = json::from_str(&blob).unwrap();
 }
 
 #[test]
@@ -202,6 +226,729 @@ fn test_handle_unknown_message_type() {
         ],
         "ok" : true
     }"#;
-    let response: Response<Vec<Update>> = json::decode(&blob).unwrap();
+    let response: Response<Vec<Update>> = json::from_str(&blob).unwrap();
     assert_eq!(response.result.unwrap().remove(0).message.unwrap().msg, MessageType::Unknown);
 }
+
+#[test]
+fn chat_ref() {
+    use {Chat, ChatId, ChatRef, User, UserId};
+
+    assert_eq!(ChatRef::from(ChatId(-12345678)), ChatRef::Id(ChatId(-12345678)));
+    assert_eq!(ChatRef::from(-12345678), ChatRef::Id(ChatId(-12345678)));
+    assert_eq!(ChatRef::from("foo"), ChatRef::Username("@foo".into()));
+    assert_eq!(ChatRef::from("@foo"), ChatRef::Username("@foo".into()));
+    assert_eq!(ChatRef::from(String::from("foo")), ChatRef::Username("@foo".into()));
+
+    let user = User {
+        id: UserId(123456789),
+        first_name: "test".into(),
+        last_name: None,
+        username: None,
+    };
+    assert_eq!(ChatRef::from(&user), ChatRef::Id(ChatId(123456789)));
+
+    let blob = r#"{"title":"This is a channel","id":-12345678,"type":"channel","username":"foo"}"#;
+    let chat: Chat = json::from_str(&blob).unwrap();
+    assert_eq!(ChatRef::from(&chat), ChatRef::Id(ChatId(-12345678)));
+
+    assert_eq!(ChatRef::Id(ChatId(-12345678)).to_string(), "-12345678");
+    assert_eq!(ChatRef::Username("@foo".into()).to_string(), "@foo");
+}
+
+#[test]
+fn decode_ids() {
+    use {ChatId, FileId, Message, MessageId, PhotoSize, UserId};
+
+    let blob = r#"{
+        "message_id" : 74,
+        "from" : {"first_name" : "test", "id" : 123456789},
+        "date" : 1437821492,
+        "chat" : {"first_name" : "test", "id" : 123456789, "type" : "private"},
+        "photo" : [{"file_id" : "AgADBAAD", "width" : 90, "height" : 51}]
+    }"#;
+    let message: Message = json::from_str(&blob).unwrap();
+    assert_eq!(message.message_id, MessageId(74));
+    assert_eq!(message.from.id, UserId(123456789));
+    assert_eq!(message.chat.id(), ChatId::from(message.from.id));
+    assert_eq!(message.msg, ::MessageType::Photo(vec![PhotoSize {
+        file_id: FileId::from("AgADBAAD"),
+        width: 90,
+        height: 51,
+        file_size: None,
+    }]));
+
+    // Ids are encoded as plain values
+    assert_eq!(json::to_string(&message.from).unwrap(),
+        r#"{"id":123456789,"first_name":"test"}"#.to_string());
+    assert_eq!(MessageId(74).to_string(), "74");
+}
+
+#[test]
+fn encode_chats() {
+    use {Chat, ChatId};
+
+    let private = Chat::Private {
+        id: ChatId(42),
+        first_name: "Alice".into(),
+        last_name: Some("Liddell".into()),
+        username: Some("alice".into()),
+    };
+    assert_eq!(json::to_string(&private).unwrap(),
+        r#"{"type":"private","id":42,"first_name":"Alice","last_name":"Liddell","username":"alice"}"#);
+
+    let channel = Chat::Channel {
+        id: ChatId(-1001),
+        title: "News".into(),
+        name: Some("news".into()),
+    };
+    assert_eq!(json::to_string(&channel).unwrap(),
+        r#"{"type":"channel","id":-1001,"title":"News","username":"news"}"#);
+
+    // Public supergroups have a username
+    let supergroup = Chat::Group {
+        id: ChatId(-1002),
+        title: "Chat".into(),
+        is_supergroup: true,
+        username: Some("chat".into()),
+    };
+    assert_eq!(json::to_string(&supergroup).unwrap(),
+        r#"{"type":"supergroup","id":-1002,"title":"Chat","username":"chat"}"#);
+
+    round_trip(&private);
+    round_trip(&channel);
+    round_trip(&supergroup);
+    round_trip(&Chat::Private {
+        id: ChatId(42),
+        first_name: "Alice".into(),
+        last_name: None,
+        username: None,
+    });
+    round_trip(&Chat::Group {
+        id: ChatId(-1),
+        title: "Group".into(),
+        is_supergroup: false,
+        username: None,
+    });
+    round_trip(&Chat::Group {
+        id: ChatId(-2),
+        title: "Super".into(),
+        is_supergroup: true,
+        username: None,
+    });
+    round_trip(&Chat::Channel { id: ChatId(-3), title: "Channel".into(), name: None });
+}
+
+#[test]
+fn round_trip_messages() {
+    use {Audio, Chat, ChatId, Contact, Dice, Document, FileId, GroupToSuperGroupMigration,
+         Location, MaskPoint, MaskPosition, Message, MessageId, MessageType, PhotoSize,
+         Sticker, StickerKind, Update, UpdateId, User, UserId, Venue, Video, VideoChatEnded,
+         Voice};
+
+    let user = User {
+        id: UserId(42),
+        first_name: "Alice".into(),
+        last_name: None,
+        username: Some("alice".into()),
+    };
+    let photo = PhotoSize {
+        file_id: FileId::from("photo"),
+        width: 90,
+        height: 51,
+        file_size: Some(1024),
+    };
+    let original = Message {
+        message_id: MessageId(1),
+        from: user.clone(),
+        chat: Chat::Group {
+            id: ChatId(-1),
+            title: "Group".into(),
+            is_supergroup: false,
+            username: None,
+        },
+        date: 1437821492,
+        forward: None,
+        reply: None,
+        msg: MessageType::Text("Original".into()),
+        caption: None,
+        media_group_id: None,
+    };
+
+    let types = vec![
+        MessageType::Text("Hi".into()),
+        MessageType::Audio(Audio {
+            file_id: FileId::from("audio"),
+            duration: 120,
+            performer: Some("Band".into()),
+            title: None,
+            mime_type: Some("audio/mpeg".into()),
+            file_size: None,
+        }),
+        MessageType::Voice(Voice {
+            file_id: FileId::from("voice"),
+            duration: 3,
+            mime_type: None,
+            file_size: Some(100),
+        }),
+        MessageType::File(Document {
+            file_id: FileId::from("document"),
+            thumb: Some(photo.clone()),
+            file_name: Some("a.pdf".into()),
+            mime_type: None,
+            file_size: None,
+        }),
+        MessageType::Photo(vec![photo.clone(), photo.clone()]),
+        MessageType::Sticker(Sticker {
+            file_id: FileId::from("sticker"),
+            width: 512,
+            height: 512,
+            kind: StickerKind::Mask,
+            is_animated: false,
+            is_video: true,
+            thumb: None,
+            emoji: Some("😎".into()),
+            set_name: Some("masks_by_fake_bot".into()),
+            mask_position: Some(MaskPosition {
+                point: MaskPoint::Eyes,
+                x_shift: -0.5,
+                y_shift: 0.25,
+                scale: 2.0,
+            }),
+            file_size: None,
+        }),
+        MessageType::Video(Video {
+            file_id: FileId::from("video"),
+            width: 640,
+            height: 480,
+            duration: 10,
+            thumb: Some(photo.clone()),
+            mime_type: None,
+            file_size: None,
+        }),
+        MessageType::Contact(Contact {
+            phone_number: "+49123".into(),
+            first_name: "Bob".into(),
+            last_name: None,
+            user_id: Some(UserId(7)),
+        }),
+        MessageType::Location(Location { longitude: 13.4, latitude: 52.52 }),
+        MessageType::NewChatParticipant(user.clone()),
+        MessageType::LeftChatParticipant(user.clone()),
+        MessageType::NewChatTitle("New title".into()),
+        MessageType::NewChatPhoto(vec![photo.clone()]),
+        MessageType::DeleteChatPhoto,
+        MessageType::GroupChatCreated,
+        MessageType::SuperGroupChatCreated(GroupToSuperGroupMigration {
+            from: ChatId(-1),
+            to: ChatId(-1001),
+        }),
+        MessageType::ChannelChatCreated,
+        MessageType::MigrateToChatId(ChatId(-1001)),
+        MessageType::MigrateFromChatId(ChatId(-1)),
+        MessageType::Dice(Dice { emoji: "🎲".into(), value: 3 }),
+        MessageType::Venue(Venue {
+            location: Location { longitude: 13.4, latitude: 52.52 },
+            title: "Venue".into(),
+            address: "Street 1".into(),
+            foursquare_id: Some("4b".into()),
+            foursquare_type: None,
+            google_place_id: None,
+            google_place_type: None,
+        }),
+        MessageType::PinnedMessage(Box::new(original.clone())),
+        MessageType::NewChatMembers(vec![user.clone()]),
+        MessageType::ForumTopicClosed,
+        MessageType::VideoChatStarted,
+        MessageType::VideoChatEnded(VideoChatEnded { duration: 60 }),
+        MessageType::Unknown,
+    ];
+
+    for msg in types {
+        let message = Message {
+            message_id: MessageId(2),
+            forward: Some((user.clone(), 1437821000)),
+            reply: Some(Box::new(original.clone())),
+            msg: msg,
+            caption: Some("Caption".into()),
+            media_group_id: Some("13571357135713571".into()),
+            ..original.clone()
+        };
+        round_trip(&message);
+        round_trip(&Update {
+            update_id: UpdateId(3),
+            message: Some(message),
+            poll: None,
+            poll_answer: None,
+            shipping_query: None,
+            pre_checkout_query: None,
+        });
+    }
+    round_trip(&Update {
+        update_id: UpdateId(4),
+        message: None,
+        poll: None,
+        poll_answer: None,
+        shipping_query: None,
+        pre_checkout_query: None,
+    });
+}
+
+#[test]
+fn decode_venue() {
+    use {Location, MessageType, Venue};
+
+    // Venues are sent with their location, too
+    let msg = decode_message_type(r#"
+        "location": {"latitude": 52.5163, "longitude": 13.3777},
+        "venue": {
+            "location": {"latitude": 52.5163, "longitude": 13.3777},
+            "title": "Brandenburger Tor",
+            "address": "Pariser Platz",
+            "google_place_id": "ChIJiQnyVcZRqEcRY0xnhE77uyY"
+        }"#);
+    assert_eq!(msg, MessageType::Venue(Venue {
+        location: Location { latitude: 52.5163, longitude: 13.3777 },
+        title: "Brandenburger Tor".into(),
+        address: "Pariser Platz".into(),
+        foursquare_id: None,
+        foursquare_type: None,
+        google_place_id: Some("ChIJiQnyVcZRqEcRY0xnhE77uyY".into()),
+        google_place_type: None,
+    }));
+}
+
+#[test]
+fn decode_animation() {
+    use {FileId, MessageType};
+
+    // Animations are sent with a document, too
+    let msg = decode_message_type(r#"
+        "animation": {"file_id": "CgADBAAD", "width": 320, "height": 240, "duration": 3,
+                      "file_name": "cat.mp4", "mime_type": "video/mp4"},
+        "document": {"file_id": "CgADBAAD", "file_name": "cat.mp4", "mime_type": "video/mp4"}"#);
+    match msg {
+        MessageType::Animation(a) => {
+            assert_eq!(a.file_id, FileId::from("CgADBAAD"));
+            assert_eq!((a.width, a.height, a.duration), (320, 240, 3));
+            assert_eq!(a.file_name, Some("cat.mp4".into()));
+        }
+        other => panic!("unexpected message type: {:?}", other),
+    }
+}
+
+#[test]
+fn decode_video_note() {
+    use {FileId, MessageType, VideoNote};
+
+    let msg = decode_message_type(r#"
+        "video_note": {"file_id": "DQADBAAD", "length": 240, "duration": 5}"#);
+    assert_eq!(msg, MessageType::VideoNote(VideoNote {
+        file_id: FileId::from("DQADBAAD"),
+        length: 240,
+        duration: 5,
+        thumb: None,
+        file_size: None,
+    }));
+}
+
+#[test]
+fn decode_dice() {
+    use {Dice, MessageType};
+
+    let msg = decode_message_type(r#""dice": {"emoji": "🎯", "value": 6}"#);
+    assert_eq!(msg, MessageType::Dice(Dice { emoji: "🎯".into(), value: 6 }));
+}
+
+#[test]
+fn decode_poll() {
+    use {MessageType, PollKind, PollOption};
+
+    let msg = decode_message_type(r#"
+        "poll": {
+            "id": "5386123875633299457",
+            "question": "2 + 2?",
+            "options": [{"text": "4", "voter_count": 2}, {"text": "5", "voter_count": 1}],
+            "total_voter_count": 3,
+            "is_closed": false,
+            "is_anonymous": true,
+            "type": "quiz",
+            "allows_multiple_answers": false,
+            "correct_option_id": 0
+        }"#);
+    match msg {
+        MessageType::Poll(poll) => {
+            assert_eq!(poll.kind, PollKind::Quiz);
+            assert_eq!(poll.options[0], PollOption { text: "4".into(), voter_count: 2 });
+            assert_eq!(poll.total_voter_count, 3);
+            assert_eq!(poll.correct_option_id, Some(0));
+            assert_eq!(poll.explanation, None);
+        }
+        other => panic!("unexpected message type: {:?}", other),
+    }
+}
+
+#[test]
+fn decode_poll_updates() {
+    use {MessageEntityKind, Update, UserId};
+
+    let update: Update = json::from_str(r#"{
+        "update_id": 10,
+        "poll": {
+            "id": "5386123875633299457",
+            "question": "2 + 2?",
+            "options": [{"text": "4", "voter_count": 1}, {"text": "5", "voter_count": 0}],
+            "total_voter_count": 1,
+            "is_closed": true,
+            "is_anonymous": false,
+            "type": "quiz",
+            "allows_multiple_answers": false,
+            "correct_option_id": 0,
+            "explanation": "Count again",
+            "explanation_entities": [
+                {"type": "bold", "offset": 0, "length": 5},
+                {"type": "text_link", "offset": 6, "length": 5, "url": "https://example.com"},
+                {"type": "some_new_kind", "offset": 0, "length": 1}
+            ]
+        }
+    }"#).unwrap();
+    let entities = update.poll.unwrap().explanation_entities.unwrap();
+    assert_eq!(entities[0].kind, MessageEntityKind::Bold);
+    assert_eq!(entities[1].kind, MessageEntityKind::TextLink);
+    assert_eq!(entities[1].url, Some("https://example.com".into()));
+    assert_eq!(entities[2].kind, MessageEntityKind::Unknown);
+
+    let update: Update = json::from_str(r#"{
+        "update_id": 11,
+        "poll_answer": {
+            "poll_id": "5386123875633299457",
+            "user": {"id": 123456789, "first_name": "test"},
+            "option_ids": [0]
+        }
+    }"#).unwrap();
+    let answer = update.poll_answer.unwrap();
+    assert_eq!(answer.user.unwrap().id, UserId(123456789));
+    assert_eq!(answer.option_ids, vec![0]);
+    assert!(update.message.is_none());
+}
+
+#[test]
+fn decode_game() {
+    use MessageType;
+
+    let msg = decode_message_type(r#"
+        "game": {
+            "title": "Tetris",
+            "description": "Stack the blocks",
+            "photo": [{"file_id": "AgADBAAD", "width": 640, "height": 360}]
+        }"#);
+    match msg {
+        MessageType::Game(game) => {
+            assert_eq!(game.title, "Tetris");
+            assert_eq!(game.photo.len(), 1);
+            assert_eq!(game.animation, None);
+        }
+        other => panic!("unexpected message type: {:?}", other),
+    }
+}
+
+#[test]
+fn decode_payments() {
+    use {Invoice, MessageType, SuccessfulPayment};
+
+    let msg = decode_message_type(r#"
+        "invoice": {"title": "Coffee", "description": "A cup of coffee",
+                    "start_parameter": "coffee", "currency": "EUR", "total_amount": 250}"#);
+    assert_eq!(msg, MessageType::Invoice(Invoice {
+        title: "Coffee".into(),
+        description: "A cup of coffee".into(),
+        start_parameter: "coffee".into(),
+        currency: "EUR".into(),
+        total_amount: 250,
+    }));
+
+    let msg = decode_message_type(r#"
+        "successful_payment": {"currency": "XTR", "total_amount": 50,
+                               "invoice_payload": "order-1",
+                               "telegram_payment_charge_id": "stxAbC",
+                               "provider_payment_charge_id": ""}"#);
+    assert_eq!(msg, MessageType::SuccessfulPayment(SuccessfulPayment {
+        currency: "XTR".into(),
+        total_amount: 50,
+        invoice_payload: "order-1".into(),
+        shipping_option_id: None,
+        order_info: None,
+        telegram_payment_charge_id: "stxAbC".into(),
+        provider_payment_charge_id: "".into(),
+    }));
+
+    let msg = decode_message_type(r#"
+        "refunded_payment": {"currency": "XTR", "total_amount": 50,
+                             "invoice_payload": "order-1",
+                             "telegram_payment_charge_id": "stxAbC"}"#);
+    match msg {
+        MessageType::RefundedPayment(refund) => {
+            assert_eq!(refund.telegram_payment_charge_id, "stxAbC");
+            assert_eq!(refund.provider_payment_charge_id, None);
+        }
+        other => panic!("unexpected message type: {:?}", other),
+    }
+}
+
+#[test]
+fn decode_payment_queries() {
+    use Update;
+
+    let update: Update = json::from_str(r#"{
+        "update_id": 12,
+        "shipping_query": {
+            "id": "1",
+            "from": {"id": 123456789, "first_name": "test"},
+            "invoice_payload": "order-1",
+            "shipping_address": {"country_code": "DE", "state": "", "city": "Berlin",
+                                 "street_line1": "Pariser Platz 1", "street_line2": "",
+                                 "post_code": "10117"}
+        }
+    }"#).unwrap();
+    let query = update.shipping_query.unwrap();
+    assert_eq!(query.shipping_address.city, "Berlin");
+    round_trip(&query);
+
+    let update: Update = json::from_str(r#"{
+        "update_id": 13,
+        "pre_checkout_query": {
+            "id": "2",
+            "from": {"id": 123456789, "first_name": "test"},
+            "currency": "EUR",
+            "total_amount": 1250,
+            "invoice_payload": "order-1",
+            "shipping_option_id": "dhl",
+            "order_info": {"name": "Test", "email": "test@example.com"}
+        }
+    }"#).unwrap();
+    let query = update.pre_checkout_query.unwrap();
+    assert_eq!(query.shipping_option_id, Some("dhl".into()));
+    let order = query.order_info.unwrap();
+    assert_eq!(order.email, Some("test@example.com".into()));
+    assert_eq!(order.shipping_address, None);
+}
+
+#[test]
+fn decode_story() {
+    use {ChatId, MessageType};
+
+    let msg = decode_message_type(r#"
+        "story": {"chat": {"id": -1001, "title": "News", "type": "channel"}, "id": 17}"#);
+    match msg {
+        MessageType::Story(story) => {
+            assert_eq!(story.chat.id(), ChatId(-1001));
+            assert_eq!(story.id, 17);
+        }
+        other => panic!("unexpected message type: {:?}", other),
+    }
+}
+
+#[test]
+fn decode_pinned_message() {
+    use {MessageId, MessageType};
+
+    let msg = decode_message_type(r#"
+        "pinned_message": {
+            "message_id": 70,
+            "from": {"first_name": "test", "id": 123456789},
+            "date": 1437821000,
+            "chat": {"first_name": "test", "id": 123456789, "type": "private"},
+            "text": "Pin me"
+        }"#);
+    match msg {
+        MessageType::PinnedMessage(pinned) => {
+            assert_eq!(pinned.message_id, MessageId(70));
+            assert_eq!(pinned.msg, MessageType::Text("Pin me".into()));
+        }
+        other => panic!("unexpected message type: {:?}", other),
+    }
+}
+
+#[test]
+fn decode_new_chat_members() {
+    use {MessageType, UserId};
+
+    // Telegram sends the first new member in "new_chat_participant", too
+    let msg = decode_message_type(r#"
+        "new_chat_participant": {"first_name": "A", "id": 1},
+        "new_chat_member": {"first_name": "A", "id": 1},
+        "new_chat_members": [{"first_name": "A", "id": 1}, {"first_name": "B", "id": 2}]"#);
+    match msg {
+        MessageType::NewChatMembers(users) => {
+            let ids: Vec<_> = users.iter().map(|u| u.id).collect();
+            assert_eq!(ids, vec![UserId(1), UserId(2)]);
+        }
+        other => panic!("unexpected message type: {:?}", other),
+    }
+
+    let msg = decode_message_type(r#""new_chat_participant": {"first_name": "A", "id": 1}"#);
+    assert!(if let MessageType::NewChatParticipant(_) = msg { true } else { false });
+}
+
+#[test]
+fn decode_migration() {
+    use {ChatId, MessageType};
+
+    let msg = decode_message_type(r#""migrate_to_chat_id": -1001234"#);
+    assert_eq!(msg, MessageType::MigrateToChatId(ChatId(-1001234)));
+
+    let msg = decode_message_type(r#""migrate_from_chat_id": -1234"#);
+    assert_eq!(msg, MessageType::MigrateFromChatId(ChatId(-1234)));
+}
+
+#[test]
+fn decode_service_messages() {
+    use {MessageAutoDeleteTimerChanged, MessageType, WebAppData};
+
+    let msg = decode_message_type(
+        r#""message_auto_delete_timer_changed": {"message_auto_delete_time": 86400}"#);
+    assert_eq!(msg, MessageType::MessageAutoDeleteTimerChanged(MessageAutoDeleteTimerChanged {
+        message_auto_delete_time: 86400,
+    }));
+
+    let msg = decode_message_type(
+        r#""web_app_data": {"data": "{\"size\":2}", "button_text": "Order"}"#);
+    assert_eq!(msg, MessageType::WebAppData(WebAppData {
+        data: r#"{"size":2}"#.into(),
+        button_text: "Order".into(),
+    }));
+
+    let msg = decode_message_type(r#""connected_website": "example.com""#);
+    assert_eq!(msg, MessageType::ConnectedWebsite("example.com".into()));
+
+    let msg = decode_message_type(r#"
+        "proximity_alert_triggered": {"traveler": {"first_name": "A", "id": 1},
+                                      "watcher": {"first_name": "B", "id": 2},
+                                      "distance": 42}"#);
+    match msg {
+        MessageType::ProximityAlertTriggered(alert) => assert_eq!(alert.distance, 42),
+        other => panic!("unexpected message type: {:?}", other),
+    }
+}
+
+#[test]
+fn decode_forum_topics() {
+    use {ForumTopicCreated, ForumTopicEdited, MessageType};
+
+    let msg = decode_message_type(
+        r#""forum_topic_created": {"name": "Support", "icon_color": 7322096}"#);
+    assert_eq!(msg, MessageType::ForumTopicCreated(ForumTopicCreated {
+        name: "Support".into(),
+        icon_color: 7322096,
+        icon_custom_emoji_id: None,
+    }));
+
+    let msg = decode_message_type(r#""forum_topic_edited": {"name": "Help"}"#);
+    assert_eq!(msg, MessageType::ForumTopicEdited(ForumTopicEdited {
+        name: Some("Help".into()),
+        icon_custom_emoji_id: None,
+    }));
+
+    assert_eq!(decode_message_type(r#""forum_topic_closed": {}"#),
+               MessageType::ForumTopicClosed);
+    assert_eq!(decode_message_type(r#""forum_topic_reopened": {}"#),
+               MessageType::ForumTopicReopened);
+    assert_eq!(decode_message_type(r#""general_forum_topic_hidden": {}"#),
+               MessageType::GeneralForumTopicHidden);
+    assert_eq!(decode_message_type(r#""general_forum_topic_unhidden": {}"#),
+               MessageType::GeneralForumTopicUnhidden);
+}
+
+#[test]
+fn decode_video_chats() {
+    use {MessageType, VideoChatEnded, VideoChatScheduled};
+
+    assert_eq!(decode_message_type(r#""video_chat_scheduled": {"start_date": 1437821492}"#),
+               MessageType::VideoChatScheduled(VideoChatScheduled { start_date: 1437821492 }));
+    assert_eq!(decode_message_type(r#""video_chat_started": {}"#),
+               MessageType::VideoChatStarted);
+    assert_eq!(decode_message_type(r#""video_chat_ended": {"duration": 600}"#),
+               MessageType::VideoChatEnded(VideoChatEnded { duration: 600 }));
+}
+
+// Decodes the update in `fixtures/$file` (as sent by the Bot API) and checks
+// that its message has the expected type.
+#[cfg(test)]
+macro_rules! fixture {
+    ($file:expr, $pattern:pat) => { fixture!($file, $pattern if true) };
+    ($file:expr, $pattern:pat if $cond:expr) => {{
+        let update: ::Update = json::from_str(include_str!(concat!("fixtures/", $file)))
+            .expect(concat!("could not decode ", $file));
+        match update.message.expect(concat!("no message in ", $file)).msg {
+            $pattern if $cond => {},
+            other => panic!("{}: unexpected message type: {:?}", $file, other),
+        }
+    }}
+}
+
+#[test]
+fn decode_fixtures() {
+    use MessageType::*;
+
+    fixture!("animation.json", Animation(..));
+    fixture!("animation_with_caption.json", Animation(..));
+    fixture!("audio.json", Audio(..));
+    fixture!("document.json", File(..));
+    fixture!("game.json", Game(..));
+    fixture!("location.json", Location(..));
+    fixture!("new_chat_members.json", NewChatMembers(ref users) if users.len() == 2);
+    fixture!("paid_media.json", PaidMedia(..));
+    fixture!("photo_with_caption.json", Photo(ref sizes) if sizes.len() == 3);
+    fixture!("sticker.json", Sticker(::Sticker { thumb: Some(_), ref set_name, .. })
+             if set_name.as_ref().map(|n| &**n) == Some("HotCherry"));
+    fixture!("text_with_link_preview.json", Text(..));
+    fixture!("venue.json", Venue(..));
+    fixture!("video_note.json", VideoNote(..));
+    fixture!("video_with_caption.json", Video(::Video { thumb: Some(_), .. }));
+    fixture!("voice.json", Voice(..));
+}
+
+#[test]
+fn media_before_text() {
+    use {FileId, MessageType};
+
+    let msg = decode_message_type(r#"
+        "text": "ignored",
+        "photo": [{"file_id": "AgADBAAD", "width": 90, "height": 67}]"#);
+    match msg {
+        MessageType::Photo(sizes) => assert_eq!(sizes[0].file_id, FileId::from("AgADBAAD")),
+        other => panic!("unexpected message type: {:?}", other),
+    }
+
+    // There is no "file" field in the Bot API
+    let msg = decode_message_type(r#""file": {"file_id": "BQADBAAD"}"#);
+    assert_eq!(msg, MessageType::Unknown);
+}
+
+#[test]
+fn decode_sticker_set() {
+    use {MaskPoint, StickerKind, StickerSet};
+
+    let set: StickerSet = json::from_str(r#"{
+        "name": "masks_by_fake_bot",
+        "title": "Masks",
+        "sticker_type": "mask",
+        "stickers": [{
+            "file_id": "CAACAgIAAxkBAAIBTm",
+            "file_unique_id": "AgADugIAAiVvzFI",
+            "width": 512,
+            "height": 512,
+            "type": "mask",
+            "is_animated": true,
+            "is_video": false,
+            "set_name": "masks_by_fake_bot",
+            "mask_position": {"point": "chin", "x_shift": 0.1, "y_shift": -0.2, "scale": 1.5}
+        }],
+        "thumbnail": {"file_id": "AAMCAgADGQEAAgFJ", "width": 100, "height": 100}
+    }"#).unwrap();
+    assert_eq!(set.sticker_type, StickerKind::Mask);
+    assert!(set.stickers[0].is_animated);
+    assert_eq!(set.stickers[0].mask_position.as_ref().unwrap().point, MaskPoint::Chin);
+    assert_eq!(set.thumbnail.as_ref().unwrap().width, 100);
+    round_trip(&set);
+}
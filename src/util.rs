@@ -1,36 +1,187 @@
 use super::Result;
-use rustc_serialize::{json, Encodable};
+use types::FileId;
+use serde::Serialize;
+use serde_json;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
 
-// Type for managing GET and POST parameter
+/// A file to upload, e.g. the certificate for `SetWebhook`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct InputFile {
+    name: String,
+    data: Vec<u8>,
+}
+
+impl InputFile {
+    /// Creates a file with the given name and content.
+    pub fn new<N: Into<String>>(name: N, data: Vec<u8>) -> InputFile {
+        InputFile {
+            name: name.into(),
+            data: data,
+        }
+    }
+
+    /// Reads the file at the given path.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<InputFile> {
+        let path = path.as_ref();
+        let mut data = Vec::new();
+        try!(try!(File::open(path)).read_to_end(&mut data));
+
+        let name = path.file_name().map_or("file".into(), |n| n.to_string_lossy().into_owned());
+        Ok(InputFile::new(name, data))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+/// A file to send: Either one which is stored on the Telegram servers already,
+/// one Telegram downloads from a URL or a new upload.
+#[derive(Debug, PartialEq, Clone)]
+pub enum FileSource {
+    Id(FileId),
+    Url(String),
+    Upload(InputFile),
+}
+
+impl From<FileId> for FileSource {
+    fn from(id: FileId) -> FileSource {
+        FileSource::Id(id)
+    }
+}
+
+impl From<InputFile> for FileSource {
+    fn from(file: InputFile) -> FileSource {
+        FileSource::Upload(file)
+    }
+}
+
+/// Parameters of a request. Used by `Request::params`.
+///
+/// Plain values are converted with `ToString`, complex values (like
+/// `ReplyMarkup`) are encoded as JSON.
 pub struct Params<'a> {
     gets: Vec<(&'a str, String)>,
+    files: Vec<(String, InputFile)>,
 }
 
 impl<'a> Params<'a> {
+    /// Creates an empty parameter list.
     pub fn new() -> Params<'a> {
         Params {
             gets: Vec::new(),
+            files: Vec::new(),
         }
     }
 
+    /// Adds the parameter if `value` is `Some`.
     pub fn add_get_opt<T: ToString>(&mut self, key: &'a str, value: Option<T>) {
         if let Some(d) = value {
             self.gets.push((key, d.to_string()));
         }
     }
+
+    /// Adds the parameter.
     pub fn add_get<T: ToString>(&mut self, key: &'a str, value: T) {
         self.gets.push((key, value.to_string()));
     }
 
-    pub fn add_get_json_opt<T: Encodable>(&mut self,
+    /// Adds the JSON encoded value if `value` is `Some`.
+    pub fn add_get_json_opt<T: Serialize>(&mut self,
                             key: &'a str, value: Option<T>) -> Result<()> {
         if let Some(d) = value {
-            self.gets.push((key, try!(json::encode(&d))));
+            self.gets.push((key, try!(serde_json::to_string(&d))));
         }
         Ok(())
     }
 
+    /// Adds a file to upload. Requests with files are sent as
+    /// "multipart/form-data".
+    pub fn add_file<K: Into<String>>(&mut self, key: K, file: InputFile) {
+        self.files.push((key.into(), file));
+    }
+
+    /// Adds the file as parameter: Uploads with `add_file`, ids and URLs as
+    /// plain parameters.
+    pub fn add_file_source(&mut self, key: &'a str, file: &FileSource) {
+        match *file {
+            FileSource::Id(ref id) => self.add_get(key, id),
+            FileSource::Url(ref url) => self.add_get(key, url),
+            FileSource::Upload(ref upload) => self.add_file(key, upload.clone()),
+        }
+    }
+
+    /// Returns the value which refers to the file inside of a JSON
+    /// parameter (e.g. the stickers of a sticker set). Uploads are added
+    /// with the given name and referred to as "attach://<name>".
+    pub fn attach(&mut self, name: String, file: &FileSource) -> String {
+        match *file {
+            FileSource::Id(ref id) => id.to_string(),
+            FileSource::Url(ref url) => url.clone(),
+            FileSource::Upload(ref upload) => {
+                let reference = format!("attach://{}", name);
+                self.add_file(name, upload.clone());
+                reference
+            }
+        }
+    }
+
+    /// Returns all parameters in the order they were added.
     pub fn get_params(&self) -> &Vec<(&str, String)> {
         &self.gets
     }
+
+    /// Returns all files in the order they were added.
+    pub fn get_files(&self) -> &Vec<(String, InputFile)> {
+        &self.files
+    }
+
+    /// Encodes all parameters and files as "multipart/form-data" body with
+    /// the given boundary.
+    pub fn to_multipart(&self, boundary: &str) -> Vec<u8> {
+        let mut body = Vec::new();
+        for &(key, ref value) in &self.gets {
+            body.extend(format!("--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n",
+                                boundary, quote(key)).bytes());
+            body.extend(value.bytes());
+            body.extend(b"\r\n");
+        }
+        for &(ref key, ref file) in &self.files {
+            body.extend(format!("--{}\r\nContent-Disposition: form-data; name=\"{}\"; \
+                                 filename=\"{}\"\r\n\
+                                 Content-Type: application/octet-stream\r\n\r\n",
+                                boundary, quote(key), quote(&file.name)).bytes());
+            body.extend(&file.data);
+            body.extend(b"\r\n");
+        }
+        body.extend(format!("--{}--\r\n", boundary).bytes());
+        body
+    }
+}
+
+// Makes a string usable as quoted header parameter.
+fn quote(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"").replace('\r', "").replace('\n', "")
+}
+
+/// Opens (or creates) a file with one entry per line for appending. A
+/// partially written last line (e.g. after a crash) is cut off first, so
+/// the next entry starts on a line of its own.
+pub fn open_lines_for_append<P: AsRef<Path>>(path: P) -> Result<File> {
+    let mut file = try!(OpenOptions::new().read(true).write(true).create(true).open(path));
+    let mut content = Vec::new();
+    try!(file.read_to_end(&mut content));
+
+    let complete = content.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+    if complete < content.len() {
+        try!(file.set_len(complete as u64));
+    }
+    try!(file.seek(SeekFrom::End(0)));
+    Ok(file)
 }
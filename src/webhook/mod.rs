@@ -0,0 +1,375 @@
+//! Receiving updates via webhook.
+//!
+//! Instead of polling, Telegram can send every update to an HTTPS endpoint
+//! of the bot (see `requests::SetWebhook`). A `WebhookServer` is a small HTTP
+//! server which receives these requests and passes the updates to a handler,
+//! just like `Listener::listen`.
+//!
+//! Everybody who knows the URL of the endpoint could send fake updates, so
+//! requests should be verified: With a secret token (which has to be passed
+//! to `SetWebhook::secret_token` as well), requests without the matching
+//! "X-Telegram-Bot-Api-Secret-Token" header are rejected with "401
+//! Unauthorized". Additionally, the source addresses can be restricted, e.g.
+//! to the ranges used by Telegram (`Webhook::telegram_ips_only`); requests
+//! from other addresses are rejected with "403 Forbidden". Both checks happen
+//! before the request body is read. Bodies larger than `MAX_BODY_SIZE` are
+//! rejected with "413 Payload Too Large".
+//!
+//! The server only speaks plain HTTP, so it's usually run behind a reverse
+//! proxy terminating TLS. In this case, all requests come from the proxy and
+//! the address check has to be done by the proxy instead.
+//!
+//! ```no_run
+//! use telegram_bot::*;
+//! use telegram_bot::requests::SetWebhook;
+//! use telegram_bot::webhook::Webhook;
+//!
+//! let api = Api::from_env("TELEGRAM_BOT_TOKEN").unwrap();
+//! let url = "https://bot.example.com/telegram".parse().unwrap();
+//! api.send(SetWebhook::new(url).secret_token("s3cret")).unwrap();
+//!
+//! let mut server = Webhook::new()
+//!     .path("/telegram")
+//!     .secret_token("s3cret")
+//!     .bind("127.0.0.1:8080")
+//!     .unwrap();
+//!
+//! server.listen(|u| {
+//!     if let Some(m) = u.message {
+//!         try!(m.reply(&api, "Hi!"));
+//!     }
+//!     Ok(ListeningAction::Continue)
+//! }).unwrap();
+//! ```
+
+use std::fmt;
+use std::io::Read;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::str::FromStr;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+
+use hyper::header::ContentLength;
+use hyper::method::Method;
+use hyper::server::{Handler, Listening, Request, Response, Server};
+use hyper::status::StatusCode;
+use hyper::uri::RequestUri;
+use serde_json;
+
+use types::Update;
+use {Error, ListeningAction, Result};
+
+/// The largest request body (in bytes) the server accepts. Updates are much
+/// smaller, this just keeps the server from reading arbitrarily large bodies
+/// into memory.
+pub const MAX_BODY_SIZE: u64 = 1024 * 1024;
+
+/// The address ranges Telegram sends webhook requests from.
+pub const TELEGRAM_IP_RANGES: &'static [&'static str] = &["149.154.160.0/20", "91.108.4.0/22"];
+
+/// A range of IP addresses in CIDR notation, e.g. "149.154.160.0/20".
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct IpRange {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl IpRange {
+    /// Creates a range of all addresses sharing the first `prefix` bits with
+    /// `addr`. Returns `None` if the prefix is longer than the address.
+    pub fn new(addr: IpAddr, prefix: u8) -> Option<IpRange> {
+        let bits = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        if prefix > bits {
+            return None;
+        }
+
+        Some(IpRange {
+            addr: addr,
+            prefix: prefix,
+        })
+    }
+
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        match (self.addr, addr) {
+            (IpAddr::V4(range), IpAddr::V4(addr)) => {
+                let mask = (!0u32).checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(range) & mask == u32::from(addr) & mask
+            }
+            (IpAddr::V6(range), IpAddr::V6(addr)) => {
+                let mask = (!0u128).checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(range) & mask == u128::from(addr) & mask
+            }
+            // IPv4 addresses might be mapped into IPv6 ones
+            (IpAddr::V4(_), IpAddr::V6(addr)) => {
+                addr.to_ipv4().map_or(false, |addr| self.contains(IpAddr::V4(addr)))
+            }
+            (IpAddr::V6(_), IpAddr::V4(_)) => false,
+        }
+    }
+}
+
+impl FromStr for IpRange {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<IpRange> {
+        let invalid = || Error::InvalidIpRange(s.into());
+
+        let mut parts = s.splitn(2, '/');
+        let addr = try!(parts.next().unwrap_or("").parse().map_err(|_| invalid()));
+        let prefix = match parts.next() {
+            Some(prefix) => try!(prefix.parse().map_err(|_| invalid())),
+            None => match addr {
+                IpAddr::V4(_) => 32,
+                IpAddr::V6(_) => 128,
+            },
+        };
+
+        IpRange::new(addr, prefix).ok_or_else(invalid)
+    }
+}
+
+impl fmt::Display for IpRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+/// Configuration of a `WebhookServer`.
+#[derive(Debug, Clone, Default)]
+pub struct Webhook {
+    path: Option<String>,
+    secret_token: Option<String>,
+    allowed_ips: Vec<IpRange>,
+    threads: Option<usize>,
+}
+
+impl Webhook {
+    /// Creates a configuration accepting requests from everywhere.
+    pub fn new() -> Webhook {
+        Webhook::default()
+    }
+
+    /// Only accepts requests for the given path, e.g. "/telegram". Requests
+    /// for other paths are answered with "404 Not Found".
+    pub fn path<S: Into<String>>(mut self, path: S) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// Only accepts requests with the given token in the
+    /// "X-Telegram-Bot-Api-Secret-Token" header.
+    pub fn secret_token<S: Into<String>>(mut self, token: S) -> Self {
+        self.secret_token = Some(token.into());
+        self
+    }
+
+    /// Accepts requests from the given addresses. Can be called multiple
+    /// times; as long as it's never called, all addresses are accepted.
+    pub fn allow_ips(mut self, range: IpRange) -> Self {
+        self.allowed_ips.push(range);
+        self
+    }
+
+    /// Only accepts requests from the addresses used by Telegram (see
+    /// `TELEGRAM_IP_RANGES`).
+    pub fn telegram_ips_only(mut self) -> Self {
+        for range in TELEGRAM_IP_RANGES {
+            self.allowed_ips.push(range.parse().unwrap());
+        }
+        self
+    }
+
+    /// Number of threads receiving requests. Default: 4.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = Some(threads);
+        self
+    }
+
+    /// Starts the server on the given address. Updates are only accepted
+    /// while `WebhookServer::listen` is running, other requests are answered
+    /// with "503 Service Unavailable", so Telegram sends them again later.
+    pub fn bind<A: ToSocketAddrs>(self, addr: A) -> Result<WebhookServer> {
+        let threads = self.threads.unwrap_or(4);
+        let receiver = Arc::new(Mutex::new(None));
+        let handler = WebhookHandler {
+            config: self,
+            receiver: receiver.clone(),
+        };
+
+        let server = try!(Server::http(addr));
+        let listening = try!(server.handle_threads(handler, threads));
+        Ok(WebhookServer {
+            listening: listening,
+            receiver: receiver,
+        })
+    }
+}
+
+/// An update together with a channel to report the result of handling it.
+type Incoming = (Update, mpsc::Sender<bool>);
+
+/// A running webhook server, see `Webhook::bind`. The server is stopped when
+/// it's dropped.
+pub struct WebhookServer {
+    listening: Listening,
+    receiver: Arc<Mutex<Option<mpsc::Sender<Incoming>>>>,
+}
+
+impl WebhookServer {
+    /// The address the server is listening on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.listening.socket
+    }
+
+    /// Calls the given handler for every received update, like
+    /// `Listener::listen`.
+    ///
+    /// If the handler returns `Ok`, the request is answered with "200 OK".
+    /// If it returns an `Err`, the request is answered with "500 Internal
+    /// Server Error", so Telegram will send the update again later, and this
+    /// method returns the error. `Ok(ListeningAction::Stop)` stops listening
+    /// after answering the request.
+    pub fn listen<H>(&mut self, mut handler: H) -> Result<()>
+        where H: FnMut(Update) -> Result<ListeningAction>
+    {
+        let (tx, rx) = mpsc::channel();
+        *self.receiver.lock().unwrap() = Some(tx);
+
+        let mut result = Ok(());
+        for (update, handled) in rx.iter() {
+            match handler(update) {
+                Ok(action) => {
+                    let _ = handled.send(true);
+                    if let ListeningAction::Stop = action {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    let _ = handled.send(false);
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+
+        *self.receiver.lock().unwrap() = None;
+        result
+    }
+}
+
+impl Drop for WebhookServer {
+    fn drop(&mut self) {
+        // Dropping `Listening` would wait for the server threads forever.
+        let _ = self.listening.close();
+    }
+}
+
+struct WebhookHandler {
+    config: Webhook,
+    receiver: Arc<Mutex<Option<mpsc::Sender<Incoming>>>>,
+}
+
+impl WebhookHandler {
+    // Checks everything except the body.
+    fn verify(&self, req: &Request) -> ::std::result::Result<(), StatusCode> {
+        if req.method != Method::Post {
+            return Err(StatusCode::MethodNotAllowed);
+        }
+
+        if let Some(ref expected) = self.config.path {
+            let path = match req.uri {
+                RequestUri::AbsolutePath(ref p) => p.splitn(2, '?').next().unwrap_or(""),
+                _ => "",
+            };
+            if path != expected {
+                return Err(StatusCode::NotFound);
+            }
+        }
+
+        let ip = req.remote_addr.ip();
+        let allowed = &self.config.allowed_ips;
+        if !allowed.is_empty() && !allowed.iter().any(|range| range.contains(ip)) {
+            warn!("Rejected webhook request from {}", ip);
+            return Err(StatusCode::Forbidden);
+        }
+
+        if let Some(ref secret) = self.config.secret_token {
+            let token = req.headers.get_raw("X-Telegram-Bot-Api-Secret-Token")
+                .and_then(|values| values.first());
+            if !token.map_or(false, |token| constant_time_eq(token, secret.as_bytes())) {
+                warn!("Rejected webhook request with wrong secret token from {}", ip);
+                return Err(StatusCode::Unauthorized);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn receive(&self, req: &mut Request) -> StatusCode {
+        if let Err(status) = self.verify(req) {
+            return status;
+        }
+
+        if let Some(&ContentLength(length)) = req.headers.get() {
+            if length > MAX_BODY_SIZE {
+                return StatusCode::PayloadTooLarge;
+            }
+        }
+        // The body might be chunked or longer than announced
+        let mut body = Vec::new();
+        if req.take(MAX_BODY_SIZE + 1).read_to_end(&mut body).is_err() {
+            return StatusCode::BadRequest;
+        }
+        if body.len() as u64 > MAX_BODY_SIZE {
+            return StatusCode::PayloadTooLarge;
+        }
+        let update: Update = match serde_json::from_slice(&body) {
+            Ok(update) => update,
+            Err(e) => {
+                warn!("Received invalid update: {:?}", e);
+                return StatusCode::BadRequest;
+            }
+        };
+
+        let receiver = match *self.receiver.lock().unwrap() {
+            Some(ref receiver) => receiver.clone(),
+            None => return StatusCode::ServiceUnavailable,
+        };
+        let (tx, rx) = mpsc::channel();
+        if receiver.send((update, tx)).is_err() {
+            return StatusCode::ServiceUnavailable;
+        }
+
+        match rx.recv() {
+            Ok(true) => StatusCode::Ok,
+            Ok(false) => StatusCode::InternalServerError,
+            // Listening stopped before the update was handled
+            Err(_) => StatusCode::ServiceUnavailable,
+        }
+    }
+}
+
+impl Handler for WebhookHandler {
+    fn handle(&self, mut req: Request, mut res: Response) {
+        *res.status_mut() = self.receive(&mut req);
+        let _ = res.send(b"");
+    }
+}
+
+// Compares without stopping at the first difference, so the secret can't be
+// guessed by measuring response times.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+// ===========================================================================
+// Unit tests
+// ===========================================================================
+#[cfg(test)]
+mod test;
@@ -0,0 +1,120 @@
+use super::*;
+use hyper::client::Client;
+use hyper::header::Headers;
+use hyper::status::StatusCode;
+use std::net::SocketAddr;
+use std::thread;
+use std::time::Duration;
+use types::UpdateId;
+
+const UPDATE: &'static str = r#"{"update_id": 7, "message": {
+    "message_id": 1, "date": 0, "text": "hi",
+    "from": {"id": 42, "first_name": "Alice"},
+    "chat": {"id": 42, "type": "private", "first_name": "Alice"}}}"#;
+
+fn post(addr: SocketAddr, path: &str, token: Option<&str>, body: &str) -> StatusCode {
+    let mut headers = Headers::new();
+    if let Some(token) = token {
+        headers.set_raw("X-Telegram-Bot-Api-Secret-Token", vec![token.into()]);
+    }
+    let url = format!("http://{}{}", addr, path);
+    Client::new().post(&*url).headers(headers).body(body).send().unwrap().status
+}
+
+// Posts the update until `listen` was called.
+fn deliver(addr: SocketAddr, token: Option<&str>) -> StatusCode {
+    for _ in 0..100 {
+        match post(addr, "/hook", token, UPDATE) {
+            StatusCode::ServiceUnavailable => thread::sleep(Duration::from_millis(20)),
+            status => return status,
+        }
+    }
+    StatusCode::ServiceUnavailable
+}
+
+#[test]
+fn ip_ranges() {
+    let range: IpRange = "149.154.160.0/20".parse().unwrap();
+    assert!(range.contains("149.154.167.220".parse().unwrap()));
+    assert!(!range.contains("149.154.176.1".parse().unwrap()));
+    assert!(range.contains("::ffff:149.154.160.1".parse().unwrap()));
+
+    let single: IpRange = "2001:db8::1".parse().unwrap();
+    assert!(single.contains("2001:db8::1".parse().unwrap()));
+    assert!(!single.contains("2001:db8::2".parse().unwrap()));
+
+    assert!("10.0.0.0/33".parse::<IpRange>().is_err());
+    assert!("garbage".parse::<IpRange>().is_err());
+}
+
+#[test]
+fn verify_requests() {
+    let mut server = Webhook::new()
+        .path("/hook")
+        .secret_token("s3cret")
+        .bind("127.0.0.1:0")
+        .unwrap();
+    let addr = server.local_addr();
+
+    let client = thread::spawn(move || vec![
+        post(addr, "/hook", Some("wrong"), UPDATE),
+        post(addr, "/hook", None, UPDATE),
+        post(addr, "/other", Some("s3cret"), UPDATE),
+        post(addr, "/hook", Some("s3cret"), "not json"),
+        deliver(addr, Some("s3cret")),
+    ]);
+
+    let mut received = Vec::new();
+    server.listen(|u| {
+        received.push(u.update_id);
+        Ok(ListeningAction::Stop)
+    }).unwrap();
+
+    assert_eq!(received, vec![UpdateId(7)]);
+    assert_eq!(client.join().unwrap(), vec![
+        StatusCode::Unauthorized,
+        StatusCode::Unauthorized,
+        StatusCode::NotFound,
+        StatusCode::BadRequest,
+        StatusCode::Ok,
+    ]);
+
+    // Nobody is listening anymore
+    assert_eq!(post(addr, "/hook", Some("s3cret"), UPDATE), StatusCode::ServiceUnavailable);
+}
+
+#[test]
+fn reject_addresses() {
+    let server = Webhook::new()
+        .allow_ips("10.0.0.0/8".parse().unwrap())
+        .bind("127.0.0.1:0")
+        .unwrap();
+    assert_eq!(post(server.local_addr(), "/", None, UPDATE), StatusCode::Forbidden);
+}
+
+#[test]
+fn body_size() {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    let server = Webhook::new().bind("127.0.0.1:0").unwrap();
+    let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+    // Rejected without reading the body
+    write!(stream, "POST / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\
+                    Content-Length: {}\r\n\r\n",
+           MAX_BODY_SIZE + 1).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 413"), "unexpected response: {}", response);
+}
+
+#[test]
+fn handler_error() {
+    let mut server = Webhook::new().bind("127.0.0.1:0").unwrap();
+    let addr = server.local_addr();
+    let client = thread::spawn(move || deliver(addr, None));
+
+    // Telegram will send the update again
+    assert!(server.listen(|_| Err(Error::InvalidState("failed".into()))).is_err());
+    assert_eq!(client.join().unwrap(), StatusCode::InternalServerError);
+}